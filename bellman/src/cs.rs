@@ -175,7 +175,38 @@ pub enum SynthesisError {
     /// During verification, our verifying key was malformed.
     MalformedVerifyingKey,
     /// During CRS generation, we observed an unconstrained auxillary variable
-    UnconstrainedVariable
+    UnconstrainedVariable,
+    /// During verification, our verifying key contained an identity element
+    /// that would make part of the verification equation vacuous
+    SuspiciousVerifyingKey,
+    /// During synthesis, the witness value of the constant wire wasn't one
+    InvalidConstantWire,
+    /// During verification, the number of public inputs didn't match the verifying key
+    InputCountMismatch {
+        expected: usize,
+        got: usize
+    },
+    /// During proof generation, precomputed evaluations didn't match the
+    /// densities or assignments they were supplied with
+    EvaluationLengthMismatch,
+    /// During proof generation, the proving key's queries didn't line up
+    /// with the circuit's inputs and densities
+    InconsistentParameters,
+    /// During proof generation, the proving key turned out to be generated
+    /// for a different circuit, going by the circuit hash stored with it
+    CircuitHashMismatch,
+    /// During synthesis, the circuit had more constraints than the largest
+    /// radix-2 evaluation domain of the scalar field can hold
+    DomainTooLarge {
+        constraints: usize,
+        max_constraints: usize
+    },
+    /// During proof generation, multiexp windows of zero bases were requested
+    InvalidChunkSize,
+    /// During verification, a public input wasn't below the scalar field modulus
+    InputOutOfRange {
+        index: usize
+    }
 }
 
 impl From<io::Error> for SynthesisError {
@@ -195,7 +226,16 @@ impl SynthesisError {
             SynthesisError::UnexpectedIdentity => "encountered an identity element in the CRS",
             SynthesisError::IoError(_) => "encountered an I/O error",
             SynthesisError::MalformedVerifyingKey => "malformed verifying key",
-            SynthesisError::UnconstrainedVariable => "auxillary variable was unconstrained"
+            SynthesisError::UnconstrainedVariable => "auxillary variable was unconstrained",
+            SynthesisError::SuspiciousVerifyingKey => "verifying key contains an identity element",
+            SynthesisError::InvalidConstantWire => "constant wire of the witness is not one",
+            SynthesisError::InputCountMismatch { .. } => "wrong number of public inputs",
+            SynthesisError::EvaluationLengthMismatch => "evaluation lengths don't match the densities",
+            SynthesisError::InconsistentParameters => "proving key doesn't match the circuit",
+            SynthesisError::CircuitHashMismatch => "proving key was generated for a different circuit",
+            SynthesisError::DomainTooLarge { .. } => "too many constraints for the scalar field's two-adicity",
+            SynthesisError::InvalidChunkSize => "multiexp chunk size must be positive",
+            SynthesisError::InputOutOfRange { .. } => "public input is not below the field modulus"
         }
     }
 }
@@ -208,11 +248,26 @@ impl Error for SynthesisError {
 
 impl fmt::Display for SynthesisError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        if let &SynthesisError::IoError(ref e) = self {
-            write!(f, "I/O error: ")?;
-            e.fmt(f)
-        } else {
-            write!(f, "{}", self.self_description())
+        match *self {
+            SynthesisError::IoError(ref e) => {
+                write!(f, "I/O error: ")?;
+                e.fmt(f)
+            },
+            SynthesisError::InputCountMismatch { expected, got } => {
+                write!(f, "{}: expected {}, got {}", self.self_description(), expected, got)?;
+                if got < expected {
+                    // circom places outputs before inputs among public signals
+                    write!(f, " (public signals include circuit outputs, were they left out?)")?;
+                }
+                Ok(())
+            },
+            SynthesisError::DomainTooLarge { constraints, max_constraints } => {
+                write!(f, "{}: {} constraints, at most {} fit in an evaluation domain", self.self_description(), constraints, max_constraints)
+            },
+            SynthesisError::InputOutOfRange { index } => {
+                write!(f, "{}: input {}", self.self_description(), index)
+            },
+            _ => write!(f, "{}", self.self_description())
         }
     }
 }
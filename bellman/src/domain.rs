@@ -27,6 +27,21 @@ use super::{
 use super::worker::Worker;
 pub use super::group::*;
 
+/// Size of the evaluation domain for `num_constraints` constraints, i.e. the
+/// next power of two. `EvaluationDomain` supports at most `2^F::S - 1`
+/// coefficients, larger circuits can't be proven over this field.
+pub fn required_domain_size<F: PrimeField>(num_constraints: usize) -> Result<usize, SynthesisError> {
+    let max_constraints = (1usize << F::S) - 1;
+    if num_constraints > max_constraints {
+        return Err(SynthesisError::DomainTooLarge {
+            constraints: num_constraints,
+            max_constraints
+        });
+    }
+
+    Ok(num_constraints.next_power_of_two())
+}
+
 pub struct EvaluationDomain<E: Engine, G: Group<E>> {
     coeffs: Vec<G>,
     exp: u32,
@@ -190,7 +205,14 @@ impl<E: Engine, G: Group<E>> EvaluationDomain<E, G> {
 
     pub fn coset_fft(&mut self, worker: &Worker)
     {
-        self.distribute_powers(worker, E::Fr::multiplicative_generator());
+        self.coset_fft_with_generator(worker, E::Fr::multiplicative_generator());
+    }
+
+    /// Evaluates over the coset `g * domain` instead of the one shifted by
+    /// the field's multiplicative generator
+    pub fn coset_fft_with_generator(&mut self, worker: &Worker, g: E::Fr)
+    {
+        self.distribute_powers(worker, g);
         self.fft(worker);
     }
 
@@ -202,6 +224,16 @@ impl<E: Engine, G: Group<E>> EvaluationDomain<E, G> {
         self.distribute_powers(worker, geninv);
     }
 
+    /// Inverse of `coset_fft_with_generator` for the same `g`, which must be
+    /// nonzero
+    pub fn icoset_fft_with_generator(&mut self, worker: &Worker, g: E::Fr)
+    {
+        let ginv = g.inverse().expect("coset generator must be nonzero");
+
+        self.ifft(worker);
+        self.distribute_powers(worker, ginv);
+    }
+
     pub fn transform_powers_of_tau_into_lagrange_basis(&mut self, worker: &Worker) {
         self.ifft(&worker);
     }
@@ -238,7 +270,15 @@ impl<E: Engine, G: Group<E>> EvaluationDomain<E, G> {
     /// a coset.
     pub fn divide_by_z_on_coset(&mut self, worker: &Worker)
     {
-        let i = self.z(&E::Fr::multiplicative_generator()).inverse().unwrap();
+        self.divide_by_z_on_coset_with_generator(worker, E::Fr::multiplicative_generator())
+            .expect("the multiplicative generator is not a root of unity");
+    }
+
+    /// Same as `divide_by_z_on_coset` for evaluations over `g * domain`.
+    /// Fails if Z vanishes there, i.e. `g` is in the domain.
+    pub fn divide_by_z_on_coset_with_generator(&mut self, worker: &Worker, g: E::Fr) -> Result<(), SynthesisError>
+    {
+        let i = self.z(&g).inverse().ok_or(SynthesisError::DivisionByZero)?;
 
         worker.scope(self.coeffs.len(), |scope, chunk| {
             for v in self.coeffs.chunks_mut(chunk) {
@@ -249,6 +289,8 @@ impl<E: Engine, G: Group<E>> EvaluationDomain<E, G> {
                 });
             }
         });
+
+        Ok(())
     }
 
     /// Perform O(n) multiplication of two polynomials in the domain.
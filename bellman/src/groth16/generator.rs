@@ -18,6 +18,7 @@ use crate::pairing::ff::{
 
 use super::{
     Parameters,
+    PrepareOptions,
     VerifyingKey
 };
 
@@ -46,6 +47,18 @@ pub fn generate_random_parameters<E, C, R>(
     rng: &mut R
 ) -> Result<Parameters<E>, SynthesisError>
     where E: Engine, C: Circuit<E>, R: Rng
+{
+    generate_random_parameters_with_options(circuit, rng, PrepareOptions::default())
+}
+
+/// Same as `generate_random_parameters`, synthesizing `circuit` as set in
+/// `options`. Proofs have to be prepared with the same options.
+pub fn generate_random_parameters_with_options<E, C, R>(
+    circuit: C,
+    rng: &mut R,
+    options: PrepareOptions
+) -> Result<Parameters<E>, SynthesisError>
+    where E: Engine, C: Circuit<E>, R: Rng
 {
     let g1 = rng.gen();
     let g2 = rng.gen();
@@ -55,7 +68,7 @@ pub fn generate_random_parameters<E, C, R>(
     let delta = rng.gen();
     let tau = rng.gen();
 
-    generate_parameters::<E, C>(
+    generate_parameters_with_options::<E, C>(
         circuit,
         g1,
         g2,
@@ -63,7 +76,8 @@ pub fn generate_random_parameters<E, C, R>(
         beta,
         gamma,
         delta,
-        tau
+        tau,
+        options
     )
 }
 
@@ -186,6 +200,24 @@ pub fn generate_parameters<E, C>(
     tau: E::Fr
 ) -> Result<Parameters<E>, SynthesisError>
     where E: Engine, C: Circuit<E>
+{
+    generate_parameters_with_options(circuit, g1, g2, alpha, beta, gamma, delta, tau, PrepareOptions::default())
+}
+
+/// Same as `generate_parameters`, synthesizing `circuit` as set in `options`.
+/// Proofs have to be prepared with the same options.
+pub fn generate_parameters_with_options<E, C>(
+    circuit: C,
+    g1: E::G1,
+    g2: E::G2,
+    alpha: E::Fr,
+    beta: E::Fr,
+    gamma: E::Fr,
+    delta: E::Fr,
+    tau: E::Fr,
+    options: PrepareOptions
+) -> Result<Parameters<E>, SynthesisError>
+    where E: Engine, C: Circuit<E>
 {
     let mut assembly = KeypairAssembly {
         num_inputs: 0,
@@ -207,12 +239,14 @@ pub fn generate_parameters<E, C>(
 
     // Input constraints to ensure full density of IC query
     // x * 0 = 0
-    for i in 0..assembly.num_inputs {
-        assembly.enforce(|| "",
-            |lc| lc + Variable(Index::Input(i)),
-            |lc| lc,
-            |lc| lc,
-        );
+    if options.enforce_input_density {
+        for i in 0..assembly.num_inputs {
+            assembly.enforce(|| "",
+                |lc| lc + Variable(Index::Input(i)),
+                |lc| lc,
+                |lc| lc,
+            );
+        }
     }
 
     elog_verbose!("Making {} powers of tau", assembly.num_constraints);
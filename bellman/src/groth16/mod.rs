@@ -97,6 +97,50 @@ impl<E: Engine> Proof<E> {
     }
 }
 
+/// The three group elements of a `Proof`, for callers that compose
+/// proofs with other protocols (e.g. aggregation).
+#[derive(Debug, Clone)]
+pub struct ProofComponents<E: Engine> {
+    a: E::G1Affine,
+    b: E::G2Affine,
+    c: E::G1Affine
+}
+
+impl<E: Engine> ProofComponents<E> {
+    pub fn new(a: E::G1Affine, b: E::G2Affine, c: E::G1Affine) -> Self {
+        ProofComponents { a, b, c }
+    }
+
+    /// The A element, in G1.
+    pub fn a(&self) -> &E::G1Affine {
+        &self.a
+    }
+
+    /// The B element, in G2.
+    pub fn b(&self) -> &E::G2Affine {
+        &self.b
+    }
+
+    /// The C element, in G1.
+    pub fn c(&self) -> &E::G1Affine {
+        &self.c
+    }
+}
+
+pub fn proof_components<E: Engine>(proof: &Proof<E>) -> ProofComponents<E> {
+    ProofComponents::new(proof.a, proof.b, proof.c)
+}
+
+impl<E: Engine> Proof<E> {
+    pub fn from_components(components: ProofComponents<E>) -> Self {
+        Proof {
+            a: components.a,
+            b: components.b,
+            c: components.c
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct VerifyingKey<E: Engine> {
     // alpha in g1 for verifying and for creating A/C elements of
@@ -381,6 +425,7 @@ impl<E: Engine> Parameters<E> {
     }
 }
 
+#[derive(Clone)]
 pub struct PreparedVerifyingKey<E: Engine> {
     /// Pairing result of alpha*beta
     alpha_g1_beta_g2: E::Fqk,
@@ -388,10 +433,56 @@ pub struct PreparedVerifyingKey<E: Engine> {
     neg_gamma_g2: <E::G2Affine as CurveAffine>::Prepared,
     /// -delta in G2
     neg_delta_g2: <E::G2Affine as CurveAffine>::Prepared,
+    /// gamma and delta in G2 before preparation. Prepared points can't be
+    /// serialized, so these allow to rebuild them.
+    gamma_g2: E::G2Affine,
+    delta_g2: E::G2Affine,
     /// Copy of IC from `VerifiyingKey`.
     ic: Vec<E::G1Affine>
 }
 
+impl<E: Engine> PreparedVerifyingKey<E> {
+    /// Rebuilds a prepared verifying key from a precomputed alpha*beta pairing
+    /// without evaluating the pairing again.
+    pub fn from_parts(
+        alpha_g1_beta_g2: E::Fqk,
+        gamma_g2: E::G2Affine,
+        delta_g2: E::G2Affine,
+        ic: Vec<E::G1Affine>
+    ) -> Self
+    {
+        let mut neg_gamma = gamma_g2;
+        neg_gamma.negate();
+        let mut neg_delta = delta_g2;
+        neg_delta.negate();
+
+        PreparedVerifyingKey {
+            alpha_g1_beta_g2: alpha_g1_beta_g2,
+            neg_gamma_g2: neg_gamma.prepare(),
+            neg_delta_g2: neg_delta.prepare(),
+            gamma_g2: gamma_g2,
+            delta_g2: delta_g2,
+            ic: ic
+        }
+    }
+
+    pub fn alpha_g1_beta_g2(&self) -> &E::Fqk {
+        &self.alpha_g1_beta_g2
+    }
+
+    pub fn gamma_g2(&self) -> &E::G2Affine {
+        &self.gamma_g2
+    }
+
+    pub fn delta_g2(&self) -> &E::G2Affine {
+        &self.delta_g2
+    }
+
+    pub fn ic(&self) -> &[E::G1Affine] {
+        &self.ic
+    }
+}
+
 pub trait ParameterSource<E: Engine> {
     type G1Builder: SourceBuilder<E::G1Affine>;
     type G2Builder: SourceBuilder<E::G2Affine>;
@@ -456,9 +547,17 @@ impl<'a, E: Engine> ParameterSource<E> for &'a Parameters<E> {
     fn get_a(
         &mut self,
         num_inputs: usize,
-        _: usize
+        num_aux: usize
     ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError>
     {
+        // Every input gets an `input * 0 = 0` constraint, so its A polynomial
+        // is never zero and the A query holds a point for each of them; the
+        // prover relies on that by using full density for the inputs. A query
+        // of any other size would shift every aux base, so refuse it.
+        if self.a.len() != num_inputs + num_aux {
+            return Err(SynthesisError::InconsistentParameters);
+        }
+
         Ok(((self.a.clone(), 0), (self.a.clone(), num_inputs)))
     }
 
@@ -490,6 +589,22 @@ mod test_with_bls12_381 {
     use crate::pairing::ff::{Field};
     use crate::pairing::bls12_381::{Bls12, Fr};
 
+    #[test]
+    fn proof_components_round_trip() {
+        use crate::pairing::bls12_381::{G1Affine, G2Affine};
+
+        let proof = Proof::<Bls12> {
+            a: G1Affine::one(),
+            b: G2Affine::one(),
+            c: G1Affine::zero()
+        };
+        let components = proof_components(&proof);
+        assert!(*components.a() == proof.a);
+        assert!(*components.b() == proof.b);
+        assert!(*components.c() == proof.c);
+        assert!(Proof::from_components(components) == proof);
+    }
+
     #[test]
     fn serialization() {
         struct MySillyCircuit<E: Engine> {
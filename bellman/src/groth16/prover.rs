@@ -3,6 +3,7 @@ use crate::log::Stopwatch;
 use rand::Rng;
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::pairing::{CurveAffine, CurveProjective, Engine};
 
@@ -12,9 +13,9 @@ use super::{ParameterSource, Proof};
 
 use crate::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 
-use crate::domain::{EvaluationDomain, Scalar};
+use crate::domain::{required_domain_size, EvaluationDomain, Scalar};
 
-use crate::source::{DensityTracker, FullDensity};
+use crate::source::{DensityTracker, FullDensity, Source, SourceBuilder};
 
 use crate::multiexp::*;
 
@@ -60,7 +61,7 @@ fn eval<E: Engine>(
 
 pub(crate) fn field_elements_into_representations<E: Engine>(
     worker: &Worker,
-    scalars: Vec<E::Fr>,
+    scalars: &[E::Fr],
 ) -> Result<Vec<<E::Fr as PrimeField>::Repr>, SynthesisError> {
     let mut representations = vec![<E::Fr as PrimeField>::Repr::default(); scalars.len()];
     worker.scope(scalars.len(), |scope, chunk| {
@@ -94,17 +95,258 @@ pub(crate) fn scalars_into_representations<E: Engine>(
     Ok(representations)
 }
 
+/// Computes the coefficients of the quotient polynomial H = (A * B - C) / Z
+/// from the evaluations of A, B and C over the radix-2 domain. The result has
+/// one coefficient less than the domain, matching the size of the H query.
+pub fn compute_h_coeffs<E: Engine>(
+    a: Vec<Scalar<E>>,
+    b: Vec<Scalar<E>>,
+    c: Vec<Scalar<E>>,
+    worker: &Worker,
+) -> Result<Vec<E::Fr>, SynthesisError> {
+    compute_h_coeffs_with_generator(a, b, c, worker, E::Fr::multiplicative_generator())
+}
+
+/// Same as `compute_h_coeffs`, dividing by Z over the coset `g * domain`
+/// instead of the one shifted by the field's multiplicative generator. The
+/// coefficients don't depend on `g`, only the intermediate evaluations do,
+/// which is what other implementations can be compared on. Fails if `g` is
+/// in the domain.
+pub fn compute_h_coeffs_with_generator<E: Engine>(
+    a: Vec<Scalar<E>>,
+    b: Vec<Scalar<E>>,
+    c: Vec<Scalar<E>>,
+    worker: &Worker,
+    g: E::Fr,
+) -> Result<Vec<E::Fr>, SynthesisError> {
+    let mut a = EvaluationDomain::from_coeffs(a)?;
+    let mut b = EvaluationDomain::from_coeffs(b)?;
+    let mut c = EvaluationDomain::from_coeffs(c)?;
+    elog_verbose!("H query domain size is {}", a.as_ref().len());
+
+    // here a coset is a domain where denominator (z) does not vanish
+    // inverse FFT is an interpolation
+    a.ifft(&worker);
+    // evaluate in coset
+    a.coset_fft_with_generator(&worker, g);
+    // same is for B and C
+    b.ifft(&worker);
+    b.coset_fft_with_generator(&worker, g);
+    c.ifft(&worker);
+    c.coset_fft_with_generator(&worker, g);
+
+    // do A*B-C in coset
+    a.mul_assign(&worker, &b);
+    drop(b);
+    a.sub_assign(&worker, &c);
+    drop(c);
+    // z does not vanish in coset, so we divide by non-zero
+    a.divide_by_z_on_coset_with_generator(&worker, g)?;
+    // interpolate back in coset
+    a.icoset_fft_with_generator(&worker, g);
+    let mut a = a.into_coeffs();
+    let a_len = a.len() - 1;
+    a.truncate(a_len);
+
+    Ok(a.into_iter().map(|s| s.0).collect())
+}
+
 // This is a proving assignment with densities precalculated
+#[derive(Clone)]
 pub struct PreparedProver<E: Engine> {
     pub assignment: ProvingAssignment<E>,
 }
 
+/// Number of bases in each multiexp performed by `create_proof`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpCounts {
+    pub h: usize,
+    pub l: usize,
+    pub a_inputs: usize,
+    pub a_aux: usize,
+    pub b_g1_inputs: usize,
+    pub b_g1_aux: usize,
+    pub b_g2_inputs: usize,
+    pub b_g2_aux: usize,
+}
+
+fn op_counts(
+    num_constraints: usize,
+    a_aux_density: &DensityTracker,
+    b_input_density: &DensityTracker,
+    b_aux_density: &DensityTracker,
+    num_inputs: usize,
+    num_aux: usize,
+) -> OpCounts {
+    // H is a quotient polynomial over a radix-2 domain, so it has one
+    // coefficient less than the domain size
+    let domain_size = num_constraints.next_power_of_two();
+    let b_inputs = b_input_density.get_total_density();
+    let b_aux = b_aux_density.get_total_density();
+
+    OpCounts {
+        h: domain_size - 1,
+        l: num_aux,
+        a_inputs: num_inputs,
+        a_aux: a_aux_density.get_total_density(),
+        b_g1_inputs: b_inputs,
+        b_g1_aux: b_aux,
+        b_g2_inputs: b_inputs,
+        b_g2_aux: b_aux,
+    }
+}
+
+/// Phases of `create_proof`, reported to the `create_proof_with_progress`
+/// callback as each one starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofStage {
+    /// FFTs for the quotient polynomial and the multiexp over the H query
+    HEvaluation,
+    /// Converting the witness into scalar representations
+    InputAssignment,
+    L,
+    A,
+    BG1,
+    BG2,
+    /// Waiting for the multiexps and assembling the proof
+    Finalize,
+}
+
+/// Time spent in the phases of `create_proof`, as returned by
+/// `create_proof_timed`. All zero with the `nolog` feature, which compiles the
+/// prover's timers out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProofTimings {
+    /// FFTs for the quotient polynomial, up to starting the H multiexp
+    pub h_evaluation: Duration,
+    /// Converting the witness into scalar representations
+    pub input_representation: Duration,
+    /// The multiexps over the L, A and B queries, waiting for all multiexps
+    /// including H, and assembling the proof
+    pub point_multiplication: Duration,
+}
+
+/// Densities of the queries that depend on the circuit structure, as tracked
+/// by `ProvingAssignment`
+#[derive(Clone)]
+pub struct QueryDensities {
+    pub a_aux: DensityTracker,
+    pub b_input: DensityTracker,
+    pub b_aux: DensityTracker,
+}
+
+#[derive(Clone, Copy)]
+struct ProofOptions<'a, E: Engine> {
+    worker: &'a Worker,
+    // window size for the dense H and L multiexps, all at once if `None`
+    chunk_size: Option<usize>,
+    // shift of the coset the H polynomial is computed over
+    coset_generator: E::Fr,
+}
+
+impl<'a, E: Engine> ProofOptions<'a, E> {
+    fn new(worker: &'a Worker) -> Self {
+        ProofOptions {
+            worker,
+            chunk_size: None,
+            coset_generator: E::Fr::multiplicative_generator(),
+        }
+    }
+}
+
+/// A dense multiexp either still running on the worker or already summed up
+/// window by window
+enum DenseMultiexp<G: CurveProjective> {
+    Pending(ChunksJoiner<G>),
+    Done(G),
+}
+
+impl<G: CurveProjective> DenseMultiexp<G> {
+    fn wait(self) -> Result<G, SynthesisError> {
+        match self {
+            DenseMultiexp::Pending(joiner) => joiner.wait(),
+            DenseMultiexp::Done(result) => Ok(result),
+        }
+    }
+}
+
+/// Source builder starting `offset` bases into the wrapped one
+#[derive(Clone)]
+struct OffsetSourceBuilder<S> {
+    builder: S,
+    offset: usize,
+}
+
+struct OffsetSource<S> {
+    source: S,
+    // bases still to skip before the first one is read
+    pending: usize,
+}
+
+impl<G: CurveAffine, S: SourceBuilder<G>> SourceBuilder<G> for OffsetSourceBuilder<S> {
+    type Source = OffsetSource<S::Source>;
+
+    fn new(self) -> Self::Source {
+        OffsetSource {
+            source: self.builder.new(),
+            pending: self.offset,
+        }
+    }
+}
+
+impl<G: CurveAffine, S: Source<G>> Source<G> for OffsetSource<S> {
+    fn add_assign_mixed(
+        &mut self,
+        to: &mut <G as CurveAffine>::Projective,
+    ) -> Result<(), SynthesisError> {
+        if self.pending > 0 {
+            self.source.skip(self.pending)?;
+            self.pending = 0;
+        }
+        self.source.add_assign_mixed(to)
+    }
+
+    fn skip(&mut self, amt: usize) -> Result<(), SynthesisError> {
+        self.pending += amt;
+        Ok(())
+    }
+}
+
+/// Computes a FullDensity multiexp over the first `len` bases of `bases` in
+/// windows of `chunk_size`, waiting for each window before building the
+/// scalars of the next one with `window_scalars`
+fn chunked_dense_multiexp<G, S, F>(
+    worker: &Worker,
+    bases: S,
+    len: usize,
+    chunk_size: usize,
+    mut window_scalars: F,
+) -> Result<G::Projective, SynthesisError>
+where
+    G: CurveAffine,
+    S: SourceBuilder<G>,
+    F: FnMut(std::ops::Range<usize>) -> Result<Vec<<G::Scalar as PrimeField>::Repr>, SynthesisError>,
+{
+    let mut acc = G::Projective::zero();
+    for start in (0..len).step_by(chunk_size) {
+        let end = std::cmp::min(start + chunk_size, len);
+        let scalars = Arc::new(window_scalars(start..end)?);
+        let source = OffsetSourceBuilder {
+            builder: bases.clone(),
+            offset: start,
+        };
+        acc.add_assign(&multiexp(worker, source, FullDensity, scalars).wait()?);
+    }
+
+    Ok(acc)
+}
+
 #[derive(Clone)]
 pub struct ProvingAssignment<E: Engine> {
-    // Density of queries
-    pub a_aux_density: DensityTracker,
-    pub b_input_density: DensityTracker,
-    pub b_aux_density: DensityTracker,
+    // Density of queries, shared with the multiexps of every proof
+    pub a_aux_density: Arc<DensityTracker>,
+    pub b_input_density: Arc<DensityTracker>,
+    pub b_aux_density: Arc<DensityTracker>,
 
     // Evaluations of A, B, C polynomials
     a: Vec<Scalar<E>>,
@@ -114,38 +356,386 @@ pub struct ProvingAssignment<E: Engine> {
     // Assignments of variables
     input_assignment: Vec<E::Fr>,
     aux_assignment: Vec<E::Fr>,
+
+    // whether synthesis appends the `input * 0 = 0` constraints
+    enforce_input_density: bool,
+}
+
+/// How `prepare_prover_with_options` and `generate_parameters_with_options`
+/// synthesize a circuit. The defaults match `prepare_prover`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrepareOptions {
+    /// Append an `input * 0 = 0` constraint for every input. These keep the
+    /// A query dense in the inputs and the input polynomials linearly
+    /// independent, which soundness relies on. Only skip them if every input,
+    /// including the constant one, already appears in A in a way that keeps
+    /// them independent. Parameters and proofs must use the same setting.
+    pub enforce_input_density: bool,
+}
+
+impl Default for PrepareOptions {
+    fn default() -> Self {
+        PrepareOptions {
+            enforce_input_density: true,
+        }
+    }
 }
 
 pub fn prepare_prover<E, C>(circuit: C) -> Result<PreparedProver<E>, SynthesisError>
+where
+    E: Engine,
+    C: Circuit<E>,
+{
+    prepare_prover_with_options(circuit, PrepareOptions::default())
+}
+
+/// Same as `prepare_prover`, synthesizing `circuit` as set in `options`.
+/// `resynthesize` keeps using the same options.
+pub fn prepare_prover_with_options<E, C>(
+    circuit: C,
+    options: PrepareOptions,
+) -> Result<PreparedProver<E>, SynthesisError>
 where
     E: Engine,
     C: Circuit<E>,
 {
     let mut prover = ProvingAssignment {
-        a_aux_density: DensityTracker::new(),
-        b_input_density: DensityTracker::new(),
-        b_aux_density: DensityTracker::new(),
+        a_aux_density: Arc::new(DensityTracker::new()),
+        b_input_density: Arc::new(DensityTracker::new()),
+        b_aux_density: Arc::new(DensityTracker::new()),
         a: vec![],
         b: vec![],
         c: vec![],
         input_assignment: vec![],
         aux_assignment: vec![],
+        enforce_input_density: options.enforce_input_density,
     };
 
-    prover.alloc_input(|| "", || Ok(E::Fr::one()))?;
+    prover.synthesize_circuit(circuit)?;
+
+    let prepared = PreparedProver { assignment: prover };
 
-    circuit.synthesize(&mut prover)?;
+    return Ok(prepared);
+}
 
-    for i in 0..prover.input_assignment.len() {
-        prover.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
+impl<E: Engine> ProvingAssignment<E> {
+    /// Empties the assignment and the density trackers, keeping their
+    /// capacity so that the next synthesis doesn't have to grow them again.
+    /// Trackers still shared with a `ConstraintLayout` are copied first.
+    pub fn reset_and_reuse(&mut self) {
+        Arc::make_mut(&mut self.a_aux_density).clear();
+        Arc::make_mut(&mut self.b_input_density).clear();
+        Arc::make_mut(&mut self.b_aux_density).clear();
+        self.a.clear();
+        self.b.clear();
+        self.c.clear();
+        self.input_assignment.clear();
+        self.aux_assignment.clear();
     }
 
-    let prepared = PreparedProver { assignment: prover };
+    fn synthesize_circuit<C: Circuit<E>>(&mut self, circuit: C) -> Result<(), SynthesisError> {
+        self.alloc_input(|| "", || Ok(E::Fr::one()))?;
 
-    return Ok(prepared);
+        circuit.synthesize(self)?;
+
+        if self.enforce_input_density {
+            for i in 0..self.input_assignment.len() {
+                self.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
+            }
+        }
+
+        // fail before any of the expensive work if the FFTs can't be done
+        required_domain_size::<E::Fr>(self.a.len())?;
+
+        Ok(())
+    }
+}
+
+/// Constraints of a circuit recorded once, together with the query densities
+/// they imply. The densities only depend on which variables each constraint
+/// references, so proving another witness with `PreparedProver::from_layout`
+/// only re-evaluates A, B and C instead of running `synthesize` again.
+#[derive(Clone)]
+pub struct ConstraintLayout<E: Engine> {
+    // including the `input * 0 = 0` rows for every input, unless the options skip them
+    constraints: Vec<(LinearCombination<E>, LinearCombination<E>, LinearCombination<E>)>,
+    // shared with every `ProvingAssignment` built from the layout
+    a_aux_density: Arc<DensityTracker>,
+    b_input_density: Arc<DensityTracker>,
+    b_aux_density: Arc<DensityTracker>,
+    num_inputs: usize,
+    num_aux: usize,
+    // passed on to every `ProvingAssignment` built from the layout
+    enforce_input_density: bool,
+}
+
+impl<E: Engine> ConstraintLayout<E> {
+    /// Synthesizes `circuit` without calling any of its value closures, so a
+    /// circuit without a witness, like the one used for parameter generation,
+    /// can be recorded too.
+    pub fn new<C: Circuit<E>>(circuit: C) -> Result<Self, SynthesisError> {
+        Self::new_with_options(circuit, PrepareOptions::default())
+    }
+
+    /// Same as `new`, synthesizing `circuit` as set in `options`. Provers
+    /// built with `PreparedProver::from_layout` keep using the same options.
+    pub fn new_with_options<C: Circuit<E>>(
+        circuit: C,
+        options: PrepareOptions,
+    ) -> Result<Self, SynthesisError> {
+        let mut layout = ConstraintLayout {
+            constraints: vec![],
+            a_aux_density: Arc::new(DensityTracker::new()),
+            b_input_density: Arc::new(DensityTracker::new()),
+            b_aux_density: Arc::new(DensityTracker::new()),
+            num_inputs: 0,
+            num_aux: 0,
+            enforce_input_density: options.enforce_input_density,
+        };
+
+        layout.alloc_input(|| "", || Ok(E::Fr::one()))?;
+
+        circuit.synthesize(&mut layout)?;
+
+        if layout.enforce_input_density {
+            for i in 0..layout.num_inputs {
+                layout.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
+            }
+        }
+
+        Ok(layout)
+    }
+
+    /// Multiexp lengths that proving a witness of this circuit will use.
+    /// Unlike proving, this also works for circuits too large for the
+    /// evaluation domain.
+    pub fn operation_counts(&self) -> OpCounts {
+        op_counts(
+            self.constraints.len(),
+            &self.a_aux_density,
+            &self.b_input_density,
+            &self.b_aux_density,
+            self.num_inputs,
+            self.num_aux,
+        )
+    }
+
+    /// Number of input variables, including the constant one
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_aux(&self) -> usize {
+        self.num_aux
+    }
+
+    /// Number of constraints, including the rows added for the inputs unless
+    /// the options skip them
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+}
+
+impl<E: Engine> ConstraintSystem<E> for ConstraintLayout<E> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, _: A, _: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.num_aux += 1;
+        Arc::make_mut(&mut self.a_aux_density).add_element();
+        Arc::make_mut(&mut self.b_aux_density).add_element();
+
+        Ok(Variable(Index::Aux(self.num_aux - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _: A, _: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.num_inputs += 1;
+        Arc::make_mut(&mut self.b_input_density).add_element();
+
+        Ok(Variable(Index::Input(self.num_inputs - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+    {
+        let a = a(LinearCombination::zero());
+        let b = b(LinearCombination::zero());
+        let c = c(LinearCombination::zero());
+
+        // same densities as `ProvingAssignment` tracks: inputs are dense in
+        // the A query and there is no C query
+        let a_aux = Arc::make_mut(&mut self.a_aux_density);
+        for &(var, _) in a.0.iter() {
+            if let Variable(Index::Aux(i)) = var {
+                a_aux.inc(i);
+            }
+        }
+        let (b_input, b_aux) = (
+            Arc::make_mut(&mut self.b_input_density),
+            Arc::make_mut(&mut self.b_aux_density),
+        );
+        for &(var, _) in b.0.iter() {
+            match var {
+                Variable(Index::Input(i)) => b_input.inc(i),
+                Variable(Index::Aux(i)) => b_aux.inc(i),
+            }
+        }
+
+        self.constraints.push((a, b, c));
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn pop_namespace(&mut self) {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
 }
 
 impl<E: Engine> PreparedProver<E> {
+    /// Builds the prover state from evaluations of A, B and C computed
+    /// elsewhere, skipping synthesis. The evaluations must include the
+    /// `input * 0 = 0` rows `prepare_prover` appends for every input, and
+    /// `inputs` must start with the constant one.
+    pub fn from_evaluations(
+        a: Vec<E::Fr>,
+        b: Vec<E::Fr>,
+        c: Vec<E::Fr>,
+        densities: QueryDensities,
+        inputs: Vec<E::Fr>,
+        aux: Vec<E::Fr>,
+    ) -> Result<Self, SynthesisError> {
+        if a.len() != b.len()
+            || a.len() != c.len()
+            || a.len() < inputs.len()
+            || densities.b_input.bv.len() != inputs.len()
+            || densities.a_aux.bv.len() != aux.len()
+            || densities.b_aux.bv.len() != aux.len()
+        {
+            return Err(SynthesisError::EvaluationLengthMismatch);
+        }
+
+        let assignment = ProvingAssignment {
+            a_aux_density: Arc::new(densities.a_aux),
+            b_input_density: Arc::new(densities.b_input),
+            b_aux_density: Arc::new(densities.b_aux),
+            a: a.into_iter().map(Scalar).collect(),
+            b: b.into_iter().map(Scalar).collect(),
+            c: c.into_iter().map(Scalar).collect(),
+            input_assignment: inputs,
+            aux_assignment: aux,
+            enforce_input_density: true,
+        };
+
+        Ok(PreparedProver { assignment })
+    }
+
+    /// Builds the prover state for a new witness of the circuit recorded in
+    /// `layout`, evaluating its constraints on all cores of `worker`.
+    /// `inputs` must start with the constant one. The prover uses the options
+    /// the layout was recorded with, also when it is resynthesized.
+    pub fn from_layout(
+        layout: &ConstraintLayout<E>,
+        inputs: Vec<E::Fr>,
+        aux: Vec<E::Fr>,
+        worker: &Worker,
+    ) -> Result<Self, SynthesisError> {
+        if inputs.len() != layout.num_inputs || aux.len() != layout.num_aux {
+            return Err(SynthesisError::EvaluationLengthMismatch);
+        }
+        if inputs[0] != E::Fr::one() {
+            return Err(SynthesisError::InvalidConstantWire);
+        }
+        let n = layout.constraints.len();
+        required_domain_size::<E::Fr>(n)?;
+
+        let mut a = vec![Scalar(E::Fr::zero()); n];
+        let mut b = a.clone();
+        let mut c = a.clone();
+        worker.scope(n, |scope, chunk| {
+            for (((constraints, a), b), c) in layout
+                .constraints
+                .chunks(chunk)
+                .zip(a.chunks_mut(chunk))
+                .zip(b.chunks_mut(chunk))
+                .zip(c.chunks_mut(chunk))
+            {
+                let (inputs, aux) = (&inputs, &aux);
+                scope.spawn(move |_| {
+                    for (((lc_a, lc_b, lc_c), a), (b, c)) in constraints
+                        .iter()
+                        .zip(a.iter_mut())
+                        .zip(b.iter_mut().zip(c.iter_mut()))
+                    {
+                        a.0 = eval(lc_a, None, None, inputs, aux);
+                        b.0 = eval(lc_b, None, None, inputs, aux);
+                        c.0 = eval(lc_c, None, None, inputs, aux);
+                    }
+                });
+            }
+        });
+
+        let assignment = ProvingAssignment {
+            a_aux_density: layout.a_aux_density.clone(),
+            b_input_density: layout.b_input_density.clone(),
+            b_aux_density: layout.b_aux_density.clone(),
+            a,
+            b,
+            c,
+            input_assignment: inputs,
+            aux_assignment: aux,
+            enforce_input_density: layout.enforce_input_density,
+        };
+
+        Ok(PreparedProver { assignment })
+    }
+
+    /// Multiexp lengths that `create_proof` will use, derived from the
+    /// densities and the assignment lengths. Doesn't perform any proving work.
+    pub fn operation_counts(&self) -> OpCounts {
+        let prover = &self.assignment;
+        op_counts(
+            prover.a.len(),
+            &prover.a_aux_density,
+            &prover.b_input_density,
+            &prover.b_aux_density,
+            prover.input_assignment.len(),
+            prover.aux_assignment.len(),
+        )
+    }
+
+    /// Synthesizes `circuit` into the buffers left by the previous witness,
+    /// replacing it. Combined with `create_proof_ref` this proves many
+    /// witnesses of the same circuit without reallocating the assignment.
+    /// On error the assignment is left partially filled.
+    pub fn resynthesize<C: Circuit<E>>(&mut self, circuit: C) -> Result<(), SynthesisError> {
+        self.assignment.reset_and_reuse();
+        self.assignment.synthesize_circuit(circuit)
+    }
+
     pub fn create_random_proof<R, P: ParameterSource<E>>(
         self,
         params: P,
@@ -162,52 +752,182 @@ impl<E: Engine> PreparedProver<E> {
 
     pub fn create_proof<P: ParameterSource<E>>(
         self,
-        mut params: P,
+        params: P,
+        r: E::Fr,
+        s: E::Fr,
+    ) -> Result<Proof<E>, SynthesisError> {
+        self.create_proof_with_progress(params, r, s, |_| {})
+    }
+
+    /// Same as `create_proof`, calling `progress` as each stage starts. The
+    /// multiexps run in the background, so a stage may still be computing
+    /// when the next one is reported.
+    pub fn create_proof_with_progress<P: ParameterSource<E>, F: FnMut(ProofStage)>(
+        self,
+        params: P,
         r: E::Fr,
         s: E::Fr,
+        progress: F,
     ) -> Result<Proof<E>, SynthesisError> {
-        let prover = self.assignment;
         let worker = Worker::new();
+        self.create_proof_inner_owned(params, r, s, ProofOptions::new(&worker), progress)
+    }
+
+    /// Same as `create_proof`, also measuring the time spent in each phase.
+    /// The phases are timed from the stages `create_proof_with_progress`
+    /// reports, the logs stay as they are.
+    pub fn create_proof_timed<P: ParameterSource<E>>(
+        self,
+        params: P,
+        r: E::Fr,
+        s: E::Fr,
+    ) -> Result<(Proof<E>, ProofTimings), SynthesisError> {
+        let stopwatch = Stopwatch::new();
+        let (mut input_start, mut multiexp_start) = (Duration::default(), Duration::default());
+        let proof = self.create_proof_with_progress(params, r, s, |stage| match stage {
+            ProofStage::InputAssignment => input_start = stopwatch.elapsed_duration(),
+            ProofStage::L => multiexp_start = stopwatch.elapsed_duration(),
+            _ => {}
+        })?;
+        let end = stopwatch.elapsed_duration();
+        let timings = ProofTimings {
+            h_evaluation: input_start,
+            input_representation: multiexp_start - input_start,
+            point_multiplication: end - multiexp_start,
+        };
+        Ok((proof, timings))
+    }
+
+    /// Same as `create_proof`, running the FFTs and multiexps on `worker`
+    /// instead of a new pool sized to all cores
+    pub fn create_proof_with_worker<P: ParameterSource<E>>(
+        self,
+        params: P,
+        r: E::Fr,
+        s: E::Fr,
+        worker: &Worker,
+    ) -> Result<Proof<E>, SynthesisError> {
+        self.create_proof_inner_owned(params, r, s, ProofOptions::new(worker), |_| {})
+    }
+
+    /// Same as `create_proof`, computing the dense H and L multiexps
+    /// `chunk_size` bases at a time. The scalars of each window are converted
+    /// right before it is processed and the windows run one after another, so
+    /// only one window of scalar representations and multiexp buffers is
+    /// alive at a time. A `chunk_size` of 0 is rejected with
+    /// `SynthesisError::InvalidChunkSize`.
+    pub fn create_proof_chunked<P: ParameterSource<E>>(
+        self,
+        params: P,
+        r: E::Fr,
+        s: E::Fr,
+        chunk_size: usize,
+    ) -> Result<Proof<E>, SynthesisError> {
+        if chunk_size == 0 {
+            return Err(SynthesisError::InvalidChunkSize);
+        }
+        let worker = Worker::new();
+        let options = ProofOptions {
+            chunk_size: Some(chunk_size),
+            ..ProofOptions::new(&worker)
+        };
+        self.create_proof_inner_owned(params, r, s, options, |_| {})
+    }
+
+    /// Same as `create_proof`, computing H over the coset shifted by `g`
+    /// instead of the field's multiplicative generator, for differential
+    /// testing against implementations that use another shift. Proofs are
+    /// the same for every valid `g`; fails if `g` is in the evaluation domain.
+    pub fn create_proof_with_coset_generator<P: ParameterSource<E>>(
+        self,
+        params: P,
+        r: E::Fr,
+        s: E::Fr,
+        g: E::Fr,
+    ) -> Result<Proof<E>, SynthesisError> {
+        let worker = Worker::new();
+        let options = ProofOptions {
+            coset_generator: g,
+            ..ProofOptions::new(&worker)
+        };
+        self.create_proof_inner_owned(params, r, s, options, |_| {})
+    }
+
+    /// Same as `create_proof`, leaving the assignment in place so that it
+    /// can be refilled with `resynthesize` or proven again with other
+    /// blinding factors. Copies the evaluations of A, B and C for the FFTs
+    /// instead of transforming them in place, so each call temporarily needs
+    /// three more vectors of one scalar per constraint. The density trackers
+    /// are shared with the multiexps rather than copied, and the witness is
+    /// only borrowed.
+    pub fn create_proof_ref<P: ParameterSource<E>>(
+        &self,
+        params: P,
+        r: E::Fr,
+        s: E::Fr,
+    ) -> Result<Proof<E>, SynthesisError> {
+        let prover = &self.assignment;
+        let evaluations = (prover.a.clone(), prover.b.clone(), prover.c.clone());
+
+        let worker = Worker::new();
+        let options = ProofOptions::new(&worker);
+        Self::create_proof_inner(prover, evaluations, params, r, s, options, |_| {})
+    }
+
+    fn create_proof_inner_owned<P: ParameterSource<E>, F: FnMut(ProofStage)>(
+        self,
+        params: P,
+        r: E::Fr,
+        s: E::Fr,
+        options: ProofOptions<E>,
+        progress: F,
+    ) -> Result<Proof<E>, SynthesisError> {
+        let mut prover = self.assignment;
+        let evaluations = (
+            std::mem::take(&mut prover.a),
+            std::mem::take(&mut prover.b),
+            std::mem::take(&mut prover.c),
+        );
+
+        Self::create_proof_inner(&prover, evaluations, params, r, s, options, progress)
+    }
+
+    fn create_proof_inner<P: ParameterSource<E>, F: FnMut(ProofStage)>(
+        prover: &ProvingAssignment<E>,
+        (a, b, c): (Vec<Scalar<E>>, Vec<Scalar<E>>, Vec<Scalar<E>>),
+        mut params: P,
+        r: E::Fr,
+        s: E::Fr,
+        options: ProofOptions<E>,
+        mut progress: F,
+    ) -> Result<Proof<E>, SynthesisError> {
+        let worker = options.worker;
 
         let vk = params.get_vk(prover.input_assignment.len())?;
 
         let _stopwatch = Stopwatch::new();
 
+        progress(ProofStage::HEvaluation);
         let h = {
-            let mut a = EvaluationDomain::from_coeffs(prover.a)?;
-            let mut b = EvaluationDomain::from_coeffs(prover.b)?;
-            let mut c = EvaluationDomain::from_coeffs(prover.c)?;
-            elog_verbose!("H query domain size is {}", a.as_ref().len());
-
-            // here a coset is a domain where denominator (z) does not vanish
-            // inverse FFT is an interpolation
-            a.ifft(&worker);
-            // evaluate in coset
-            a.coset_fft(&worker);
-            // same is for B and C
-            b.ifft(&worker);
-            b.coset_fft(&worker);
-            c.ifft(&worker);
-            c.coset_fft(&worker);
-
-            // do A*B-C in coset
-            a.mul_assign(&worker, &b);
-            drop(b);
-            a.sub_assign(&worker, &c);
-            drop(c);
-            // z does not vanish in coset, so we divide by non-zero
-            a.divide_by_z_on_coset(&worker);
-            // interpolate back in coset
-            a.icoset_fft(&worker);
-            let mut a = a.into_coeffs();
-            let a_len = a.len() - 1;
-            a.truncate(a_len);
-            // TODO: parallelize if it's even helpful
-            // TODO: in large settings it may worth to parallelize
-            let a = Arc::new(scalars_into_representations::<E>(&worker, a)?);
-            // let a = Arc::new(a.into_iter().map(|s| s.0.into_repr()).collect::<Vec<_>>());
-
-            multiexp(&worker, params.get_h(a.len())?, FullDensity, a)
+            let a = compute_h_coeffs_with_generator(a, b, c, worker, options.coset_generator)?;
+            let bases = params.get_h(a.len())?;
+            match options.chunk_size {
+                Some(chunk_size) => DenseMultiexp::Done(chunked_dense_multiexp(
+                    worker,
+                    bases,
+                    a.len(),
+                    chunk_size,
+                    |window| field_elements_into_representations::<E>(worker, &a[window]),
+                )?),
+                None => {
+                    // TODO: parallelize if it's even helpful
+                    // TODO: in large settings it may worth to parallelize
+                    let a = Arc::new(field_elements_into_representations::<E>(worker, &a)?);
+                    // let a = Arc::new(a.into_iter().map(|s| s.0.into_repr()).collect::<Vec<_>>());
+
+                    DenseMultiexp::Pending(multiexp(worker, bases, FullDensity, a))
+                }
+            }
         };
 
         elog_verbose!(
@@ -230,13 +950,14 @@ impl<E: Engine> PreparedProver<E> {
             );
         }
 
+        progress(ProofStage::InputAssignment);
         let input_assignment = Arc::new(field_elements_into_representations::<E>(
-            &worker,
-            prover.input_assignment,
+            worker,
+            &prover.input_assignment,
         )?);
         let aux_assignment = Arc::new(field_elements_into_representations::<E>(
-            &worker,
-            prover.aux_assignment,
+            worker,
+            &prover.aux_assignment,
         )?);
 
         // TODO: parallelize if it's even helpful
@@ -248,62 +969,78 @@ impl<E: Engine> PreparedProver<E> {
         // let aux_len = aux_assignment.len();
 
         // Run a dedicated process for dense vector
-        let l = multiexp(
-            &worker,
-            params.get_l(aux_assignment.len())?,
-            FullDensity,
-            aux_assignment.clone(),
-        );
+        progress(ProofStage::L);
+        let l_bases = params.get_l(aux_assignment.len())?;
+        let l = match options.chunk_size {
+            Some(chunk_size) => DenseMultiexp::Done(chunked_dense_multiexp(
+                worker,
+                l_bases,
+                aux_assignment.len(),
+                chunk_size,
+                |window| Ok(aux_assignment[window].to_vec()),
+            )?),
+            None => DenseMultiexp::Pending(multiexp(
+                worker,
+                l_bases,
+                FullDensity,
+                aux_assignment.clone(),
+            )),
+        };
 
+        progress(ProofStage::A);
         let a_aux_density_total = prover.a_aux_density.get_total_density();
 
         let (a_inputs_source, a_aux_source) =
             params.get_a(input_assignment.len(), a_aux_density_total)?;
 
         let a_inputs = multiexp(
-            &worker,
+            worker,
             a_inputs_source,
             FullDensity,
             input_assignment.clone(),
         );
         let a_aux = multiexp(
-            &worker,
+            worker,
             a_aux_source,
-            Arc::new(prover.a_aux_density),
+            prover.a_aux_density.clone(),
             aux_assignment.clone(),
         );
 
-        let b_input_density = Arc::new(prover.b_input_density);
+        let b_input_density = prover.b_input_density.clone();
         let b_input_density_total = b_input_density.get_total_density();
-        let b_aux_density = Arc::new(prover.b_aux_density);
+        let b_aux_density = prover.b_aux_density.clone();
         let b_aux_density_total = b_aux_density.get_total_density();
 
+        progress(ProofStage::BG1);
         let (b_g1_inputs_source, b_g1_aux_source) =
             params.get_b_g1(b_input_density_total, b_aux_density_total)?;
 
         let b_g1_inputs = multiexp(
-            &worker,
+            worker,
             b_g1_inputs_source,
             b_input_density.clone(),
             input_assignment.clone(),
         );
         let b_g1_aux = multiexp(
-            &worker,
+            worker,
             b_g1_aux_source,
             b_aux_density.clone(),
             aux_assignment.clone(),
         );
 
+        progress(ProofStage::BG2);
         let (b_g2_inputs_source, b_g2_aux_source) =
             params.get_b_g2(b_input_density_total, b_aux_density_total)?;
 
         let b_g2_inputs = multiexp(
-            &worker,
+            worker,
             b_g2_inputs_source,
             b_input_density,
             input_assignment,
         );
-        let b_g2_aux = multiexp(&worker, b_g2_aux_source, b_aux_density, aux_assignment);
+        let b_g2_aux = multiexp(worker, b_g2_aux_source, b_aux_density, aux_assignment);
+
+        progress(ProofStage::Finalize);
 
         if vk.delta_g1.is_zero() || vk.delta_g2.is_zero() {
             // If this element is zero, someone is trying to perform a
@@ -364,8 +1101,8 @@ impl<E: Engine> ConstraintSystem<E> for ProvingAssignment<E> {
         AR: Into<String>,
     {
         self.aux_assignment.push(f()?);
-        self.a_aux_density.add_element();
-        self.b_aux_density.add_element();
+        Arc::make_mut(&mut self.a_aux_density).add_element();
+        Arc::make_mut(&mut self.b_aux_density).add_element();
 
         Ok(Variable(Index::Aux(self.aux_assignment.len() - 1)))
     }
@@ -377,7 +1114,7 @@ impl<E: Engine> ConstraintSystem<E> for ProvingAssignment<E> {
         AR: Into<String>,
     {
         self.input_assignment.push(f()?);
-        self.b_input_density.add_element();
+        Arc::make_mut(&mut self.b_input_density).add_element();
 
         Ok(Variable(Index::Input(self.input_assignment.len() - 1)))
     }
@@ -400,14 +1137,14 @@ impl<E: Engine> ConstraintSystem<E> for ProvingAssignment<E> {
             // because there are constraints of the
             // form x * 0 = 0 for each input.
             None,
-            Some(&mut self.a_aux_density),
+            Some(Arc::make_mut(&mut self.a_aux_density)),
             &self.input_assignment,
             &self.aux_assignment,
         )));
         self.b.push(Scalar(eval(
             &b,
-            Some(&mut self.b_input_density),
-            Some(&mut self.b_aux_density),
+            Some(Arc::make_mut(&mut self.b_input_density)),
+            Some(Arc::make_mut(&mut self.b_aux_density)),
             &self.input_assignment,
             &self.aux_assignment,
         )));
@@ -20,9 +20,22 @@ use crate::{
 
 use super::{
     generate_parameters,
+    generate_parameters_with_options,
     prepare_verifying_key,
+    prepare_prover,
+    prepare_prover_with_options,
     create_proof,
-    verify_proof
+    verify_proof,
+    compute_h_coeffs,
+    ConstraintLayout,
+    OpCounts,
+    PreparedProver,
+    Parameters,
+    PreparedVerifyingKey,
+    Proof,
+    PrepareOptions,
+    ProofStage,
+    QueryDensities
 };
 
 #[test]
@@ -328,3 +341,385 @@ fn test_xordemo() {
         &[Fr::one()]
     ).unwrap());
 }
+
+// `XORDemo` with both bits assigned
+fn xordemo(a: bool, b: bool) -> XORDemo<DummyEngine> {
+    XORDemo {
+        a: Some(a),
+        b: Some(b),
+        _marker: PhantomData
+    }
+}
+
+// Parameters for `XORDemo` from the toxic waste of `test_xordemo`
+fn xordemo_params() -> Parameters<DummyEngine> {
+    let c = XORDemo::<DummyEngine> {
+        a: None,
+        b: None,
+        _marker: PhantomData
+    };
+
+    generate_parameters(
+        c,
+        Fr::one(),
+        Fr::one(),
+        Fr::from_str("48577").unwrap(),
+        Fr::from_str("22580").unwrap(),
+        Fr::from_str("53332").unwrap(),
+        Fr::from_str("5481").unwrap(),
+        Fr::from_str("3673").unwrap()
+    ).unwrap()
+}
+
+#[test]
+fn test_operation_counts() {
+    let prover = prepare_prover(xordemo(true, false)).unwrap();
+
+    // 3 constraints plus 2 input constraints give a domain of 8, and aux
+    // variables a and b both appear in both the A and B queries
+    assert_eq!(prover.operation_counts(), OpCounts {
+        h: 7,
+        l: 2,
+        a_inputs: 2,
+        a_aux: 2,
+        b_g1_inputs: 0,
+        b_g1_aux: 2,
+        b_g2_inputs: 0,
+        b_g2_aux: 2,
+    });
+}
+
+#[test]
+fn test_proof_progress() {
+    let params = xordemo_params();
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+    let prover = || prepare_prover(xordemo(true, false)).unwrap();
+
+    let mut stages = vec![];
+    let proof = prover().create_proof_with_progress(&params, r, s, |stage| stages.push(stage)).unwrap();
+    assert_eq!(stages, vec![
+        ProofStage::HEvaluation,
+        ProofStage::InputAssignment,
+        ProofStage::L,
+        ProofStage::A,
+        ProofStage::BG1,
+        ProofStage::BG2,
+        ProofStage::Finalize,
+    ]);
+    assert!(proof == prover().create_proof(&params, r, s).unwrap());
+}
+
+#[test]
+fn test_single_threaded_worker() {
+    use crate::worker::Worker;
+
+    let params = xordemo_params();
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+    let prover = || prepare_prover(xordemo(true, false)).unwrap();
+
+    let worker = Worker::new_with_cpus(1);
+    let proof = prover().create_proof_with_worker(&params, r, s, &worker).unwrap();
+    assert!(verify_proof(&prepare_verifying_key(&params.vk), &proof, &[Fr::one()]).unwrap());
+    assert!(proof == prover().create_proof(&params, r, s).unwrap());
+}
+
+#[test]
+fn test_reused_assignment() {
+    let params = xordemo_params();
+    let pvk = prepare_verifying_key(&params.vk);
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+    let circuit = xordemo;
+
+    let mut prover = prepare_prover(circuit(true, false)).unwrap();
+    let first = prover.create_proof_ref(&params, r, s).unwrap();
+    assert!(verify_proof(&pvk, &first, &[Fr::one()]).unwrap());
+    assert!(first == prepare_prover(circuit(true, false)).unwrap().create_proof(&params, r, s).unwrap());
+
+    let capacity = prover.assignment.a_aux_density.bv.capacity();
+    prover.resynthesize(circuit(true, true)).unwrap();
+    assert_eq!(prover.assignment.a_aux_density.bv.capacity(), capacity);
+    assert_eq!(prover.operation_counts(), prepare_prover(circuit(true, true)).unwrap().operation_counts());
+
+    let second = prover.create_proof_ref(&params, r, s).unwrap();
+    assert!(verify_proof(&pvk, &second, &[Fr::zero()]).unwrap());
+    assert!(!verify_proof(&pvk, &second, &[Fr::one()]).unwrap());
+    assert!(second == prepare_prover(circuit(true, true)).unwrap().create_proof(&params, r, s).unwrap());
+}
+
+#[test]
+fn test_chunked_proof() {
+    let params = xordemo_params();
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+    let prover = || prepare_prover(xordemo(true, false)).unwrap();
+
+    let expected = prover().create_proof(&params, r, s).unwrap();
+    let counts = prover().operation_counts();
+    // windows smaller than, dividing, not dividing and exceeding the H and L queries
+    for chunk_size in [1, 2, 3, counts.h, counts.h + 1, 100].iter() {
+        let proof = prover().create_proof_chunked(&params, r, s, *chunk_size).unwrap();
+        assert!(proof == expected, "chunk size {}", chunk_size);
+    }
+    assert!(matches!(
+        prover().create_proof_chunked(&params, r, s, 0),
+        Err(SynthesisError::InvalidChunkSize)
+    ));
+}
+
+#[test]
+fn test_coset_generator() {
+    let params = xordemo_params();
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+    let prover = || prepare_prover(xordemo(true, false)).unwrap();
+
+    let expected = prover().create_proof(&params, r, s).unwrap();
+    let default = prover().create_proof_with_coset_generator(&params, r, s, Fr::multiplicative_generator()).unwrap();
+    assert!(default == expected);
+    assert!(verify_proof(&prepare_verifying_key(&params.vk), &default, &[Fr::one()]).unwrap());
+
+    // H doesn't depend on the shift as long as Z doesn't vanish on the coset
+    let other = prover().create_proof_with_coset_generator(&params, r, s, Fr::from_str("7").unwrap()).unwrap();
+    assert!(other == expected);
+    assert!(matches!(
+        prover().create_proof_with_coset_generator(&params, r, s, Fr::one()),
+        Err(SynthesisError::DivisionByZero)
+    ));
+}
+
+#[test]
+fn test_compute_h_coeffs() {
+    use crate::domain::Scalar;
+    use crate::worker::Worker;
+
+    // Over the domain {1, -1}, A = B = X and C = 1, so
+    // A * B - C = X^2 - 1 = Z and the quotient is exactly 1
+    let mut minus_one = Fr::one();
+    minus_one.negate();
+    let a = vec![Scalar::<DummyEngine>(Fr::one()), Scalar(minus_one)];
+    let b = a.clone();
+    let c = vec![Scalar(Fr::one()), Scalar(Fr::one())];
+
+    let h = compute_h_coeffs(a, b, c, &Worker::new()).unwrap();
+    assert_eq!(h, vec![Fr::one()]);
+}
+
+#[test]
+fn test_prover_from_evaluations() {
+    use crate::source::DensityTracker;
+
+    let params = xordemo_params();
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+
+    let synthesized = prepare_prover(xordemo(true, false)).unwrap().create_proof(&params, r, s).unwrap();
+
+    // With a = 1, b = 0 and c = 1 the constraints of XORDemo evaluate to
+    //     (1 - 1) * 1 = 0
+    //     (1 - 0) * 0 = 0
+    //     (1 + 1) * 0 = (1 + 0 - 1)
+    // followed by (1) * 0 = 0 for each of the two inputs
+    let fr = |x: &str| Fr::from_str(x).unwrap();
+    let a = vec![fr("0"), fr("1"), fr("2"), fr("1"), fr("1")];
+    let b = vec![fr("1"), fr("0"), fr("0"), fr("0"), fr("0")];
+    let c = vec![fr("0"); 5];
+    let both = || {
+        let mut tracker = DensityTracker::new();
+        tracker.pad(2);
+        tracker.inc(0);
+        tracker.inc(1);
+        tracker
+    };
+    let mut no_inputs = DensityTracker::new();
+    no_inputs.pad(2);
+    let densities = QueryDensities { a_aux: both(), b_input: no_inputs, b_aux: both() };
+
+    let prover = PreparedProver::<DummyEngine>::from_evaluations(
+        a.clone(),
+        b.clone(),
+        c.clone(),
+        densities.clone(),
+        vec![fr("1"), fr("1")],
+        vec![fr("1"), fr("0")]
+    ).unwrap();
+    assert!(prover.create_proof(&params, r, s).unwrap() == synthesized);
+
+    assert!(matches!(
+        PreparedProver::<DummyEngine>::from_evaluations(
+            a,
+            b,
+            c,
+            densities,
+            vec![fr("1"), fr("1")],
+            vec![fr("1")]
+        ),
+        Err(SynthesisError::EvaluationLengthMismatch)
+    ));
+}
+
+#[test]
+fn test_prover_from_layout() {
+    use crate::worker::Worker;
+
+    let params = xordemo_params();
+    let pvk = prepare_verifying_key(&params.vk);
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+
+    // recorded from the witness-less circuit, the value closures are never called
+    let layout = ConstraintLayout::<DummyEngine>::new(XORDemo::<DummyEngine> {
+        a: None,
+        b: None,
+        _marker: PhantomData
+    }).unwrap();
+    assert_eq!(layout.num_inputs(), 2);
+    assert_eq!(layout.num_aux(), 2);
+    assert_eq!(layout.num_constraints(), 5);
+
+    let worker = Worker::new();
+    let bit = |x: bool| if x { Fr::one() } else { Fr::zero() };
+    for &(a, b) in [(false, false), (true, false), (false, true), (true, true)].iter() {
+        let synthesized = prepare_prover(xordemo(a, b)).unwrap();
+        let prover = PreparedProver::from_layout(&layout, vec![Fr::one(), bit(a ^ b)], vec![bit(a), bit(b)], &worker).unwrap();
+        assert_eq!(prover.operation_counts(), synthesized.operation_counts());
+        assert_eq!(layout.operation_counts(), synthesized.operation_counts());
+        let proof = prover.create_proof(&params, r, s).unwrap();
+        assert!(proof == synthesized.create_proof(&params, r, s).unwrap());
+        assert!(verify_proof(&pvk, &proof, &[bit(a ^ b)]).unwrap());
+    }
+
+    // provers share the layout's density trackers until one of them is resynthesized
+    let prover = |a: bool| PreparedProver::from_layout(&layout, vec![Fr::one(), bit(a)], vec![bit(a), Fr::zero()], &worker).unwrap();
+    let (first, mut second) = (prover(false), prover(true));
+    assert!(std::sync::Arc::ptr_eq(&first.assignment.b_aux_density, &second.assignment.b_aux_density));
+    second.resynthesize(xordemo(true, true)).unwrap();
+    assert!(!std::sync::Arc::ptr_eq(&first.assignment.b_aux_density, &second.assignment.b_aux_density));
+    assert_eq!(first.operation_counts(), layout.operation_counts());
+    assert_eq!(second.operation_counts(), layout.operation_counts());
+
+    assert!(matches!(
+        PreparedProver::from_layout(&layout, vec![Fr::one()], vec![Fr::one(), Fr::zero()], &worker),
+        Err(SynthesisError::EvaluationLengthMismatch)
+    ));
+    assert!(matches!(
+        PreparedProver::from_layout(&layout, vec![Fr::zero(), Fr::one()], vec![Fr::one(), Fr::zero()], &worker),
+        Err(SynthesisError::InvalidConstantWire)
+    ));
+}
+
+#[test]
+fn test_reblinded_proofs() {
+    let params = xordemo_params();
+    let pvk = prepare_verifying_key(&params.vk);
+    let circuit = xordemo(true, false);
+
+    let prover = prepare_prover(circuit).unwrap();
+    let first = prover.create_proof_ref(&params, Fr::from_str("27134").unwrap(), Fr::from_str("17146").unwrap()).unwrap();
+    let second = prover.create_proof_ref(&params, Fr::from_str("3").unwrap(), Fr::from_str("5").unwrap()).unwrap();
+    assert!(first != second);
+    assert!(verify_proof(&pvk, &first, &[Fr::one()]).unwrap());
+    assert!(verify_proof(&pvk, &second, &[Fr::one()]).unwrap());
+
+    // the prover is left intact, so consuming it afterwards gives the same proof
+    let again = prover.clone().create_proof(&params, Fr::from_str("3").unwrap(), Fr::from_str("5").unwrap()).unwrap();
+    assert!(again == second);
+}
+
+// Computes x^3 + x for a public x. Both x and the constant one appear in A,
+// in different constraints, so the input constraints can be left out.
+struct CubeDemo<E: Engine> {
+    x: Option<E::Fr>
+}
+
+impl<E: Engine> Circuit<E> for CubeDemo<E> {
+    fn synthesize<CS: ConstraintSystem<E>>(
+        self,
+        cs: &mut CS
+    ) -> Result<(), SynthesisError>
+    {
+        let x = self.x;
+        let x_var = cs.alloc_input(|| "x", || x.ok_or(SynthesisError::AssignmentMissing))?;
+        let square = x.map(|x| { let mut x2 = x; x2.square(); x2 });
+        let square_var = cs.alloc(|| "x^2", || square.ok_or(SynthesisError::AssignmentMissing))?;
+        cs.enforce(
+            || "square",
+            |lc| lc + x_var,
+            |lc| lc + x_var,
+            |lc| lc + square_var
+        );
+
+        let out = x.and_then(|x| square.map(|mut x2| { x2.add_assign(&E::Fr::one()); x2.mul_assign(&x); x2 }));
+        let out_var = cs.alloc(|| "out", || out.ok_or(SynthesisError::AssignmentMissing))?;
+        cs.enforce(
+            || "out",
+            |lc| lc + square_var + CS::one(),
+            |lc| lc + x_var,
+            |lc| lc + out_var
+        );
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_skipped_input_constraints() {
+    use crate::worker::Worker;
+
+    let x = Fr::from_str("3").unwrap();
+    let r = Fr::from_str("27134").unwrap();
+    let s = Fr::from_str("17146").unwrap();
+
+    let mut counts = vec![];
+    for enforce_input_density in [true, false].iter() {
+        let options = PrepareOptions { enforce_input_density: *enforce_input_density };
+        let params = generate_parameters_with_options(
+            CubeDemo::<DummyEngine> { x: None },
+            Fr::one(),
+            Fr::one(),
+            Fr::from_str("48577").unwrap(),
+            Fr::from_str("22580").unwrap(),
+            Fr::from_str("53332").unwrap(),
+            Fr::from_str("5481").unwrap(),
+            Fr::from_str("3673").unwrap(),
+            options
+        ).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+
+        let prover = prepare_prover_with_options(CubeDemo::<DummyEngine> { x: Some(x) }, options).unwrap();
+        counts.push(prover.operation_counts());
+        let proof = prover.create_proof(&params, r, s).unwrap();
+        assert!(verify_proof(&pvk, &proof, &[x]).unwrap());
+        assert!(!verify_proof(&pvk, &proof, &[Fr::one()]).unwrap());
+
+        // the layout path follows the same options, also after resynthesizing
+        let layout = ConstraintLayout::new_with_options(CubeDemo::<DummyEngine> { x: None }, options).unwrap();
+        assert_eq!(layout.num_constraints(), if *enforce_input_density { 4 } else { 2 });
+        let (square, out) = (Fr::from_str("9").unwrap(), Fr::from_str("30").unwrap());
+        let mut from_layout = PreparedProver::from_layout(&layout, vec![Fr::one(), x], vec![square, out], &Worker::new()).unwrap();
+        assert_eq!(from_layout.operation_counts(), *counts.last().unwrap());
+        let layout_proof = from_layout.create_proof_ref(&params, r, s).unwrap();
+        assert!(layout_proof == proof);
+        assert!(verify_proof(&pvk, &layout_proof, &[x]).unwrap());
+        from_layout.resynthesize(CubeDemo::<DummyEngine> { x: Some(x) }).unwrap();
+        assert!(from_layout.create_proof(&params, r, s).unwrap() == proof);
+    }
+    assert_eq!(PrepareOptions::default(), PrepareOptions { enforce_input_density: true });
+    assert_eq!(counts[0].h, 3);
+    assert_eq!(counts[1].h, 1);
+}
+
+#[test]
+fn test_empty_ic() {
+    // a key without even the IC point of the constant input accepts no input count
+    let pvk = PreparedVerifyingKey::<DummyEngine>::from_parts(Fr::one(), Fr::one(), Fr::one(), vec![]);
+    let proof = Proof::<DummyEngine> { a: Fr::one(), b: Fr::one(), c: Fr::one() };
+    for inputs in [vec![], vec![Fr::one()]].iter() {
+        match verify_proof(&pvk, &proof, inputs) {
+            Err(SynthesisError::InputCountMismatch { expected: 0, got }) => assert_eq!(got, inputs.len()),
+            _ => panic!("expected an input count mismatch")
+        }
+    }
+}
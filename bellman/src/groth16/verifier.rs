@@ -20,17 +20,12 @@ pub fn prepare_verifying_key<E: Engine>(
     vk: &VerifyingKey<E>
 ) -> PreparedVerifyingKey<E>
 {
-    let mut gamma = vk.gamma_g2;
-    gamma.negate();
-    let mut delta = vk.delta_g2;
-    delta.negate();
-
-    PreparedVerifyingKey {
-        alpha_g1_beta_g2: E::pairing(vk.alpha_g1, vk.beta_g2),
-        neg_gamma_g2: gamma.prepare(),
-        neg_delta_g2: delta.prepare(),
-        ic: vk.ic.clone()
-    }
+    PreparedVerifyingKey::from_parts(
+        E::pairing(vk.alpha_g1, vk.beta_g2),
+        vk.gamma_g2,
+        vk.delta_g2,
+        vk.ic.clone()
+    )
 }
 
 pub fn verify_proof<'a, E: Engine>(
@@ -40,7 +35,10 @@ pub fn verify_proof<'a, E: Engine>(
 ) -> Result<bool, SynthesisError>
 {
     if (public_inputs.len() + 1) != pvk.ic.len() {
-        return Err(SynthesisError::MalformedVerifyingKey);
+        return Err(SynthesisError::InputCountMismatch {
+            expected: pvk.ic.len().saturating_sub(1),
+            got: public_inputs.len()
+        });
     }
 
     let mut acc = pvk.ic[0].into_projective();
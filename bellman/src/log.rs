@@ -18,12 +18,24 @@ cfg_if! {
             ($($t:tt)*) => ()
         }
 
+        // Zero-sized and never reads a clock, so timing code compiles away entirely
         pub struct Stopwatch {}
 
         impl Stopwatch {
+            #[inline(always)]
             pub fn new() -> Stopwatch {
                 Stopwatch {}
             }
+
+            #[inline(always)]
+            pub fn elapsed(&self) -> f64 {
+                0.0
+            }
+
+            #[inline(always)]
+            pub fn elapsed_duration(&self) -> std::time::Duration {
+                std::time::Duration::from_secs(0)
+            }
         }
     } else if #[cfg(feature = "wasm")] {
         use web_sys;
@@ -59,6 +71,10 @@ cfg_if! {
             pub fn elapsed(&self) -> f64 {
                 (self.perf.now() - self.start) / 1000.0
             }
+
+            pub fn elapsed_duration(&self) -> std::time::Duration {
+                std::time::Duration::from_secs_f64(self.elapsed())
+            }
         }
     } else {
         macro_rules! log {
@@ -89,6 +105,24 @@ cfg_if! {
             pub fn elapsed(&self) -> f64 {
                 self.start.elapsed().as_millis() as f64 / 1000.0
             }
+
+            // full resolution, `elapsed` is rounded down to milliseconds for the logs
+            pub fn elapsed_duration(&self) -> std::time::Duration {
+                self.start.elapsed()
+            }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(all(test, feature = "nolog"))]
+mod tests {
+    use super::Stopwatch;
+
+    #[test]
+    fn nolog_stopwatch_is_free() {
+        assert_eq!(std::mem::size_of::<Stopwatch>(), 0);
+        let stopwatch = Stopwatch::new();
+        elog_verbose!("{} seconds", stopwatch.elapsed());
+        assert_eq!(stopwatch.elapsed(), 0.0);
+        assert_eq!(stopwatch.elapsed_duration(), std::time::Duration::from_secs(0));
+    }
+}
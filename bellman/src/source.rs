@@ -150,6 +150,26 @@ impl DensityTracker {
     pub fn get_total_density(&self) -> usize {
         self.total_density
     }
+
+    /// Drops every element while keeping the allocated storage
+    pub fn clear(&mut self) {
+        self.bv.truncate(0);
+        self.total_density = 0;
+    }
+
+    /// Adds every element marked in `other` to this tracker, padding it if
+    /// `other` is longer. Used to combine the densities of synthesis shards
+    /// that enforced disjoint sets of constraints over the same variables.
+    pub fn merge(&mut self, other: &DensityTracker) {
+        if other.bv.len() > self.bv.len() {
+            self.pad(other.bv.len());
+        }
+        for (idx, set) in other.bv.iter().enumerate() {
+            if set {
+                self.inc(idx);
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -182,3 +202,39 @@ impl<'a> QueryDensity for &'a DensityTrackerersChain {
         Some(self.tracker_0.bv.len() + self.tracker_1.bv.len())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::DensityTracker;
+
+    #[test]
+    fn test_merge_density_shards() {
+        // Variables touched by each constraint of a small circuit.
+        let constraints: Vec<Vec<usize>> = vec![vec![0, 2], vec![1], vec![2, 3], vec![5], vec![0, 5]];
+        let num_vars = 7;
+
+        let mut sequential = DensityTracker::new();
+        sequential.pad(num_vars);
+        for vars in constraints.iter() {
+            for &v in vars {
+                sequential.inc(v);
+            }
+        }
+
+        let mut merged = DensityTracker::new();
+        for shard in constraints.chunks(2) {
+            let mut tracker = DensityTracker::new();
+            tracker.pad(num_vars);
+            for vars in shard {
+                for &v in vars {
+                    tracker.inc(v);
+                }
+            }
+            merged.merge(&tracker);
+        }
+
+        assert_eq!(merged.get_total_density(), sequential.get_total_density());
+        assert_eq!(merged.get_total_density(), 5);
+        assert_eq!(merged.bv, sequential.bv);
+    }
+}
@@ -0,0 +1,43 @@
+//! Compares verifying a batch of proofs with `PreparedVk`, which prepares the key once, to
+//! calling `prepare_verifying_key` for every proof.
+//!
+//! cargo bench --bench prepared_vk [num_proofs]
+use std::time::Instant;
+use bellman_ce::groth16::{prepare_verifying_key, verify_proof};
+use bellman_ce::pairing::bn256::Bn256;
+use bellman_ce::pairing::ff::{PrimeField, ScalarEngine};
+use zkutil::circom_circuit::{generate_random_parameters, prove, seeded_rng, CircomCircuitBuilder, R1CSBuilder};
+use zkutil::utils::PreparedVk;
+
+fn main() {
+    let n = std::env::args().skip(1).find_map(|a| a.parse().ok()).unwrap_or(200);
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    // out = a * b with one public output
+    let r1cs = R1CSBuilder::<Bn256>::new(4)
+        .set_public_count(1)
+        .add_constraint(vec![(2, fr("1"))], vec![(3, fr("1"))], vec![(1, fr("1"))])
+        .build()
+        .unwrap();
+    let setup = CircomCircuitBuilder::new(r1cs.clone()).build().unwrap();
+    let params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+    let circuit = CircomCircuitBuilder::new(r1cs)
+        .witness(vec![fr("1"), fr("12"), fr("3"), fr("4")])
+        .build()
+        .unwrap();
+    let inputs = circuit.get_public_inputs().unwrap();
+    let proofs = (0..n).map(|seed| prove(circuit.clone(), &params, seeded_rng(seed)).unwrap()).collect::<Vec<_>>();
+    println!("{} proofs", n);
+
+    let start = Instant::now();
+    for proof in proofs.iter() {
+        assert!(verify_proof(&prepare_verifying_key(&params.vk), proof, &inputs).unwrap());
+    }
+    println!("prepare per proof: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let vk = PreparedVk::new(&params.vk);
+    for proof in proofs.iter() {
+        assert!(vk.verify(proof, &inputs).unwrap());
+    }
+    println!("PreparedVk:        {:?}", start.elapsed());
+}
@@ -0,0 +1,67 @@
+//! Compares preparing the prover for a batch of witnesses with `prepare_prover`, which runs
+//! `synthesize` for every witness, to evaluating a `ConstraintLayout` recorded once.
+//!
+//! cargo bench --bench prover_layout [num_constraints]
+use std::time::Instant;
+use bellman_ce::groth16::{prepare_prover, ConstraintLayout, PreparedProver};
+use bellman_ce::pairing::bn256::{Bn256, Fr};
+use bellman_ce::pairing::ff::{Field, PrimeField};
+use bellman_ce::worker::Worker;
+use zkutil::circom_circuit::{generate_random_parameters, seeded_rng, CircomCircuitBuilder, R1CSBuilder};
+
+const WITNESSES: u64 = 20;
+
+fn main() {
+    let n = std::env::args().skip(1).find_map(|a| a.parse().ok()).unwrap_or(100_000);
+    // wire 1 is the public output, wires 2.. square the previous one
+    let one = Fr::one();
+    let mut builder = R1CSBuilder::<Bn256>::new(n + 2);
+    builder.set_public_count(1);
+    for i in 0..n {
+        let out = if i + 1 == n { 1 } else { i + 3 };
+        builder.add_constraint(vec![(i + 2, one)], vec![(i + 2, one)], vec![(out, one)]);
+    }
+    let r1cs = builder.build().unwrap();
+    let witness = |seed: u64| {
+        let mut w = vec![one, Fr::zero(), Fr::from_str(&(seed + 2).to_string()).unwrap()];
+        for _ in 1..n {
+            let mut x = *w.last().unwrap();
+            x.square();
+            w.push(x);
+        }
+        let mut out = *w.last().unwrap();
+        out.square();
+        w[1] = out;
+        w
+    };
+    let setup = CircomCircuitBuilder::new(r1cs.clone()).build().unwrap();
+    let params = generate_random_parameters(setup.clone(), seeded_rng(1)).unwrap();
+    println!("{} constraints, {} witnesses", n, WITNESSES);
+
+    let start = Instant::now();
+    let mut synthesized = vec![];
+    for seed in 0..WITNESSES {
+        let circuit = CircomCircuitBuilder::new(r1cs.clone()).witness(witness(seed)).build().unwrap();
+        synthesized.push(prepare_prover(circuit).unwrap());
+    }
+    println!("prepare_prover:   {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let layout = ConstraintLayout::new(setup).unwrap();
+    println!("record layout:    {:?}", start.elapsed());
+
+    let worker = Worker::new();
+    let start = Instant::now();
+    let mut from_layout = vec![];
+    for seed in 0..WITNESSES {
+        let mut aux = witness(seed);
+        let inputs = aux.drain(..r1cs.num_inputs).collect();
+        from_layout.push(PreparedProver::from_layout(&layout, inputs, aux, &worker).unwrap());
+    }
+    println!("from_layout:      {:?}", start.elapsed());
+
+    // both paths must give the same proof
+    let (r, s) = (Fr::from_str("3").unwrap(), Fr::from_str("5").unwrap());
+    let first = synthesized.swap_remove(0).create_proof(&params, r, s).unwrap();
+    assert!(first == from_layout.swap_remove(0).create_proof(&params, r, s).unwrap());
+}
@@ -0,0 +1,64 @@
+//! Proves a batch of witnesses of one circuit with `resynthesize` and `create_proof_ref`,
+//! which keep the assignment and share its density trackers with the multiexps. For
+//! comparison it also times copying the three trackers, which a proof used to do every time.
+//!
+//! cargo bench --bench prover_reuse [num_constraints]
+use std::time::Instant;
+use bellman_ce::groth16::prepare_prover;
+use bellman_ce::pairing::bn256::{Bn256, Fr};
+use bellman_ce::pairing::ff::{Field, PrimeField};
+use zkutil::circom_circuit::{generate_random_parameters, seeded_rng, CircomCircuitBuilder, R1CSBuilder};
+
+const WITNESSES: u64 = 10;
+
+fn main() {
+    let n = std::env::args().skip(1).find_map(|a| a.parse().ok()).unwrap_or(100_000);
+    // wire 1 is the public output, wires 2.. square the previous one
+    let one = Fr::one();
+    let mut builder = R1CSBuilder::<Bn256>::new(n + 2);
+    builder.set_public_count(1);
+    for i in 0..n {
+        let out = if i + 1 == n { 1 } else { i + 3 };
+        builder.add_constraint(vec![(i + 2, one)], vec![(i + 2, one)], vec![(out, one)]);
+    }
+    let r1cs = builder.build().unwrap();
+    let circuit = |seed: u64| {
+        let mut w = vec![one, Fr::zero(), Fr::from_str(&(seed + 2).to_string()).unwrap()];
+        for _ in 1..n {
+            let mut x = *w.last().unwrap();
+            x.square();
+            w.push(x);
+        }
+        let mut out = *w.last().unwrap();
+        out.square();
+        w[1] = out;
+        CircomCircuitBuilder::new(r1cs.clone()).witness(w).build().unwrap()
+    };
+    let setup = CircomCircuitBuilder::new(r1cs.clone()).build().unwrap();
+    let params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+    let (r, s) = (Fr::from_str("3").unwrap(), Fr::from_str("5").unwrap());
+    println!("{} constraints, {} witnesses", n, WITNESSES);
+
+    let mut prover = prepare_prover(circuit(0)).unwrap();
+    let start = Instant::now();
+    for seed in 0..WITNESSES {
+        prover.resynthesize(circuit(seed)).unwrap();
+        let proof = prover.create_proof_ref(&params, r, s).unwrap();
+        if seed == 0 {
+            assert!(proof == prepare_prover(circuit(0)).unwrap().create_proof(&params, r, s).unwrap());
+        }
+    }
+    println!("create_proof_ref:         {:?} per proof", start.elapsed() / WITNESSES as u32);
+
+    let assignment = &prover.assignment;
+    let start = Instant::now();
+    for _ in 0..WITNESSES {
+        let copies = (
+            (*assignment.a_aux_density).clone(),
+            (*assignment.b_input_density).clone(),
+            (*assignment.b_aux_density).clone(),
+        );
+        drop(copies);
+    }
+    println!("copying density trackers: {:?} per proof", start.elapsed() / WITNESSES as u32);
+}
@@ -0,0 +1,76 @@
+//! Compares `r1cs_reader::read`, which decodes constraints on all cores with the `parallel`
+//! feature, to sequential streaming with `constraints_iter`, and to `read_header_only`, which
+//! seeks over the constraints.
+//!
+//! cargo bench --features parallel --bench r1cs_parse [num_constraints]
+use std::io::Cursor;
+use std::time::Instant;
+use bellman_ce::pairing::bn256::Bn256;
+use byteorder::{LittleEndian, WriteBytesExt};
+use hex_literal::hex;
+use zkutil::r1cs_reader::{constraints_iter, read, read_header_only};
+
+/// An r1cs file with `n` constraints over 4 wires, each with a handful of full-size coefficients
+fn synthetic_r1cs(n: u32) -> Vec<u8> {
+    let coeff = |i: u32| {
+        let mut bytes = vec![0x5a; 31];
+        bytes.extend_from_slice(&[0x01]);
+        bytes[..4].copy_from_slice(&i.to_le_bytes());
+        bytes
+    };
+    let mut constraints = vec![];
+    for i in 0..n {
+        for terms in [2u32, 2, 3].iter() {
+            constraints.write_u32::<LittleEndian>(*terms).unwrap();
+            for wire in 0..*terms {
+                constraints.write_u32::<LittleEndian>(wire + 1).unwrap();
+                constraints.extend(coeff(i));
+            }
+        }
+    }
+
+    let mut data = b"r1cs".to_vec();
+    for word in [1u32, 3, 1].iter() {
+        data.write_u32::<LittleEndian>(*word).unwrap();
+    }
+    data.write_u64::<LittleEndian>(0x40).unwrap();
+    data.write_u32::<LittleEndian>(32).unwrap();
+    data.extend_from_slice(&hex!("010000f093f5e1439170b97948e833285d588181b64550b829a031e1724e6430"));
+    for word in [4u32, 1, 0, 2].iter() {
+        data.write_u32::<LittleEndian>(*word).unwrap();
+    }
+    data.write_u64::<LittleEndian>(4).unwrap();
+    data.write_u32::<LittleEndian>(n).unwrap();
+    data.write_u32::<LittleEndian>(2).unwrap();
+    data.write_u64::<LittleEndian>(constraints.len() as u64).unwrap();
+    data.extend(constraints);
+    data.write_u32::<LittleEndian>(3).unwrap();
+    data.write_u64::<LittleEndian>(4 * 8).unwrap();
+    for label in 0..4u64 {
+        data.write_u64::<LittleEndian>(label).unwrap();
+    }
+    data
+}
+
+fn main() {
+    let n = std::env::args().skip(1).find_map(|a| a.parse().ok()).unwrap_or(1_000_000);
+    let data = synthetic_r1cs(n);
+    println!("{} constraints, {} MB", n, data.len() >> 20);
+
+    let start = Instant::now();
+    let file = read::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    println!("read:             {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let streamed = constraints_iter::<Bn256, _>(Cursor::new(&data[..])).collect::<Result<Vec<_>, _>>().unwrap();
+    println!("constraints_iter: {:?}", start.elapsed());
+    assert!(streamed == file.constraints);
+
+    let path = std::env::temp_dir().join(format!("zkutil_bench_{}.r1cs", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+    let start = Instant::now();
+    let header = read_header_only(&path).unwrap();
+    println!("read_header_only: {:?}", start.elapsed());
+    std::fs::remove_file(&path).unwrap();
+    assert!(header == file.header);
+}
@@ -0,0 +1,98 @@
+//! Compares proving with a `.zkey` read into memory by `read_zkey` to proving with one indexed
+//! by `load_zkey_streaming`, which reads the `A`, `B` and `C` points from the file while
+//! proving. The zkey is written with `write_zkey_streaming` from cheap, non-random points, so
+//! the proofs don't verify but have to be the same. Peak memory is the process's resident
+//! high-water mark, reset before each run (linux only).
+//!
+//! cargo bench --bench zkey_streaming [num_constraints]
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::time::Instant;
+use bellman_ce::groth16::{Parameters, VerifyingKey};
+use bellman_ce::pairing::bn256::{Bn256, Fr};
+use bellman_ce::pairing::ff::{Field, PrimeField};
+use bellman_ce::pairing::{CurveAffine, CurveProjective};
+use zkutil::circom_circuit::{prove_zkey, prove_zkey_streaming, seeded_rng, CircomCircuit, R1CSBuilder};
+use zkutil::utils::{load_zkey_streaming, read_zkey, write_zkey_streaming};
+
+// P, 2P, 3P, ...
+fn points<G: CurveAffine>(n: usize) -> Vec<G> {
+    let mut acc = G::Projective::zero();
+    let mut points = Vec::with_capacity(n);
+    for _ in 0..n {
+        acc.add_assign_mixed(&G::one());
+        points.push(acc);
+    }
+    G::Projective::batch_normalization(&mut points);
+    points.into_iter().map(|p| p.into_affine()).collect()
+}
+
+fn peak_memory_reset() {
+    let _ = fs::write("/proc/self/clear_refs", "5");
+}
+
+fn peak_memory() -> String {
+    fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| status.lines().find(|l| l.starts_with("VmHWM")).map(|l| l[6..].trim().to_string()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn main() {
+    let n = std::env::args().skip(1).find_map(|a| a.parse().ok()).unwrap_or(1 << 16);
+    let one = Fr::one();
+    // x * x = y_1, y_i * y_i = y_{i+1}, ..., ending in the public output y_n
+    let wire = |i: usize| if i == n { 1 } else { i + 2 };
+    let mut builder = R1CSBuilder::<Bn256>::new(n + 2);
+    builder.set_public_count(1);
+    let mut witness = vec![one; n + 2];
+    witness[2] = Fr::from_str("3").unwrap();
+    for i in 0..n {
+        builder.add_constraint(vec![(wire(i), one)], vec![(wire(i), one)], vec![(wire(i + 1), one)]);
+        let mut square = witness[wire(i)];
+        square.square();
+        witness[wire(i + 1)] = square;
+    }
+    let r1cs = builder.build().unwrap();
+    let circuit = CircomCircuit { r1cs: r1cs.clone(), witness: Some(witness), wire_mapping: None };
+
+    let n_vars = r1cs.num_variables;
+    let domain_size = (n + r1cs.num_inputs).next_power_of_two();
+    let g1 = points(n_vars.max(domain_size));
+    let params = Parameters::<Bn256> {
+        vk: VerifyingKey {
+            alpha_g1: g1[0],
+            beta_g1: g1[1],
+            beta_g2: CurveAffine::one(),
+            gamma_g2: CurveAffine::one(),
+            delta_g1: g1[2],
+            delta_g2: CurveAffine::one(),
+            ic: g1[..r1cs.num_inputs].to_vec(),
+        },
+        h: g1[..domain_size - 1].to_vec().into(),
+        l: g1[..n_vars - r1cs.num_inputs].to_vec().into(),
+        a: g1[..n_vars].to_vec().into(),
+        b_g1: g1[..n_vars].to_vec().into(),
+        b_g2: points(n_vars).into(),
+    };
+    let path = std::env::temp_dir().join(format!("zkutil_bench_{}.zkey", std::process::id()));
+    write_zkey_streaming(&params, &r1cs, File::create(&path).unwrap()).unwrap();
+    drop(params);
+    drop(g1);
+    println!("{} constraints, {} byte zkey", n, fs::metadata(&path).unwrap().len());
+
+    peak_memory_reset();
+    let start = Instant::now();
+    let (params, _) = read_zkey(&mut BufReader::new(File::open(&path).unwrap())).unwrap();
+    let in_memory = prove_zkey(circuit.clone(), &params, seeded_rng(1)).unwrap();
+    println!("read_zkey:           {:?}, peak memory {}", start.elapsed(), peak_memory());
+    drop(params);
+
+    peak_memory_reset();
+    let start = Instant::now();
+    let zkey = load_zkey_streaming(&path).unwrap();
+    let streamed = prove_zkey_streaming(circuit, &zkey, seeded_rng(1)).unwrap();
+    println!("load_zkey_streaming: {:?}, peak memory {}", start.elapsed(), peak_memory());
+    assert!(in_memory == streamed);
+    fs::remove_file(&path).unwrap();
+}
@@ -3,30 +3,44 @@ extern crate bellman_ce;
 extern crate rand;
 
 use itertools::Itertools;
-use rand::{OsRng, Rng};
+use num_bigint::BigUint;
+use rand::{ChaChaRng, OsRng, Rand, Rng};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufReader, Read, Seek};
 use std::iter::repeat;
 use std::str;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use bellman_ce::{
+    domain::required_domain_size,
     groth16::{
         create_random_proof, generate_random_parameters as generate_random_parameters2,
-        prepare_prover, prepare_verifying_key, verify_proof, Parameters, Proof,
+        prepare_prover, prepare_verifying_key, verify_proof, ConstraintLayout, OpCounts,
+        Parameters, PreparedProver, PreparedVerifyingKey, Proof, ProofTimings, QueryDensities,
+        VerifyingKey,
     },
     pairing::{
-        bn256::{Bn256, Fq, Fq2, G1Affine, G2Affine},
-        ff::PrimeField,
-        ff::ScalarEngine,
-        CurveAffine, Engine,
+        bn256::{Bn256, Fq, Fq2, Fr, G1Affine, G2Affine},
+        ff::{Field, PrimeField, PrimeFieldRepr, ScalarEngine},
+        CurveAffine, CurveProjective, Engine,
     },
-    source::QueryDensity,
+    source::{DensityTracker, QueryDensity},
     Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable,
 };
 
-use crate::utils::{p1_to_vec, p2_to_vec, pairing_to_vec, proof_to_hex, repr_to_big};
+use crate::parse_error::{AtOffset, ParseError, ParseResult};
+use crate::r1cs_reader::R1CSFile;
+use crate::sym_reader::SymTable;
+use crate::utils::{
+    biguint_to_fr, create_rng_from_seed, fr_ct_eq, fr_to_signed_decimal, p1_to_vec, p2_to_vec,
+    pairing_to_vec, proof_to_evm_bytes, proof_to_hex, read_prepared_vk, repr_to_big, repr_to_hex,
+    scalar_modulus, PROOF_COORDINATES,
+};
+use crate::zkey_reader::{snarkjs_root_power, StreamedZkey};
 
 #[derive(Serialize, Deserialize)]
 struct CircuitJson {
@@ -99,11 +113,110 @@ struct VerifyingKeyJson {
     pub inputs_count: usize,
 }
 
-pub type Constraint<E> = (
-    Vec<(usize, <E as ScalarEngine>::Fr)>,
-    Vec<(usize, <E as ScalarEngine>::Fr)>,
-    Vec<(usize, <E as ScalarEngine>::Fr)>,
-);
+/// A constraint `A * B = C`, with each side a linear combination given as
+/// `(wire index, coefficient)` terms
+#[derive(Clone)]
+pub struct Constraint<E: Engine> {
+    pub a: Vec<(usize, E::Fr)>,
+    pub b: Vec<(usize, E::Fr)>,
+    pub c: Vec<(usize, E::Fr)>,
+}
+
+impl<E: Engine> PartialEq for Constraint<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.a == other.a && self.b == other.b && self.c == other.c
+    }
+}
+
+impl<E: Engine> std::fmt::Debug for Constraint<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Constraint")
+            .field("a", &self.a)
+            .field("b", &self.b)
+            .field("c", &self.c)
+            .finish()
+    }
+}
+
+impl<E: Engine> Constraint<E> {
+    pub fn new(a: Vec<(usize, E::Fr)>, b: Vec<(usize, E::Fr)>, c: Vec<(usize, E::Fr)>) -> Self {
+        Constraint { a, b, c }
+    }
+
+    /// Values of A, B and C for `witness`, which is indexed by wire and starts
+    /// with the constant one. Panics if a term references a wire past its end.
+    pub fn evaluate(&self, witness: &[E::Fr]) -> (E::Fr, E::Fr, E::Fr) {
+        // same accumulation as the prover's `eval`
+        let eval = |lc: &Vec<(usize, E::Fr)>| {
+            let mut acc = E::Fr::zero();
+            for (index, coeff) in lc.iter() {
+                let mut tmp = witness[*index];
+                tmp.mul_assign(coeff);
+                acc.add_assign(&tmp);
+            }
+            acc
+        };
+        (eval(&self.a), eval(&self.b), eval(&self.c))
+    }
+
+    // wire of every term of A, B and C, in that order
+    fn wires(&self) -> impl Iterator<Item = usize> + '_ {
+        self.a
+            .iter()
+            .chain(&self.b)
+            .chain(&self.c)
+            .map(|(wire, _)| *wire)
+    }
+
+    /// Whether `A * B = C` holds for `witness`. The sides are compared in constant time,
+    /// so the timing doesn't reveal where they differ.
+    pub fn is_satisfied(&self, witness: &[E::Fr]) -> bool {
+        let (mut ab, b, c) = self.evaluate(witness);
+        ab.mul_assign(&b);
+        fr_ct_eq(&ab, &c)
+    }
+}
+
+// Index of the first constraint `witness` doesn't satisfy, counting from `offset`, or
+// `usize::MAX` if it satisfies all of them. Every constraint is evaluated and the results are
+// folded without branching on them, so the time taken doesn't depend on which one fails.
+fn first_unsatisfied<E: Engine>(
+    constraints: &[Constraint<E>],
+    witness: &[E::Fr],
+    offset: usize,
+) -> usize {
+    constraints
+        .iter()
+        .enumerate()
+        .fold(usize::MAX, |first, (i, constraint)| {
+            // all ones for a satisfied constraint, the index otherwise
+            let candidate =
+                (offset + i) | (constraint.is_satisfied(witness) as usize).wrapping_neg();
+            first.min(candidate)
+        })
+}
+
+/// A custom gate template declared by a circom 2 circuit (`pragma custom_templates`)
+/// together with its compile-time parameters.
+#[derive(Clone)]
+pub struct CustomGate<E: Engine> {
+    pub template_name: String,
+    pub parameters: Vec<E::Fr>,
+}
+
+impl<E: Engine> PartialEq for CustomGate<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.template_name == other.template_name && self.parameters == other.parameters
+    }
+}
+
+/// One application of a custom gate: the index of its template in
+/// `R1CS::custom_gates` and the wires it is applied to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomGateUse {
+    pub gate: usize,
+    pub signals: Vec<u64>,
+}
 
 #[derive(Clone)]
 pub struct R1CS<E: Engine> {
@@ -111,6 +224,459 @@ pub struct R1CS<E: Engine> {
     pub num_aux: usize,
     pub num_variables: usize,
     pub constraints: Vec<Constraint<E>>,
+    /// Custom gate templates, only present in R1CS v2 files
+    pub custom_gates: Vec<CustomGate<E>>,
+    pub custom_gate_uses: Vec<CustomGateUse>,
+    /// Label id of every wire, indexed by wire. Empty unless read from a `.r1cs` file
+    pub wire_to_label: Vec<u64>,
+    /// Type, file offset and size of every section of the `.r1cs` file, see
+    /// `R1CSFile::sections`. Empty unless read from a file, and not compared by `==`.
+    pub sections: Vec<(u32, u64, u64)>,
+}
+
+impl<E: Engine> PartialEq for R1CS<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.num_inputs == other.num_inputs
+            && self.num_aux == other.num_aux
+            && self.num_variables == other.num_variables
+            && self.constraints == other.constraints
+            && self.custom_gates == other.custom_gates
+            && self.custom_gate_uses == other.custom_gate_uses
+            && self.wire_to_label == other.wire_to_label
+    }
+}
+
+// `lc` with its terms sorted by wire, terms of the same wire merged and zero coefficients dropped
+fn canonical_lc<F: PrimeField>(lc: &[(usize, F)]) -> Vec<(usize, F)> {
+    let mut merged = BTreeMap::<usize, F>::new();
+    for (index, coeff) in lc.iter() {
+        merged
+            .entry(*index)
+            .and_modify(|acc| acc.add_assign(coeff))
+            .or_insert(*coeff);
+    }
+    merged
+        .into_iter()
+        .filter(|(_, coeff)| !coeff.is_zero())
+        .collect_vec()
+}
+
+impl<E: Engine> R1CS<E> {
+    /// The constraints in file order, as a slice that can be split with `chunks` and handed
+    /// to worker threads
+    pub fn constraints(&self) -> &[Constraint<E>] {
+        &self.constraints
+    }
+
+    /// SHA-256 of the wire counts, constraints and custom gates, which identifies the
+    /// constraint system that parameters were generated for. Each linear combination is hashed
+    /// in the canonical form `canonicalize` gives it, so files that only differ in the order or
+    /// splitting of their terms have the same digest. All integers are hashed as little-endian
+    /// `u64`s and coefficients as 32-byte little-endian values, so the digest is the same on
+    /// every platform. Wire labels aren't included.
+    pub fn hash(&self) -> [u8; 32] {
+        fn write_lc<F: PrimeField>(state: &mut Sha256, lc: &[(usize, F)]) {
+            state.update((lc.len() as u64).to_le_bytes());
+            for (wire, coeff) in lc {
+                state.update((*wire as u64).to_le_bytes());
+                let mut repr = vec![];
+                coeff.into_repr().write_le(&mut repr).unwrap();
+                state.update(&repr);
+            }
+        }
+        let mut state = Sha256::new();
+        for n in [self.num_inputs, self.num_aux, self.num_variables, self.constraints.len()].iter() {
+            state.update((*n as u64).to_le_bytes());
+        }
+        for Constraint { a, b, c } in &self.constraints {
+            write_lc(&mut state, &canonical_lc(a));
+            write_lc(&mut state, &canonical_lc(b));
+            write_lc(&mut state, &canonical_lc(c));
+        }
+        for gate in &self.custom_gates {
+            state.update(gate.template_name.as_bytes());
+            state.update([0]);
+            let params = gate.parameters.iter().enumerate().map(|(i, p)| (i, *p)).collect_vec();
+            write_lc(&mut state, &params);
+        }
+        for gate_use in &self.custom_gate_uses {
+            state.update((gate_use.gate as u64).to_le_bytes());
+            state.update((gate_use.signals.len() as u64).to_le_bytes());
+            for signal in &gate_use.signals {
+                state.update(signal.to_le_bytes());
+            }
+        }
+        state.finalize().into()
+    }
+
+    /// Sorts the terms of every linear combination by wire index, merges terms
+    /// referencing the same wire and drops zero coefficients. The resulting
+    /// constraint system is semantically identical.
+    pub fn canonicalize(&mut self) {
+        for constraint in self.constraints.iter_mut() {
+            constraint.a = canonical_lc(&constraint.a);
+            constraint.b = canonical_lc(&constraint.b);
+            constraint.c = canonical_lc(&constraint.c);
+        }
+    }
+
+    /// Lists the sections the circuit was read from, one per line as
+    /// `<type> (<name>) at <offset>, <size> bytes`, with `unknown` for types the
+    /// format doesn't define
+    pub fn section_summary(&self) -> String {
+        self.sections
+            .iter()
+            .map(|(ty, offset, size)| {
+                let name = crate::r1cs_reader::section_name(*ty).unwrap_or("unknown");
+                format!("{} ({}) at {:#x}, {} bytes", ty, name, offset, size)
+            })
+            .join("\n")
+    }
+
+    /// Lists the first `max` constraints as `(a) * (b) = (c)`, one per line, with
+    /// signed decimal coefficients. Wire 0 is the constant one and is printed
+    /// as a bare coefficient. Other wires are printed as `l<label>` with their
+    /// label from `wire_to_label`, or as `w<index>` if the label map doesn't
+    /// cover them.
+    pub fn to_string_constraints(&self, max: usize) -> String {
+        let wire = |index: usize| match self.wire_to_label.get(index) {
+            Some(label) => format!("l{}", label),
+            None => format!("w{}", index),
+        };
+        let term_to_string = |(index, coeff): &(usize, E::Fr)| {
+            let coeff = fr_to_signed_decimal(coeff);
+            let (sign, abs) = match coeff.strip_prefix('-') {
+                Some(abs) => ("-", abs.to_string()),
+                None => ("+", coeff),
+            };
+            let term = match (*index, abs.as_str()) {
+                (0, _) => abs,
+                (i, "1") => wire(i),
+                (i, _) => format!("{}*{}", abs, wire(i)),
+            };
+            (sign, term)
+        };
+        let lc_to_string = |lc: &Vec<(usize, E::Fr)>| {
+            let mut result = String::new();
+            for (i, (sign, term)) in lc.iter().map(term_to_string).enumerate() {
+                match (i, sign) {
+                    (0, "-") => result.push('-'),
+                    (0, _) => {}
+                    (_, sign) => result.push_str(&format!(" {} ", sign)),
+                }
+                result.push_str(&term);
+            }
+            if result.is_empty() {
+                result.push('0');
+            }
+            result
+        };
+
+        let mut result = String::new();
+        for constraint in self.constraints.iter().take(max) {
+            result.push_str(&format!(
+                "({}) * ({}) = ({})\n",
+                lc_to_string(&constraint.a),
+                lc_to_string(&constraint.b),
+                lc_to_string(&constraint.c)
+            ));
+        }
+        if self.constraints.len() > max {
+            result.push_str(&format!(
+                "... {} more constraints\n",
+                self.constraints.len() - max
+            ));
+        }
+        result
+    }
+
+    /// Reverse index mapping every wire to the (ascending) indices of the
+    /// constraints referencing it. Each list is allocated with its exact
+    /// upper bound up front, so no reallocation happens on large circuits.
+    /// Wires referenced past `num_variables` get lists too, so the index is
+    /// longer than `num_variables` for such a malformed R1CS.
+    pub fn wire_usage(&self) -> Vec<Vec<usize>> {
+        let mut counts = vec![0usize; self.num_variables];
+        for constraint in self.constraints.iter() {
+            for wire in constraint.wires() {
+                if wire >= counts.len() {
+                    counts.resize(wire + 1, 0);
+                }
+                counts[wire] += 1;
+            }
+        }
+        let mut usage = counts.into_iter().map(Vec::with_capacity).collect_vec();
+        for (i, constraint) in self.constraints.iter().enumerate() {
+            for wire in constraint.wires() {
+                if usage[wire].last() != Some(&i) {
+                    usage[wire].push(i);
+                }
+            }
+        }
+        usage
+    }
+
+    /// Size of the FFT domain the prover will use: `effective_constraint_count`
+    /// rounded up to a power of two, as `utils::required_domain_size` computes it
+    /// for any curve. Fails with `SynthesisError::DomainTooLarge` if the scalar
+    /// field has no multiplicative subgroup that large, i.e. the circuit can't be
+    /// proven on this curve.
+    pub fn qap_degree(&self) -> Result<usize, SynthesisError> {
+        required_domain_size::<E::Fr>(effective_constraint_count(self))
+    }
+}
+
+/// A constraint the witness doesn't satisfy, with the nonzero terms of its A, B and C
+/// linear combinations as `(signal name, signed decimal coefficient)` pairs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintError {
+    pub index: usize,
+    pub a: Vec<(String, String)>,
+    pub b: Vec<(String, String)>,
+    pub c: Vec<(String, String)>,
+}
+
+impl std::fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let lc = |terms: &Vec<(String, String)>| {
+            let terms = terms
+                .iter()
+                .map(|(name, coeff)| match coeff.as_str() {
+                    "1" => name.clone(),
+                    "-1" => format!("-{}", name),
+                    _ => format!("{}*{}", coeff, name),
+                })
+                .join(" + ");
+            if terms.is_empty() {
+                String::from("0")
+            } else {
+                terms
+            }
+        };
+        write!(
+            f,
+            "constraint {} is not satisfied: ({}) * ({}) != ({})",
+            self.index,
+            lc(&self.a),
+            lc(&self.b),
+            lc(&self.c)
+        )
+    }
+}
+
+impl std::error::Error for ConstraintError {}
+
+/// Why `CircomCircuit::check_witness` rejected a circuit's witness
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WitnessError {
+    /// The circuit has no witness to check
+    Missing,
+    /// The witness or wire mapping doesn't fit the circuit, see `CircomCircuit::validate`
+    Invalid(ValidationError),
+    /// The first constraint the witness doesn't satisfy
+    Unsatisfied(ConstraintError),
+}
+
+impl WitnessError {
+    /// Index of the unsatisfied constraint, if that's what the witness failed on
+    pub fn constraint(&self) -> Option<usize> {
+        match self {
+            WitnessError::Unsatisfied(e) => Some(e.index),
+            _ => None,
+        }
+    }
+}
+
+impl From<ValidationError> for WitnessError {
+    fn from(e: ValidationError) -> Self {
+        WitnessError::Invalid(e)
+    }
+}
+
+impl std::fmt::Display for WitnessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WitnessError::Missing => write!(f, "circuit has no witness"),
+            WitnessError::Invalid(e) => e.fmt(f),
+            WitnessError::Unsatisfied(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for WitnessError {}
+
+/// Ways in which a circuit's R1CS, witness and wire mapping can fail to line up, see
+/// `CircomCircuit::validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The public and private wire counts don't add up to `num_variables`, or there is no
+    /// public wire for the constant one
+    InputCountMismatch {
+        num_inputs: usize,
+        num_aux: usize,
+        num_variables: usize,
+    },
+    /// A constraint references a wire that doesn't exist
+    WireOutOfRange {
+        constraint: usize,
+        wire: usize,
+        num_variables: usize,
+    },
+    /// The witness, or the wire mapping if there is one, doesn't have one entry per wire
+    WitnessLengthMismatch { expected: usize, found: usize },
+    /// The wire mapping points past the end of the witness
+    MappingOutOfRange {
+        wire: usize,
+        index: usize,
+        witness_len: usize,
+    },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationError::InputCountMismatch {
+                num_inputs,
+                num_aux,
+                num_variables,
+            } => write!(
+                f,
+                "{} public and {} private wires don't make up {} wires",
+                num_inputs, num_aux, num_variables
+            ),
+            ValidationError::WireOutOfRange {
+                constraint,
+                wire,
+                num_variables,
+            } => write!(
+                f,
+                "constraint {} references wire {} out of {}",
+                constraint, wire, num_variables
+            ),
+            ValidationError::WitnessLengthMismatch { expected, found } => write!(
+                f,
+                "witness has {} entries, the circuit has {} wires",
+                found, expected
+            ),
+            ValidationError::MappingOutOfRange {
+                wire,
+                index,
+                witness_len,
+            } => write!(
+                f,
+                "wire {} maps to witness entry {} out of {}",
+                wire, index, witness_len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Sizes that determine the cost of proving a circuit, see `CircomCircuit::diagnostics`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitDiagnostics {
+    /// Constraints in the R1CS
+    pub num_constraints: usize,
+    /// Constraints the prover works with, see `effective_constraint_count`
+    pub domain_constraints: usize,
+    /// Size of the radix-2 FFT domain, the power of two at or above `domain_constraints`
+    pub domain_size: usize,
+    /// `2^S` for the two-adicity `S` of the scalar field, the largest domain it has. The
+    /// circuit can be proven while `domain_constraints` is below this
+    pub max_domain_size: usize,
+    /// Multiexp lengths of a proof
+    pub op_counts: OpCounts,
+    /// Total bases of the G1 multiexps: H, L, A and B in G1
+    pub g1_terms: usize,
+    /// Total bases of the B in G2 multiexp
+    pub g2_terms: usize,
+}
+
+impl CircuitDiagnostics {
+    pub fn fits_domain(&self) -> bool {
+        self.domain_constraints < self.max_domain_size
+    }
+}
+
+/// Builds an `R1CS` in code, e.g. for tests and small hand-written circuits.
+///
+/// Wires follow the circom layout: wire 0 is the constant one, followed by
+/// the public outputs and inputs, followed by private wires.
+#[derive(Clone)]
+pub struct R1CSBuilder<E: Engine> {
+    num_public: usize,
+    num_variables: usize,
+    constraints: Vec<Constraint<E>>,
+}
+
+impl<E: Engine> R1CSBuilder<E> {
+    /// Creates a builder for a circuit with `num_variables` wires, including the constant one wire.
+    pub fn new(num_variables: usize) -> Self {
+        R1CSBuilder {
+            num_public: 0,
+            num_variables,
+            constraints: vec![],
+        }
+    }
+
+    /// Sets the number of public wires (outputs and inputs), not counting the constant one wire.
+    pub fn set_public_count(&mut self, num_public: usize) -> &mut Self {
+        self.num_public = num_public;
+        self
+    }
+
+    /// Adds `a * b = c` where each linear combination is a list of `(wire, coeff)` terms.
+    pub fn add_constraint(
+        &mut self,
+        a: Vec<(usize, E::Fr)>,
+        b: Vec<(usize, E::Fr)>,
+        c: Vec<(usize, E::Fr)>,
+    ) -> &mut Self {
+        self.constraints.push(Constraint { a, b, c });
+        self
+    }
+
+    pub fn build(&self) -> std::io::Result<R1CS<E>> {
+        let num_inputs = self.num_public + 1;
+        if num_inputs > self.num_variables {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "{} public wires don't fit into {} variables",
+                    self.num_public, self.num_variables
+                ),
+            ));
+        }
+        for (i, constraint) in self.constraints.iter().enumerate() {
+            let terms = constraint
+                .a
+                .iter()
+                .chain(constraint.b.iter())
+                .chain(constraint.c.iter());
+            for (wire, _) in terms {
+                if *wire >= self.num_variables {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!(
+                            "constraint {} references wire {} out of {}",
+                            i, wire, self.num_variables
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(R1CS {
+            num_inputs,
+            num_aux: self.num_variables - num_inputs,
+            num_variables: self.num_variables,
+            constraints: self.constraints.clone(),
+            custom_gates: vec![],
+            custom_gate_uses: vec![],
+            wire_to_label: vec![],
+            sections: vec![],
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -122,19 +688,266 @@ pub struct CircomCircuit<E: Engine> {
 }
 
 impl<'a, E: Engine> CircomCircuit<E> {
+    /// Number of public signals, i.e. the length of `get_public_inputs`. Wire 0 always
+    /// holds the constant one and isn't counted, unlike in `R1CS::num_inputs`. Zero for an
+    /// R1CS without even the constant wire, which `validate` rejects.
+    pub fn num_inputs(&self) -> usize {
+        self.r1cs.num_inputs.saturating_sub(1)
+    }
+
+    /// Number of private wires, which follow the constant wire and the public signals
+    pub fn num_aux(&self) -> usize {
+        self.r1cs.num_aux
+    }
+
+    /// The public outputs followed by the public inputs, i.e. witness entries
+    /// `1..num_inputs` with the constant one wire skipped. `None` without a witness, or if
+    /// the witness or the wire mapping is too short for the public wires or the mapping
+    /// points past the end of the witness.
     pub fn get_public_inputs(&self) -> Option<Vec<E::Fr>> {
-        match &self.witness {
-            None => None,
-            Some(w) => match &self.wire_mapping {
-                None => Some(w[1..self.r1cs.num_inputs].to_vec()),
-                Some(m) => Some(
-                    m[1..self.r1cs.num_inputs]
-                        .iter()
-                        .map(|i| w[*i])
-                        .collect_vec(),
-                ),
-            },
+        // wire 0 is the constant one, which verifiers add themselves
+        let public = 1..self.r1cs.num_inputs;
+        let w = self.witness.as_ref()?;
+        match &self.wire_mapping {
+            None => Some(w.get(public)?.to_vec()),
+            Some(m) => m.get(public)?.iter().map(|i| w.get(*i).copied()).collect(),
+        }
+    }
+
+    /// The witness split into the constant one and public signals, entries `0..num_inputs`,
+    /// and the private wires after them, e.g. to hand only the private half to a delegated
+    /// prover. Follows `wire_mapping` like `get_public_inputs`. `None` without a witness, or
+    /// if the witness or the wire mapping is too short for the circuit's wires or the mapping
+    /// points past the end of the witness.
+    #[allow(clippy::type_complexity)]
+    pub fn split_witness(&self) -> Option<(Vec<E::Fr>, Vec<E::Fr>)> {
+        let w = self.witness.as_ref()?;
+        let wires = self.r1cs.num_inputs + self.r1cs.num_aux;
+        let ordered = match &self.wire_mapping {
+            None => w.get(..wires)?.to_vec(),
+            Some(m) => m
+                .get(..wires)?
+                .iter()
+                .map(|i| w.get(*i).copied())
+                .collect::<Option<Vec<_>>>()?,
+        };
+        let (public, private) = ordered.split_at(self.r1cs.num_inputs);
+        Some((public.to_vec(), private.to_vec()))
+    }
+
+    /// Label id of witness wire `i`, for looking the signal up in the `.sym` file
+    pub fn label_of_wire(&self, i: usize) -> Option<u64> {
+        self.r1cs.wire_to_label.get(i).copied()
+    }
+
+    /// Checks that the wire counts add up, that constraints only reference existing wires and
+    /// that the witness, if any, has one entry per wire, so mismatched artifacts are reported
+    /// before proving rather than as a panic or an invalid proof
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let r1cs = &self.r1cs;
+        if r1cs.num_inputs == 0 || r1cs.num_inputs + r1cs.num_aux != r1cs.num_variables {
+            return Err(ValidationError::InputCountMismatch {
+                num_inputs: r1cs.num_inputs,
+                num_aux: r1cs.num_aux,
+                num_variables: r1cs.num_variables,
+            });
+        }
+        for (i, constraint) in r1cs.constraints.iter().enumerate() {
+            let terms = constraint
+                .a
+                .iter()
+                .chain(constraint.b.iter())
+                .chain(constraint.c.iter());
+            for (wire, _) in terms {
+                if *wire >= r1cs.num_variables {
+                    return Err(ValidationError::WireOutOfRange {
+                        constraint: i,
+                        wire: *wire,
+                        num_variables: r1cs.num_variables,
+                    });
+                }
+            }
+        }
+        let witness = match &self.witness {
+            None => return Ok(()),
+            Some(w) => w,
+        };
+        let found = match &self.wire_mapping {
+            None => witness.len(),
+            Some(m) => m.len(),
+        };
+        if found != r1cs.num_variables {
+            return Err(ValidationError::WitnessLengthMismatch {
+                expected: r1cs.num_variables,
+                found,
+            });
+        }
+        if let Some(m) = &self.wire_mapping {
+            if let Some((wire, index)) = m.iter().enumerate().find(|(_, i)| **i >= witness.len()) {
+                return Err(ValidationError::MappingOutOfRange {
+                    wire,
+                    index: *index,
+                    witness_len: witness.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The witness in wire order, after checking it with `validate`
+    fn checked_witness(&self) -> Result<Cow<'_, [E::Fr]>, WitnessError> {
+        let witness = self.witness.as_ref().ok_or(WitnessError::Missing)?;
+        self.validate()?;
+        Ok(match &self.wire_mapping {
+            None => Cow::Borrowed(&witness[..]),
+            Some(m) => Cow::Owned(m.iter().map(|i| witness[*i]).collect_vec()),
+        })
+    }
+
+    /// The error for constraint `index`, naming its wires with `name_of_wire`
+    fn unsatisfied(
+        &self,
+        index: usize,
+        name_of_wire: impl Fn(usize) -> Option<String>,
+    ) -> WitnessError {
+        let named = |lc: &Vec<(usize, E::Fr)>| {
+            lc.iter()
+                .filter(|(_, coeff)| !coeff.is_zero())
+                .map(|(wire, coeff)| {
+                    let name = match (wire, name_of_wire(*wire)) {
+                        (0, _) => String::from("one"),
+                        (_, Some(name)) => name,
+                        (_, None) => format!("wire {}", wire),
+                    };
+                    (name, fr_to_signed_decimal(coeff))
+                })
+                .collect_vec()
+        };
+        let constraint = &self.r1cs.constraints[index];
+        WitnessError::Unsatisfied(ConstraintError {
+            index,
+            a: named(&constraint.a),
+            b: named(&constraint.b),
+            c: named(&constraint.c),
+        })
+    }
+
+    /// Evaluates every constraint against the witness and reports the first one where
+    /// `A * B != C`, with its wires named by index. The circuit is checked with `validate`
+    /// first, so a missing, short or mismatched witness is an error rather than a panic.
+    /// All constraints are evaluated before the result is looked at, so the time taken
+    /// doesn't reveal which one failed.
+    pub fn check_witness(&self) -> Result<(), WitnessError> {
+        let values = self.checked_witness()?;
+        match first_unsatisfied(&self.r1cs.constraints, &values, 0) {
+            usize::MAX => Ok(()),
+            i => Err(self.unsatisfied(i, |_| None)),
+        }
+    }
+
+    /// `check_witness` with the constraints split across all cores. Each chunk reports its
+    /// first failing constraint and the lowest of those is returned, so the result is the
+    /// same as the sequential one regardless of scheduling.
+    #[cfg(feature = "parallel")]
+    pub fn check_witness_parallel(&self) -> Result<(), WitnessError> {
+        self.check_witness_on(&bellman_ce::worker::Worker::new())
+    }
+
+    #[cfg(feature = "parallel")]
+    fn check_witness_on(&self, worker: &bellman_ce::worker::Worker) -> Result<(), WitnessError> {
+        let values = self.checked_witness()?;
+        let values = &values[..];
+        let constraints = &self.r1cs.constraints;
+        let chunk_size = worker.get_chunk_size(constraints.len());
+        let mut failures = vec![usize::MAX; constraints.len().div_ceil(chunk_size)];
+        worker.scope(constraints.len(), |scope, chunk_size| {
+            for (i, (chunk, failure)) in constraints
+                .chunks(chunk_size)
+                .zip(failures.iter_mut())
+                .enumerate()
+            {
+                scope.spawn(move |_| {
+                    *failure = first_unsatisfied(chunk, values, i * chunk_size);
+                });
+            }
+        });
+        match failures.into_iter().fold(usize::MAX, usize::min) {
+            usize::MAX => Ok(()),
+            i => Err(self.unsatisfied(i, |_| None)),
+        }
+    }
+
+    /// Like `check_witness`, but the error names the signals of the failing constraint's
+    /// nonzero terms, taken from the circuit's `.sym` file
+    pub fn check_witness_named(&self, sym: &SymTable) -> Result<(), WitnessError> {
+        match self.check_witness() {
+            Err(WitnessError::Unsatisfied(e)) => {
+                Err(self.unsatisfied(e.index, |wire| sym.name_of_wire(wire).map(str::to_string)))
+            }
+            result => result,
+        }
+    }
+
+    /// Number of nonzero terms in each constraint, summed over its A, B and C
+    /// linear combinations
+    pub fn constraint_density_histogram(&self) -> Vec<usize> {
+        let nonzero =
+            |lc: &Vec<(usize, E::Fr)>| lc.iter().filter(|(_, coeff)| !coeff.is_zero()).count();
+        self.r1cs
+            .constraints
+            .iter()
+            .map(|Constraint { a, b, c }| nonzero(a) + nonzero(b) + nonzero(c))
+            .collect_vec()
+    }
+
+    /// Mean of `constraint_density_histogram`, 0 for a circuit without constraints
+    pub fn average_density(&self) -> f64 {
+        let densities = self.constraint_density_histogram();
+        if densities.is_empty() {
+            return 0.0;
         }
+        densities.iter().sum::<usize>() as f64 / densities.len() as f64
+    }
+
+    /// Ascending indices of the constraints whose A, B or C has a nonzero coefficient for
+    /// wire `w`, found by scanning all constraints. Empty for a wire no constraint uses,
+    /// unlike `R1CS::wire_usage` this also skips terms with a zero coefficient.
+    pub fn constraints_with_wire(&self, w: usize) -> Vec<usize> {
+        let uses = |lc: &Vec<(usize, E::Fr)>| {
+            lc.iter()
+                .any(|(index, coeff)| *index == w && !coeff.is_zero())
+        };
+        self.r1cs
+            .constraints
+            .iter()
+            .enumerate()
+            .filter(|(_, Constraint { a, b, c })| uses(a) || uses(b) || uses(c))
+            .map(|(i, _)| i)
+            .collect_vec()
+    }
+
+    /// Constraint count, domain size and multiexp lengths of proving this circuit. The
+    /// densities come from synthesizing the circuit structure from a borrow of the R1CS, the
+    /// witness isn't used. Also works for circuits too large to prove, which `fits_domain`
+    /// reports.
+    pub fn diagnostics(&self) -> Result<CircuitDiagnostics, SynthesisError> {
+        let layout = ConstraintLayout::new(R1CSStructure(&self.r1cs))?;
+        let op_counts = layout.operation_counts();
+        let domain_constraints = layout.num_constraints();
+        Ok(CircuitDiagnostics {
+            num_constraints: self.r1cs.constraints.len(),
+            domain_constraints,
+            domain_size: domain_constraints.next_power_of_two(),
+            max_domain_size: 1 << E::Fr::S,
+            op_counts,
+            g1_terms: op_counts.h
+                + op_counts.l
+                + op_counts.a_inputs
+                + op_counts.a_aux
+                + op_counts.b_g1_inputs
+                + op_counts.b_g1_aux,
+            g2_terms: op_counts.b_g2_inputs + op_counts.b_g2_aux,
+        })
     }
 
     pub fn get_public_inputs_json(&self) -> String {
@@ -147,69 +960,153 @@ impl<'a, E: Engine> CircomCircuit<E> {
     }
 }
 
+/// Builds a `CircomCircuit` from an `R1CS`. Without a witness the circuit is in setup mode:
+/// it can be synthesized for `generate_random_parameters`, but `prove` rejects it.
+#[derive(Clone)]
+pub struct CircomCircuitBuilder<E: Engine> {
+    r1cs: R1CS<E>,
+    witness: Option<Vec<E::Fr>>,
+    wire_mapping: Option<Vec<usize>>,
+}
+
+impl<E: Engine> CircomCircuitBuilder<E> {
+    pub fn new(r1cs: R1CS<E>) -> Self {
+        CircomCircuitBuilder {
+            r1cs,
+            witness: None,
+            wire_mapping: None,
+        }
+    }
+
+    pub fn witness(&mut self, witness: Vec<E::Fr>) -> &mut Self {
+        self.witness = Some(witness);
+        self
+    }
+
+    /// Sets the witness index of every wire, for witnesses not in wire order.
+    pub fn wire_mapping(&mut self, wire_mapping: Vec<usize>) -> &mut Self {
+        self.wire_mapping = Some(wire_mapping);
+        self
+    }
+
+    /// Fails with `InvalidInput` for any circuit `CircomCircuit::validate` rejects, e.g. a
+    /// witness or wire mapping without exactly one entry per wire.
+    pub fn build(&self) -> std::io::Result<CircomCircuit<E>> {
+        let circuit = CircomCircuit {
+            r1cs: self.r1cs.clone(),
+            witness: self.witness.clone(),
+            wire_mapping: self.wire_mapping.clone(),
+        };
+        circuit
+            .validate()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        Ok(circuit)
+    }
+}
+
 /// Our demo circuit implements this `Circuit` trait which
 /// is used during paramgen and proving in order to
 /// synthesize the constraint system.
 impl<'a, E: Engine> Circuit<E> for CircomCircuit<E> {
-    //noinspection RsBorrowChecker
     fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
-        let witness = &self.witness;
-        let wire_mapping = &self.wire_mapping;
-        for i in 1..self.r1cs.num_inputs {
-            cs.alloc_input(
-                || format!("variable {}", i),
-                || {
-                    Ok(match witness {
-                        None => E::Fr::from_str("1").unwrap(),
-                        Some(w) => match wire_mapping {
-                            None => w[i],
-                            Some(m) => w[m[i]],
-                        },
-                    })
-                },
-            )?;
-        }
-
-        for i in 0..self.r1cs.num_aux {
-            cs.alloc(
-                || format!("aux {}", i),
-                || {
-                    Ok(match witness {
-                        None => E::Fr::from_str("1").unwrap(),
-                        Some(w) => match wire_mapping {
-                            None => w[i + self.r1cs.num_inputs],
-                            Some(m) => w[m[i + self.r1cs.num_inputs]],
-                        },
-                    })
-                },
-            )?;
-        }
-
-        let make_index = |index| {
-            if index < self.r1cs.num_inputs {
-                Index::Input(index)
-            } else {
-                Index::Aux(index - self.r1cs.num_inputs)
-            }
-        };
-        let make_lc = |lc_data: Vec<(usize, E::Fr)>| {
-            lc_data.iter().fold(
-                LinearCombination::<E>::zero(),
-                |lc: LinearCombination<E>, (index, coeff)| {
-                    lc + (*coeff, Variable::new_unchecked(make_index(*index)))
-                },
-            )
+        synthesize_r1cs(
+            &self.r1cs,
+            self.witness.as_ref(),
+            self.wire_mapping.as_ref(),
+            cs,
+        )
+    }
+}
+
+/// The constraints of an `R1CS` without a witness, synthesized from a borrow
+struct R1CSStructure<'a, E: Engine>(&'a R1CS<E>);
+
+impl<'a, E: Engine> Circuit<E> for R1CSStructure<'a, E> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        synthesize_r1cs(self.0, None, None, cs)
+    }
+}
+
+//noinspection RsBorrowChecker
+fn synthesize_r1cs<E: Engine, CS: ConstraintSystem<E>>(
+    r1cs: &R1CS<E>,
+    witness: Option<&Vec<E::Fr>>,
+    wire_mapping: Option<&Vec<usize>>,
+    cs: &mut CS,
+) -> Result<(), SynthesisError> {
+    if let Some(w) = witness {
+        let constant = match wire_mapping {
+            None => w.first(),
+            Some(m) => m.first().and_then(|i| w.get(*i)),
         };
-        for (i, constraint) in self.r1cs.constraints.iter().enumerate() {
-            cs.enforce(
-                || format!("constraint {}", i),
-                |_| make_lc(constraint.0.clone()),
-                |_| make_lc(constraint.1.clone()),
-                |_| make_lc(constraint.2.clone()),
-            );
+        if constant != Some(&E::Fr::one()) {
+            return Err(SynthesisError::InvalidConstantWire);
         }
-        Ok(())
     }
+    // Without a witness the placeholder values only give the constraint system its
+    // shape, which is all parameter generation needs. `prove` refuses such circuits.
+    for i in 1..r1cs.num_inputs {
+        cs.alloc_input(
+            || format!("variable {}", i),
+            || {
+                Ok(match witness {
+                    None => E::Fr::from_str("1").unwrap(),
+                    Some(w) => match wire_mapping {
+                        None => w[i],
+                        Some(m) => w[m[i]],
+                    },
+                })
+            },
+        )?;
+    }
+
+    for i in 0..r1cs.num_aux {
+        cs.alloc(
+            || format!("aux {}", i),
+            || {
+                Ok(match witness {
+                    None => E::Fr::from_str("1").unwrap(),
+                    Some(w) => match wire_mapping {
+                        None => w[i + r1cs.num_inputs],
+                        Some(m) => w[m[i + r1cs.num_inputs]],
+                    },
+                })
+            },
+        )?;
+    }
+
+    let make_index = |index| {
+        if index < r1cs.num_inputs {
+            Index::Input(index)
+        } else {
+            Index::Aux(index - r1cs.num_inputs)
+        }
+    };
+    let make_lc = |lc_data: &Vec<(usize, E::Fr)>| {
+        lc_data.iter().fold(
+            LinearCombination::<E>::zero(),
+            |lc: LinearCombination<E>, (index, coeff)| {
+                lc + (*coeff, Variable::new_unchecked(make_index(*index)))
+            },
+        )
+    };
+    for (i, constraint) in r1cs.constraints.iter().enumerate() {
+        cs.enforce(
+            || format!("constraint {}", i),
+            |_| make_lc(&constraint.a),
+            |_| make_lc(&constraint.b),
+            |_| make_lc(&constraint.c),
+        );
+    }
+    Ok(())
+}
+
+/// Number of constraints the prover actually works with: `prepare_prover` adds
+/// an `x * 0 = 0` constraint for every input (including the constant one wire),
+/// so the FFT domain is sized for `num_constraints + num_inputs`. Parameters
+/// generated elsewhere must match this, or `get_h` will see a length mismatch.
+pub fn effective_constraint_count<E: Engine>(r1cs: &R1CS<E>) -> usize {
+    r1cs.constraints.len() + r1cs.num_inputs
 }
 
 pub fn prove<E: Engine, R: Rng>(
@@ -217,11 +1114,265 @@ pub fn prove<E: Engine, R: Rng>(
     params: &Parameters<E>,
     mut rng: R,
 ) -> Result<Proof<E>, SynthesisError> {
+    if circuit.witness.is_none() {
+        return Err(SynthesisError::AssignmentMissing);
+    }
     let mut params2 = params.clone();
     filter_params(&mut params2);
     create_random_proof(circuit, &params2, &mut rng)
 }
 
+/// Same as `prove`, first checking that `params` were generated for this circuit: `r1cs_hash`
+/// is the hash `utils::read_params` returns with them, which must equal `R1CS::hash` of
+/// `circuit`. Parameters of another circuit of the same size would otherwise give a proof
+/// that doesn't verify, after all the proving work.
+pub fn prove_checked<E: Engine, R: Rng>(
+    circuit: CircomCircuit<E>,
+    params: &Parameters<E>,
+    r1cs_hash: &[u8; 32],
+    rng: R,
+) -> Result<Proof<E>, SynthesisError> {
+    if circuit.r1cs.hash() != *r1cs_hash {
+        return Err(SynthesisError::CircuitHashMismatch);
+    }
+    prove(circuit, params, rng)
+}
+
+/// Same as `prove`, also returning how long the H evaluation, the conversion of the witness
+/// and the multiexps took, for metrics. Synthesis isn't included.
+pub fn create_proof_timed<E: Engine, R: Rng>(
+    circuit: CircomCircuit<E>,
+    params: &Parameters<E>,
+    mut rng: R,
+) -> Result<(Proof<E>, ProofTimings), SynthesisError> {
+    if circuit.witness.is_none() {
+        return Err(SynthesisError::AssignmentMissing);
+    }
+    let mut params2 = params.clone();
+    filter_params(&mut params2);
+    let prover = prepare_prover(circuit)?;
+    prover.create_proof_timed(&params2, rng.gen(), rng.gen())
+}
+
+/// Proves with parameters read from a snarkjs `.zkey` by `zkey_reader::read`. snarkjs
+/// places constraint `j` at a different point of the evaluation domain than `prepare_prover`
+/// does, so the constraint evaluations are computed here in the order the zkey expects.
+pub fn prove_zkey<R: Rng>(
+    circuit: CircomCircuit<Bn256>,
+    params: &Parameters<Bn256>,
+    mut rng: R,
+) -> Result<Proof<Bn256>, SynthesisError> {
+    let n_vars = params.a.len();
+    if params.b_g1.len() != n_vars || params.b_g2.len() != n_vars {
+        return Err(SynthesisError::InconsistentParameters);
+    }
+    let assignment = ZkeyAssignment::new(&circuit, params.h.len() + 1, n_vars, params.l.len())?;
+    let num_inputs = circuit.r1cs.num_inputs;
+    let densities = &assignment.densities;
+
+    // zkeys have a point for every variable, bellman's prover expects the used ones only
+    fn dense<G: CurveAffine>(points: &[G], used: impl Iterator<Item = bool>) -> Arc<Vec<G>> {
+        Arc::new(
+            points
+                .iter()
+                .zip(used)
+                .filter(|(_, used)| *used)
+                .map(|(p, _)| *p)
+                .collect(),
+        )
+    }
+    let mut params = params.clone();
+    params.a = dense(
+        &params.a,
+        std::iter::repeat_n(true, num_inputs).chain(densities.a_aux.iter()),
+    );
+    let b_used = || densities.b_input.iter().chain(densities.b_aux.iter());
+    params.b_g1 = dense(&params.b_g1, b_used());
+    params.b_g2 = dense(&params.b_g2, b_used());
+
+    let prover = assignment.into_prover()?;
+    prover.create_proof(&params, Fr::rand(&mut rng), Fr::rand(&mut rng))
+}
+
+/// Same as `prove_zkey` for a zkey indexed by `zkey_reader::load_streaming`, reading the
+/// points of the queries from the file while the multiexps run.
+pub fn prove_zkey_streaming<R: Rng>(
+    circuit: CircomCircuit<Bn256>,
+    zkey: &StreamedZkey,
+    mut rng: R,
+) -> Result<Proof<Bn256>, SynthesisError> {
+    let n_vars = zkey.num_variables();
+    let n_l = n_vars - zkey.vk().ic.len();
+    let assignment = ZkeyAssignment::new(&circuit, zkey.domain_size(), n_vars, n_l)?;
+    let params = zkey.parameters(circuit.r1cs.num_inputs, &assignment.densities);
+    let prover = assignment.into_prover()?;
+    prover.create_proof(params, Fr::rand(&mut rng), Fr::rand(&mut rng))
+}
+
+/// The constraint evaluations of a circuit at the domain points snarkjs puts them at, with the
+/// densities of its queries and its assignment, which `prove_zkey` and `prove_zkey_streaming`
+/// prove from
+struct ZkeyAssignment {
+    evaluations: (Vec<Fr>, Vec<Fr>, Vec<Fr>),
+    densities: QueryDensities,
+    inputs: Vec<Fr>,
+    aux: Vec<Fr>,
+}
+
+impl ZkeyAssignment {
+    /// Checks the circuit against a zkey with an evaluation domain of `domain_size`,
+    /// `n_vars` variables and `n_l` points in its `C` section
+    fn new(
+        circuit: &CircomCircuit<Bn256>,
+        domain_size: usize,
+        n_vars: usize,
+        n_l: usize,
+    ) -> Result<Self, SynthesisError> {
+        let witness = circuit
+            .witness
+            .as_ref()
+            .ok_or(SynthesisError::AssignmentMissing)?;
+        let r1cs = &circuit.r1cs;
+        // a short witness or wire mapping leaves some wire without a value
+        let mut values = (0..r1cs.num_variables)
+            .map(|index| match &circuit.wire_mapping {
+                None => witness.get(index).copied(),
+                Some(m) => m.get(index).and_then(|i| witness.get(*i)).copied(),
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(SynthesisError::AssignmentMissing)?;
+        let value = |index: usize| {
+            values
+                .get(index)
+                .copied()
+                .ok_or(SynthesisError::AssignmentMissing)
+        };
+        if value(0)? != Fr::one() {
+            return Err(SynthesisError::InvalidConstantWire);
+        }
+        let num_inputs = r1cs.num_inputs;
+        if effective_constraint_count(r1cs).next_power_of_two() != domain_size {
+            return Err(SynthesisError::InconsistentParameters);
+        }
+        // the zkey has a point in each of A, B1 and B2 for every variable of its circuit
+        if n_vars != r1cs.num_variables || n_l + num_inputs != r1cs.num_variables {
+            return Err(SynthesisError::InconsistentParameters);
+        }
+        let k = snarkjs_root_power(domain_size);
+
+        let tracker = |len: usize| {
+            let mut tracker = DensityTracker::new();
+            tracker.pad(len);
+            tracker
+        };
+        let mut densities = QueryDensities {
+            a_aux: tracker(r1cs.num_aux),
+            b_input: tracker(num_inputs),
+            b_aux: tracker(r1cs.num_aux),
+        };
+        let eval = |lc: &Vec<(usize, Fr)>| {
+            let mut acc = Fr::zero();
+            for (index, coeff) in lc.iter() {
+                let mut tmp = value(*index)?;
+                tmp.mul_assign(coeff);
+                acc.add_assign(&tmp);
+            }
+            Ok::<_, SynthesisError>(acc)
+        };
+        let mut a = vec![Fr::zero(); domain_size];
+        let mut b = a.clone();
+        let mut c = a.clone();
+        for (j, constraint) in r1cs.constraints.iter().enumerate() {
+            let pos = j * k % domain_size;
+            a[pos] = eval(&constraint.a)?;
+            b[pos] = eval(&constraint.b)?;
+            c[pos] = eval(&constraint.c)?;
+            for (index, _) in constraint.a.iter() {
+                if *index >= num_inputs {
+                    densities.a_aux.inc(index - num_inputs);
+                }
+            }
+            for (index, _) in constraint.b.iter() {
+                if *index < num_inputs {
+                    densities.b_input.inc(*index);
+                } else {
+                    densities.b_aux.inc(index - num_inputs);
+                }
+            }
+        }
+        // the `x * 0 = 0` constraint of every input follows the circuit's constraints
+        for s in 0..num_inputs {
+            a[(r1cs.constraints.len() + s) * k % domain_size] = values[s];
+        }
+
+        let aux = values.split_off(num_inputs);
+        Ok(ZkeyAssignment {
+            evaluations: (a, b, c),
+            densities,
+            inputs: values,
+            aux,
+        })
+    }
+
+    fn into_prover(self) -> Result<PreparedProver<Bn256>, SynthesisError> {
+        let (a, b, c) = self.evaluations;
+        PreparedProver::from_evaluations(a, b, c, self.densities, self.inputs, self.aux)
+    }
+}
+
+/// Proves with the blinding factors `r` and `s` drawn from a ChaCha RNG seeded
+/// with `seed`, so the same circuit, parameters and seed give the same proof.
+pub fn prove_with_seed<E: Engine>(
+    circuit: CircomCircuit<E>,
+    params: &Parameters<E>,
+    seed: u64,
+) -> Result<Proof<E>, SynthesisError> {
+    prove(circuit, params, seeded_rng(seed))
+}
+
+/// ChaCha RNG seeded with `seed`, for reproducible proofs and parameters. This is
+/// `utils::create_rng_from_seed` keyed with the little-endian bytes of `seed`, zero-padded,
+/// so the same warnings apply.
+pub fn seeded_rng(seed: u64) -> ChaChaRng {
+    let mut key = [0u8; 32];
+    key[..8].copy_from_slice(&seed.to_le_bytes());
+    create_rng_from_seed(&key)
+}
+
+/// Golden-vector check: proves with `prove_with_seed` and panics unless the
+/// proof equals `expected` (in `proof_to_evm_bytes` layout), naming every
+/// coordinate that differs.
+pub fn assert_proof_matches(
+    circuit: CircomCircuit<Bn256>,
+    params: &Parameters<Bn256>,
+    seed: u64,
+    expected: &[u8],
+) {
+    let proof = prove_with_seed(circuit, params, seed).expect("proving failed");
+    let actual = proof_to_evm_bytes(&proof);
+    assert_eq!(
+        expected.len(),
+        actual.len(),
+        "expected {} bytes, but a proof has {}",
+        expected.len(),
+        actual.len()
+    );
+    let diff = PROOF_COORDINATES
+        .iter()
+        .zip(expected.chunks(32).zip(actual.chunks(32)))
+        .filter(|(_, (e, a))| e != a)
+        .map(|(name, (e, a))| {
+            format!(
+                "{}: expected 0x{}, got 0x{}",
+                name,
+                e.iter().map(|b| format!("{:02x}", b)).join(""),
+                a.iter().map(|b| format!("{:02x}", b)).join("")
+            )
+        })
+        .collect_vec();
+    assert!(diff.is_empty(), "proof mismatch\n{}", diff.join("\n"));
+}
+
 pub fn generate_random_parameters<E: Engine, R: Rng>(
     circuit: CircomCircuit<E>,
     mut rng: R,
@@ -238,15 +1389,237 @@ pub fn verify_circuit<E: Engine>(
         None => return Err(SynthesisError::AssignmentMissing),
         Some(inp) => inp,
     };
-    verify_proof(&prepare_verifying_key(&params.vk), proof, &inputs)
-}
+    validate_verifying_key(&params.vk)?;
+    verify_proof(&prepare_verifying_key(&params.vk), proof, &inputs)
+}
+
+pub fn verify<E: Engine>(
+    params: &Parameters<E>,
+    proof: &Proof<E>,
+    inputs: &[E::Fr],
+) -> Result<bool, SynthesisError> {
+    validate_verifying_key(&params.vk)?;
+    verify_proof(&prepare_verifying_key(&params.vk), proof, &inputs)
+}
+
+/// Verifies a proof against public inputs given as `BigUint`s. In `strict` mode
+/// inputs not below the scalar field modulus are rejected with
+/// `SynthesisError::InputOutOfRange`, otherwise they are reduced modulo it.
+pub fn verify_proof_biguint<E: Engine>(
+    vk: &VerifyingKey<E>,
+    proof: &Proof<E>,
+    inputs: &[BigUint],
+    strict: bool,
+) -> Result<bool, SynthesisError> {
+    let modulus = scalar_modulus::<E>();
+    let inputs = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, x)| {
+            if strict && x >= &modulus {
+                return Err(SynthesisError::InputOutOfRange { index });
+            }
+            Ok(biguint_to_fr(&(x % &modulus)).unwrap())
+        })
+        .collect::<Result<Vec<E::Fr>, _>>()?;
+    validate_verifying_key(vk)?;
+    verify_proof(&prepare_verifying_key(vk), proof, &inputs)
+}
+
+/// Verifies a proof and reports how long verification took. The timer covers
+/// preparing `vk` plus the input MSM and pairings; key validation is excluded.
+pub fn verify_proof_timed<E: Engine>(
+    vk: &VerifyingKey<E>,
+    proof: &Proof<E>,
+    inputs: &[E::Fr],
+) -> Result<(bool, Duration), SynthesisError> {
+    validate_verifying_key(vk)?;
+    let start = Instant::now();
+    let pvk = prepare_verifying_key(vk);
+    let valid = verify_proof(&pvk, proof, inputs)?;
+    Ok((valid, start.elapsed()))
+}
+
+/// Same as `verify_proof_timed` for an already prepared key, so only the input
+/// MSM and pairings are timed.
+pub fn verify_prepared_timed<E: Engine>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &Proof<E>,
+    inputs: &[E::Fr],
+) -> Result<(bool, Duration), SynthesisError> {
+    let start = Instant::now();
+    let valid = verify_proof(pvk, proof, inputs)?;
+    Ok((valid, start.elapsed()))
+}
+
+/// Verifies a proof against a prepared verifying key serialized with
+/// `write_prepared_vk`. This is the fastest cold-start path since nothing
+/// needs to be re-derived from the verifying key.
+pub fn verify_prepared_bytes(
+    pvk_bytes: &[u8],
+    proof: &Proof<Bn256>,
+    inputs: &[<Bn256 as ScalarEngine>::Fr],
+) -> Result<bool, SynthesisError> {
+    let pvk = read_prepared_vk(pvk_bytes)?;
+    verify_proof(&pvk, proof, inputs)
+}
+
+#[derive(Debug)]
+pub enum MultiVkError {
+    /// No verifying key is registered for the requested version
+    UnknownVkVersion,
+    Synthesis(SynthesisError),
+}
+
+impl From<SynthesisError> for MultiVkError {
+    fn from(e: SynthesisError) -> Self {
+        MultiVkError::Synthesis(e)
+    }
+}
+
+impl std::fmt::Display for MultiVkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MultiVkError::UnknownVkVersion => write!(f, "unknown verifying key version"),
+            MultiVkError::Synthesis(e) => e.fmt(f),
+        }
+    }
+}
+
+/// Verifies proofs against one of several verifying keys selected by a
+/// version tag, e.g. when keys are rotated over time.
+pub struct MultiVkVerifier<E: Engine, V: Ord> {
+    keys: BTreeMap<V, PreparedVerifyingKey<E>>,
+}
+
+impl<E: Engine, V: Ord> MultiVkVerifier<E, V> {
+    pub fn new() -> Self {
+        MultiVkVerifier {
+            keys: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, version: V, vk: &VerifyingKey<E>) -> Result<(), SynthesisError> {
+        validate_verifying_key(vk)?;
+        self.keys.insert(version, prepare_verifying_key(vk));
+        Ok(())
+    }
+
+    pub fn insert_prepared(&mut self, version: V, pvk: PreparedVerifyingKey<E>) {
+        self.keys.insert(version, pvk);
+    }
+
+    pub fn verify_versioned(
+        &self,
+        version: &V,
+        proof: &Proof<E>,
+        inputs: &[E::Fr],
+    ) -> Result<bool, MultiVkError> {
+        let pvk = self
+            .keys
+            .get(version)
+            .ok_or(MultiVkError::UnknownVkVersion)?;
+        Ok(verify_proof(pvk, proof, inputs)?)
+    }
+}
+
+impl<E: Engine, V: Ord> Default for MultiVkVerifier<E, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Rejects a verifying key containing identity points. An identity `ic` entry
+/// means the corresponding public input has no effect on verification, so any
+/// value for it would be accepted.
+pub fn validate_verifying_key<E: Engine>(vk: &VerifyingKey<E>) -> Result<(), SynthesisError> {
+    if vk.alpha_g1.is_zero()
+        || vk.beta_g1.is_zero()
+        || vk.beta_g2.is_zero()
+        || vk.gamma_g2.is_zero()
+        || vk.delta_g1.is_zero()
+        || vk.delta_g2.is_zero()
+    {
+        return Err(SynthesisError::SuspiciousVerifyingKey);
+    }
+    if vk.ic.is_empty() || vk.ic.iter().any(|p| p.is_zero()) {
+        return Err(SynthesisError::SuspiciousVerifyingKey);
+    }
+    Ok(())
+}
+
+pub fn validate_parameters<E: Engine>(params: &Parameters<E>) -> Result<(), SynthesisError> {
+    validate_verifying_key(&params.vk)
+}
+
+/// Spells out the pairing equation `verify` checks for this proof, listing every
+/// point with its hex coordinates and the input combination `IC`. Intended for
+/// documentation and teaching; the exact format is not stable.
+pub fn describe_verification(
+    vk: &VerifyingKey<Bn256>,
+    proof: &Proof<Bn256>,
+    inputs: &[<Bn256 as ScalarEngine>::Fr],
+) -> String {
+    let g1 = |p: &G1Affine| {
+        if p.is_zero() {
+            return "infinity".to_string();
+        }
+        let xy = p.into_xy_unchecked();
+        format!(
+            "(0x{}, 0x{})",
+            repr_to_hex(xy.0.into_repr()),
+            repr_to_hex(xy.1.into_repr())
+        )
+    };
+    let g2 = |p: &G2Affine| {
+        if p.is_zero() {
+            return "infinity".to_string();
+        }
+        let xy = p.into_xy_unchecked();
+        format!(
+            "((0x{}, 0x{}), (0x{}, 0x{}))",
+            repr_to_hex(xy.0.c0.into_repr()),
+            repr_to_hex(xy.0.c1.into_repr()),
+            repr_to_hex(xy.1.c0.into_repr()),
+            repr_to_hex(xy.1.c1.into_repr())
+        )
+    };
 
-pub fn verify<E: Engine>(
-    params: &Parameters<E>,
-    proof: &Proof<E>,
-    inputs: &[E::Fr],
-) -> Result<bool, SynthesisError> {
-    verify_proof(&prepare_verifying_key(&params.vk), proof, &inputs)
+    let mut result = String::new();
+    result.push_str("e(A, B) = e(alpha, beta) * e(IC, gamma) * e(C, delta)\n");
+    result.push_str(&format!("where A = {}\n", g1(&proof.a)));
+    result.push_str(&format!("      B = {}\n", g2(&proof.b)));
+    result.push_str(&format!("      C = {}\n", g1(&proof.c)));
+    result.push_str(&format!("      alpha = {}\n", g1(&vk.alpha_g1)));
+    result.push_str(&format!("      beta = {}\n", g2(&vk.beta_g2)));
+    result.push_str(&format!("      gamma = {}\n", g2(&vk.gamma_g2)));
+    result.push_str(&format!("      delta = {}\n", g2(&vk.delta_g2)));
+    if vk.ic.len() == inputs.len() + 1 {
+        let mut ic = vk.ic[0].into_projective();
+        let mut terms = vec!["IC[0]".to_string()];
+        for (i, (input, base)) in inputs.iter().zip(vk.ic.iter().skip(1)).enumerate() {
+            ic.add_assign(&base.mul(input.into_repr()));
+            terms.push(format!("{} * IC[{}]", fr_to_signed_decimal(input), i + 1));
+        }
+        result.push_str(&format!(
+            "      IC = {} = {}\n",
+            terms.join(" + "),
+            g1(&ic.into_affine())
+        ));
+    } else {
+        result.push_str(&format!(
+            "      IC is undefined: {} inputs given for {} IC points\n",
+            inputs.len(),
+            vk.ic.len()
+        ));
+    }
+    let outcome = match verify_proof(&prepare_verifying_key(vk), proof, inputs) {
+        Ok(true) => "holds".to_string(),
+        Ok(false) => "does not hold".to_string(),
+        Err(e) => format!("could not be evaluated: {}", e),
+    };
+    result.push_str(&format!("The equation {}\n", outcome));
+    result
 }
 
 pub fn create_verifier_sol(params: &Parameters<Bn256>) -> String {
@@ -323,7 +1696,7 @@ pub fn proof_to_json_file(proof: &Proof<Bn256>, filename: &str) -> std::io::Resu
     fs::write(filename, str.as_bytes())
 }
 
-pub fn load_params_file(filename: &str) -> Parameters<Bn256> {
+pub fn load_params_file<E: Engine>(filename: &str) -> Parameters<E> {
     let reader = OpenOptions::new()
         .read(true)
         .open(filename)
@@ -331,7 +1704,7 @@ pub fn load_params_file(filename: &str) -> Parameters<Bn256> {
     load_params(reader)
 }
 
-pub fn load_params<R: Read>(reader: R) -> Parameters<Bn256> {
+pub fn load_params<E: Engine, R: Read>(reader: R) -> Parameters<E> {
     Parameters::read(reader, true).expect("unable to read params")
 }
 
@@ -437,13 +1810,13 @@ pub fn proving_key_json(
         pols_c.push(BTreeMap::new());
     }
     for c in 0..circuit.r1cs.constraints.len() {
-        for item in circuit.r1cs.constraints[c].0.iter() {
+        for item in circuit.r1cs.constraints[c].a.iter() {
             pols_a[item.0].insert(c.to_string(), repr_to_big(item.1.into_repr()));
         }
-        for item in circuit.r1cs.constraints[c].1.iter() {
+        for item in circuit.r1cs.constraints[c].b.iter() {
             pols_b[item.0].insert(c.to_string(), repr_to_big(item.1.into_repr()));
         }
-        for item in circuit.r1cs.constraints[c].2.iter() {
+        for item in circuit.r1cs.constraints[c].c.iter() {
             pols_c[item.0].insert(c.to_string(), repr_to_big(item.1.into_repr()));
         }
     }
@@ -455,7 +1828,7 @@ pub fn proving_key_json(
         );
     }
 
-    let domain_bits = log2_floor(circuit.r1cs.constraints.len() + circuit.r1cs.num_inputs) + 1;
+    let domain_bits = log2_floor(effective_constraint_count(&circuit.r1cs)) + 1;
     let n_public = circuit.r1cs.num_inputs - 1;
     let n_vars = circuit.r1cs.num_variables;
 
@@ -623,12 +1996,10 @@ pub fn r1cs_from_json<E: Engine, R: Read>(reader: R) -> R1CS<E> {
     let constraints = circuit_json
         .constraints
         .iter()
-        .map(|c| {
-            (
-                convert_constraint(&c[0]),
-                convert_constraint(&c[1]),
-                convert_constraint(&c[2]),
-            )
+        .map(|c| Constraint {
+            a: convert_constraint(&c[0]),
+            b: convert_constraint(&c[1]),
+            c: convert_constraint(&c[2]),
         })
         .collect_vec();
 
@@ -637,35 +2008,1341 @@ pub fn r1cs_from_json<E: Engine, R: Read>(reader: R) -> R1CS<E> {
         num_aux,
         num_variables: circuit_json.num_variables,
         constraints,
+        custom_gates: vec![],
+        custom_gate_uses: vec![],
+        wire_to_label: vec![],
+        sections: vec![],
     }
 }
 
-pub fn r1cs_from_bin<R: Read + Seek>(
+pub fn r1cs_from_bin<E: Engine, R: Read + Seek>(
     reader: R,
-) -> Result<(R1CS<Bn256>, Vec<usize>), std::io::Error> {
-    let file = crate::r1cs_reader::read(reader)?;
+) -> Result<(R1CS<E>, Vec<usize>), std::io::Error> {
+    let file = crate::r1cs_reader::read::<E, R>(reader)?;
+    Ok(r1cs_from_file(file))
+}
+
+fn r1cs_from_file<E: Engine>(file: R1CSFile<E>) -> (R1CS<E>, Vec<usize>) {
     let num_inputs = (1 + file.header.n_pub_in + file.header.n_pub_out) as usize;
     let num_variables = file.header.n_wires as usize;
     let num_aux = num_variables - num_inputs;
-    Ok((
+    (
         R1CS {
             num_aux,
             num_inputs,
             num_variables,
             constraints: file.constraints,
+            custom_gates: file.custom_gates,
+            custom_gate_uses: file.custom_gate_uses,
+            wire_to_label: file.wire_mapping.clone(),
+            sections: file.sections,
         },
         file.wire_mapping.iter().map(|e| *e as usize).collect_vec(),
-    ))
+    )
 }
 
-pub fn r1cs_from_bin_file(filename: &str) -> Result<(R1CS<Bn256>, Vec<usize>), std::io::Error> {
-    let reader = OpenOptions::new()
-        .read(true)
-        .open(filename)
-        .expect("unable to open.");
+pub fn r1cs_from_bin_file<E: Engine>(
+    filename: &str,
+) -> Result<(R1CS<E>, Vec<usize>), std::io::Error> {
+    let reader = OpenOptions::new().read(true).open(filename)?;
     r1cs_from_bin(BufReader::new(reader))
 }
 
+/// Loads a circuit from a binary `.r1cs` file together with its `.wtns` witness, checking
+/// first that both were produced for the same field
+pub fn load<E: Engine>(
+    r1cs_path: &str,
+    wtns_path: &str,
+) -> Result<CircomCircuit<E>, std::io::Error> {
+    let mut r1cs_reader = BufReader::new(File::open(r1cs_path)?);
+    let mut wtns_reader = BufReader::new(File::open(wtns_path)?);
+    let r1cs_prime = crate::r1cs_reader::read_file_header(&mut r1cs_reader)?.prime();
+    let wtns_prime = crate::wtns_reader::read_file_header(&mut wtns_reader)?.prime();
+    if r1cs_prime != wtns_prime {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Witness prime 0x{:x} doesn't match the circuit prime 0x{:x}",
+                wtns_prime, r1cs_prime
+            ),
+        ));
+    }
+    r1cs_reader.rewind()?;
+    wtns_reader.rewind()?;
+    let (r1cs, _) = r1cs_from_bin(r1cs_reader)?;
+    Ok(CircomCircuit {
+        r1cs,
+        witness: Some(witness_from_bin::<E, _>(wtns_reader)?),
+        wire_mapping: None,
+    })
+}
+
+/// Same as `load`, reading the two files on separate threads. The primes are compared once
+/// both are parsed, so a mismatch is only reported after reading everything.
+pub fn load_parallel<E: Engine>(r1cs_path: &str, wtns_path: &str) -> ParseResult<CircomCircuit<E>> {
+    let (r1cs, wtns) = std::thread::scope(|scope| {
+        let r1cs = scope.spawn(|| {
+            let file = File::open(r1cs_path).at(0)?;
+            crate::r1cs_reader::read::<E, _>(BufReader::new(file))
+        });
+        let wtns = scope.spawn(|| {
+            let file = File::open(wtns_path).at(0)?;
+            crate::wtns_reader::read::<E, _>(BufReader::new(file))
+        });
+        // a reader panicking is a bug, pass it on instead of turning it into an error
+        (
+            r1cs.join().unwrap_or_else(|e| std::panic::resume_unwind(e)),
+            wtns.join().unwrap_or_else(|e| std::panic::resume_unwind(e)),
+        )
+    });
+    let (r1cs, wtns) = (r1cs?, wtns?);
+    let (r1cs_prime, wtns_prime) = (r1cs.header.prime(), wtns.header.prime());
+    if r1cs_prime != wtns_prime {
+        return Err(ParseError::malformed(
+            0,
+            format!(
+                "Witness prime 0x{:x} doesn't match the circuit prime 0x{:x}",
+                wtns_prime, r1cs_prime
+            ),
+        ));
+    }
+    let (r1cs, _) = r1cs_from_file(r1cs);
+    Ok(CircomCircuit {
+        r1cs,
+        witness: Some(wtns.witness),
+        wire_mapping: None,
+    })
+}
+
 pub fn create_rng() -> Box<dyn Rng> {
     Box::new(OsRng::new().unwrap())
 }
+
+/// A tiny `x * y = z` circuit with public `z` and private `x`, `y`, used across tests.
+#[cfg(test)]
+pub(crate) fn sample_circuit() -> CircomCircuit<Bn256> {
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let r1cs = R1CSBuilder::<Bn256>::new(4)
+        .set_public_count(1)
+        .add_constraint(vec![(2, fr("1"))], vec![(3, fr("1"))], vec![(1, fr("1"))])
+        .build()
+        .unwrap();
+    CircomCircuit {
+        r1cs,
+        witness: Some(vec![fr("1"), fr("12"), fr("3"), fr("4")]),
+        wire_mapping: None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn sample_params() -> Parameters<Bn256> {
+    let setup = CircomCircuit {
+        witness: None,
+        ..sample_circuit()
+    };
+    generate_random_parameters(setup, create_rng()).unwrap()
+}
+
+#[test]
+fn r1cs_builder() {
+    let circuit = sample_circuit();
+    assert_eq!(circuit.r1cs.num_inputs, 2);
+    assert_eq!(circuit.r1cs.num_aux, 2);
+    assert_eq!(circuit.r1cs.num_variables, 4);
+    assert_eq!(circuit.r1cs.constraints.len(), 1);
+
+    let mut rng = create_rng();
+    let params = sample_params();
+    let proof = prove(circuit.clone(), &params, &mut rng).unwrap();
+    assert!(verify_circuit(&circuit, &params, &proof).unwrap());
+
+    let one = <Bn256 as ScalarEngine>::Fr::from_str("1").unwrap();
+    let err = R1CSBuilder::<Bn256>::new(4)
+        .add_constraint(vec![(4, one)], vec![], vec![])
+        .build();
+    assert!(err.is_err());
+    assert!(R1CSBuilder::<Bn256>::new(1)
+        .set_public_count(1)
+        .build()
+        .is_err());
+}
+
+#[test]
+fn zeroed_ic_is_rejected() {
+    let mut params = sample_params();
+    assert!(validate_parameters(&params).is_ok());
+
+    params.vk.ic[1] = G1Affine::zero();
+    assert!(matches!(
+        validate_verifying_key(&params.vk),
+        Err(SynthesisError::SuspiciousVerifyingKey)
+    ));
+    assert!(matches!(
+        validate_parameters(&params),
+        Err(SynthesisError::SuspiciousVerifyingKey)
+    ));
+}
+
+#[test]
+fn verify_with_biguint_inputs() {
+    let circuit = sample_circuit();
+    let params = sample_params();
+    let proof = prove(circuit.clone(), &params, create_rng()).unwrap();
+    let inputs = circuit.get_public_inputs().unwrap();
+
+    let expected = verify(&params, &proof, &inputs).unwrap();
+    assert!(expected);
+    let big = vec![BigUint::from(12u32)];
+    assert_eq!(
+        verify_proof_biguint(&params.vk, &proof, &big, true).unwrap(),
+        expected
+    );
+    assert!(!verify_proof_biguint(&params.vk, &proof, &[BigUint::from(13u32)], true).unwrap());
+
+    let unreduced = vec![scalar_modulus::<Bn256>() + 12u32];
+    assert!(matches!(
+        verify_proof_biguint(&params.vk, &proof, &unreduced, true),
+        Err(SynthesisError::InputOutOfRange { index: 0 })
+    ));
+    assert!(verify_proof_biguint(&params.vk, &proof, &unreduced, false).unwrap());
+}
+
+#[test]
+fn effective_constraint_count_matches_domain() {
+    let circuit = sample_circuit();
+    let count = effective_constraint_count(&circuit.r1cs);
+    assert_eq!(count, 3);
+    // H query has one element less than the domain the prover evaluates over
+    let params = sample_params();
+    assert_eq!(params.h.len(), count.next_power_of_two() - 1);
+}
+
+#[test]
+fn canonicalize_r1cs() {
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let mut minus_one = fr("1");
+    minus_one.negate();
+    let mut circuit = sample_circuit();
+    circuit.r1cs.constraints[0].a = vec![(3, fr("0")), (2, fr("2")), (2, minus_one)];
+    circuit.r1cs.constraints[0].c = vec![(1, fr("1")), (0, fr("0"))];
+
+    let mut canonical = circuit.clone();
+    canonical.r1cs.canonicalize();
+    assert_eq!(canonical.r1cs.constraints[0].a, vec![(2, fr("1"))]);
+    assert_eq!(canonical.r1cs.constraints[0].c, vec![(1, fr("1"))]);
+    assert!(canonical.r1cs == sample_circuit().r1cs);
+
+    let mut twice = canonical.clone();
+    twice.r1cs.canonicalize();
+    assert!(twice.r1cs == canonical.r1cs);
+
+    let params = sample_params();
+    let proof = prove(canonical.clone(), &params, create_rng()).unwrap();
+    assert!(verify_circuit(&canonical, &params, &proof).unwrap());
+}
+
+#[test]
+fn r1cs_hash_ignores_term_order() {
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let r1cs = R1CSBuilder::<Bn256>::new(5)
+        .set_public_count(1)
+        .add_constraint(
+            vec![(2, fr("1")), (4, fr("3"))],
+            vec![(3, fr("1"))],
+            vec![(1, fr("1"))],
+        )
+        .build()
+        .unwrap();
+    let hash = r1cs.hash();
+
+    // the same terms in another order hash the same, and so does the split form `canonicalize`
+    // merges, but a different coefficient doesn't
+    let mut permuted = r1cs.clone();
+    permuted.constraints[0].a.reverse();
+    assert!(permuted.constraints[0] != r1cs.constraints[0]);
+    assert_eq!(permuted.hash(), hash);
+    let mut split = r1cs.clone();
+    split.constraints[0].a = vec![(4, fr("1")), (2, fr("1")), (4, fr("2")), (3, fr("0"))];
+    assert_eq!(split.hash(), hash);
+    let mut other = r1cs;
+    other.constraints[0].a[1].1 = fr("4");
+    assert_ne!(other.hash(), hash);
+}
+
+#[test]
+fn prepared_vk_bytes() {
+    let circuit = sample_circuit();
+    let params = sample_params();
+    let proof = prove(circuit.clone(), &params, create_rng()).unwrap();
+    let inputs = circuit.get_public_inputs().unwrap();
+
+    let mut bytes = vec![];
+    crate::utils::write_prepared_vk(&prepare_verifying_key(&params.vk), &mut bytes).unwrap();
+    assert!(verify_prepared_bytes(&bytes, &proof, &inputs).unwrap());
+    let mut wrong = inputs[0];
+    wrong.double();
+    assert!(!verify_prepared_bytes(&bytes, &proof, &[wrong]).unwrap());
+
+    // corrupt the least significant byte of the first coefficient of e(alpha, beta)
+    bytes[31] ^= 1;
+    assert!(verify_prepared_bytes(&bytes, &proof, &inputs).is_err());
+}
+
+#[test]
+fn wire_usage_index() {
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let r1cs = R1CSBuilder::<Bn256>::new(5)
+        .set_public_count(1)
+        .add_constraint(vec![(2, fr("1"))], vec![(2, fr("1"))], vec![(3, fr("1"))])
+        .add_constraint(vec![(3, fr("1"))], vec![(2, fr("1"))], vec![(1, fr("1"))])
+        .build()
+        .unwrap();
+    let usage = r1cs.wire_usage();
+    assert_eq!(usage.len(), 5);
+    assert!(usage[0].is_empty());
+    assert_eq!(usage[1], vec![1]);
+    assert_eq!(usage[2], vec![0, 1]);
+    assert_eq!(usage[3], vec![0, 1]);
+    assert!(usage[4].is_empty());
+
+    // a wire past num_variables doesn't panic
+    let mut malformed = r1cs;
+    malformed.constraints[1].c.push((7, fr("1")));
+    let usage = malformed.wire_usage();
+    assert_eq!(usage.len(), 8);
+    assert_eq!(usage[7], vec![1]);
+    assert_eq!(usage[1], vec![1]);
+}
+
+#[test]
+fn constraint_evaluation() {
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    // (2 * w2) * (w3 + 1) = w1
+    let constraint = Constraint::<Bn256>::new(
+        vec![(2, fr("2"))],
+        vec![(3, fr("1")), (0, fr("1"))],
+        vec![(1, fr("1"))],
+    );
+    let witness = vec![fr("1"), fr("30"), fr("3"), fr("4")];
+    assert!(constraint.evaluate(&witness) == (fr("6"), fr("5"), fr("30")));
+    assert!(constraint.is_satisfied(&witness));
+    assert!(!constraint.is_satisfied(&[fr("1"), fr("12"), fr("3"), fr("4")]));
+
+    let circuit = sample_circuit();
+    assert!(
+        circuit.r1cs.constraints[0]
+            == Constraint::new(vec![(2, fr("1"))], vec![(3, fr("1"))], vec![(1, fr("1"))])
+    );
+    assert!(circuit.r1cs.constraints[0].is_satisfied(circuit.witness.as_ref().unwrap()));
+}
+
+#[test]
+fn constraint_densities() {
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let r1cs = R1CSBuilder::<Bn256>::new(5)
+        .set_public_count(1)
+        .add_constraint(vec![(2, fr("1"))], vec![(3, fr("1"))], vec![(1, fr("1"))])
+        // the zero coefficient on wire 4 isn't counted
+        .add_constraint(
+            vec![(0, fr("7")), (2, fr("1")), (4, fr("0"))],
+            vec![(0, fr("1"))],
+            vec![],
+        )
+        .add_constraint(
+            vec![(2, fr("1")), (3, fr("1"))],
+            vec![(3, fr("2")), (4, fr("1"))],
+            vec![(0, fr("3")), (1, fr("1")), (4, fr("5"))],
+        )
+        .build()
+        .unwrap();
+    let circuit = CircomCircuitBuilder::new(r1cs).build().unwrap();
+    assert_eq!(circuit.constraint_density_histogram(), vec![3, 3, 7]);
+    assert!((circuit.average_density() - 13.0 / 3.0).abs() < 1e-12);
+
+    let empty = CircomCircuitBuilder::new(R1CSBuilder::<Bn256>::new(1).build().unwrap())
+        .build()
+        .unwrap();
+    assert!(empty.constraint_density_histogram().is_empty());
+    assert_eq!(empty.average_density(), 0.0);
+}
+
+#[test]
+fn constraints_listing() {
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let mut minus_two = fr("2");
+    minus_two.negate();
+    let r1cs = R1CSBuilder::<Bn256>::new(4)
+        .set_public_count(1)
+        .add_constraint(vec![(2, fr("1"))], vec![(3, fr("1"))], vec![(1, fr("1"))])
+        .add_constraint(
+            vec![(0, fr("1")), (2, minus_two)],
+            vec![(0, minus_two), (3, fr("5"))],
+            vec![],
+        )
+        .build()
+        .unwrap();
+    assert_eq!(
+        r1cs.to_string_constraints(10),
+        "(w2) * (w3) = (w1)\n(1 - 2*w2) * (-2 + 5*w3) = (0)\n"
+    );
+    assert_eq!(
+        r1cs.to_string_constraints(1),
+        "(w2) * (w3) = (w1)\n... 1 more constraints\n"
+    );
+
+    // with a label map wires are named by their label, wires past its end by index
+    let mut labelled = r1cs;
+    labelled.wire_to_label = vec![0, 3, 10];
+    assert_eq!(
+        labelled.to_string_constraints(10),
+        "(l10) * (w3) = (l3)\n(1 - 2*l10) * (-2 + 5*w3) = (0)\n"
+    );
+}
+
+#[test]
+fn versioned_verification() {
+    let circuit = sample_circuit();
+    let params_v1 = sample_params();
+    let params_v2 = sample_params();
+    let mut verifier = MultiVkVerifier::new();
+    verifier.insert(1u32, &params_v1.vk).unwrap();
+    verifier.insert(2u32, &params_v2.vk).unwrap();
+
+    let inputs = circuit.get_public_inputs().unwrap();
+    let proof_v1 = prove(circuit.clone(), &params_v1, create_rng()).unwrap();
+    let proof_v2 = prove(circuit, &params_v2, create_rng()).unwrap();
+    assert!(verifier.verify_versioned(&1, &proof_v1, &inputs).unwrap());
+    assert!(verifier.verify_versioned(&2, &proof_v2, &inputs).unwrap());
+    assert!(!verifier.verify_versioned(&2, &proof_v1, &inputs).unwrap());
+    assert!(!verifier.verify_versioned(&1, &proof_v2, &inputs).unwrap());
+    assert!(matches!(
+        verifier.verify_versioned(&3, &proof_v1, &inputs),
+        Err(MultiVkError::UnknownVkVersion)
+    ));
+}
+
+#[test]
+fn constant_wire_must_be_one() {
+    let params = sample_params();
+    let mut circuit = sample_circuit();
+    circuit.witness.as_mut().unwrap()[0] = <Bn256 as ScalarEngine>::Fr::from_str("2").unwrap();
+    assert!(matches!(
+        prove(circuit, &params, create_rng()),
+        Err(SynthesisError::InvalidConstantWire)
+    ));
+
+    // with a wire mapping the constant is wherever wire 0 is mapped to
+    let mut mapped = sample_circuit();
+    mapped.witness.as_mut().unwrap().swap(0, 3);
+    mapped.wire_mapping = Some(vec![3, 1, 2, 0]);
+    assert!(prove(mapped.clone(), &params, create_rng()).is_ok());
+    mapped.wire_mapping = Some(vec![0, 1, 2, 3]);
+    assert!(matches!(
+        prove(mapped, &params, create_rng()),
+        Err(SynthesisError::InvalidConstantWire)
+    ));
+}
+
+#[test]
+fn wire_counts() {
+    let circuit = sample_circuit();
+    assert_eq!(circuit.num_inputs(), 1);
+    assert_eq!(circuit.num_aux(), 2);
+    assert_eq!(circuit.r1cs.num_inputs, 2);
+    let inputs = circuit.get_public_inputs().unwrap();
+    assert_eq!(inputs.len(), circuit.num_inputs());
+    assert!(inputs[0] == <Bn256 as ScalarEngine>::Fr::from_str("12").unwrap());
+
+    // no wires at all, not even the constant one
+    let mut empty = sample_circuit();
+    empty.r1cs.num_inputs = 0;
+    assert_eq!(empty.num_inputs(), 0);
+    assert!(empty.validate().is_err());
+}
+
+#[test]
+fn wrong_input_count() {
+    let circuit = sample_circuit();
+    let params = sample_params();
+    let proof = prove(circuit.clone(), &params, create_rng()).unwrap();
+    let inputs = circuit.get_public_inputs().unwrap();
+
+    let too_many = [inputs[0], inputs[0]];
+    assert!(matches!(
+        verify(&params, &proof, &too_many),
+        Err(SynthesisError::InputCountMismatch {
+            expected: 1,
+            got: 2
+        })
+    ));
+    let err = verify(&params, &proof, &[]).unwrap_err();
+    assert!(matches!(
+        err,
+        SynthesisError::InputCountMismatch {
+            expected: 1,
+            got: 0
+        }
+    ));
+    assert!(err.to_string().contains("expected 1, got 0"));
+}
+
+#[test]
+fn timed_verification() {
+    let circuit = sample_circuit();
+    let params = sample_params();
+    let proof = prove(circuit.clone(), &params, create_rng()).unwrap();
+    let inputs = circuit.get_public_inputs().unwrap();
+
+    let (valid, _) = verify_proof_timed(&params.vk, &proof, &inputs).unwrap();
+    assert!(valid);
+    let pvk = prepare_verifying_key(&params.vk);
+    let (valid, _) = verify_prepared_timed(&pvk, &proof, &inputs).unwrap();
+    assert!(valid);
+    let mut wrong = inputs[0];
+    wrong.double();
+    let (valid, _) = verify_prepared_timed(&pvk, &proof, &[wrong]).unwrap();
+    assert!(!valid);
+}
+
+#[test]
+fn qap_degree_limits() {
+    let mut r1cs = sample_circuit().r1cs;
+    assert_eq!(r1cs.qap_degree().unwrap(), 4);
+    r1cs.constraints.clear();
+    r1cs.num_inputs = (1 << 28) - 1;
+    assert_eq!(r1cs.qap_degree().unwrap(), 1 << 28);
+    r1cs.num_inputs = 1 << 28;
+    assert!(matches!(
+        r1cs.qap_degree(),
+        Err(SynthesisError::DomainTooLarge {
+            constraints,
+            max_constraints,
+        }) if constraints == 1 << 28 && max_constraints == (1 << 28) - 1
+    ));
+    assert_eq!(
+        r1cs.qap_degree().unwrap_err().to_string(),
+        crate::utils::required_domain_size(1 << 28)
+            .unwrap_err()
+            .to_string()
+    );
+}
+
+#[test]
+fn verification_description() {
+    let circuit = sample_circuit();
+    let params = sample_params();
+    let proof = prove(circuit.clone(), &params, create_rng()).unwrap();
+    let inputs = circuit.get_public_inputs().unwrap();
+
+    let description = describe_verification(&params.vk, &proof, &inputs);
+    assert!(description.starts_with("e(A, B) = e(alpha, beta) * e(IC, gamma) * e(C, delta)\n"));
+    assert!(description.contains("IC = IC[0] + 12 * IC[1] = (0x"));
+    assert!(description.ends_with("The equation holds\n"));
+    let description = describe_verification(&params.vk, &proof, &[]);
+    assert!(description.contains("IC is undefined: 0 inputs given for 2 IC points"));
+}
+
+#[test]
+fn truncated_a_query_is_rejected() {
+    let circuit = sample_circuit();
+    let mut params = sample_params();
+    let mut a = (*params.a).clone();
+    a.pop();
+    params.a = Arc::new(a);
+    assert!(matches!(
+        prove(circuit, &params, create_rng()),
+        Err(SynthesisError::InconsistentParameters)
+    ));
+}
+
+#[test]
+fn golden_proof() {
+    let setup = CircomCircuit {
+        witness: None,
+        ..sample_circuit()
+    };
+    let params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+    let expected = hex!(
+        "0ed845b39e83bf5a71d550ab9af79bd87fd2aebe4c5ff699c08a31b1f9d26c52"
+        "051d2f172c4c55b129ee9ca52d12f046ea5f8d9c234cd28f2725ec35365fa805"
+        "10c9ca897d4d9c8f7ba9ccf048ee43bf6f0e3f14cc1a057d7b5908de7c7efb67"
+        "0c5e4c99454fb265f2fbb2fc53d79eb38edd15c45b2f233df5b227819f2680c3"
+        "1d91ef1a4c627b24149897a7bd1acd36a2e72c1c3b5ffb0cd6c0c707c5af9d65"
+        "2a3f13827f68c81c7662fa8747c3f4ee5adb9197241cbe9cb2a58824c47fe95b"
+        "1b43b4b74d5e3c480b0f7b86361947309c8e2fa116b8d48c06c95cdbfcf17f77"
+        "2bfcdcbe5aebf91554154910cdf902e708fc8e88f419b613c64ee3148f02ba4f"
+    );
+    assert_proof_matches(sample_circuit(), &params, 2, &expected);
+
+    let proof = prove_with_seed(sample_circuit(), &params, 2).unwrap();
+    assert!(verify(
+        &params,
+        &proof,
+        &sample_circuit().get_public_inputs().unwrap()
+    )
+    .unwrap());
+}
+
+#[test]
+fn witness_check() {
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    assert_eq!(sample_circuit().check_witness(), Ok(()));
+
+    let r1cs = R1CSBuilder::<Bn256>::new(5)
+        .set_public_count(1)
+        .add_constraint(vec![(2, fr("1"))], vec![(3, fr("1"))], vec![(1, fr("1"))])
+        .add_constraint(vec![(3, fr("1"))], vec![(3, fr("1"))], vec![(4, fr("1"))])
+        .build()
+        .unwrap();
+    let mut circuit = CircomCircuit {
+        r1cs,
+        witness: Some(vec![fr("1"), fr("12"), fr("3"), fr("4"), fr("16")]),
+        wire_mapping: None,
+    };
+    assert_eq!(circuit.check_witness(), Ok(()));
+    circuit.witness.as_mut().unwrap()[4] = fr("15");
+    let err = circuit.check_witness().unwrap_err();
+    assert_eq!(err.constraint(), Some(1));
+    assert_eq!(
+        err.to_string(),
+        "constraint 1 is not satisfied: (wire 3) * (wire 3) != (wire 4)"
+    );
+    // the same corruption is found through a wire mapping
+    circuit.witness = Some(vec![fr("1"), fr("12"), fr("3"), fr("15"), fr("4")]);
+    circuit.wire_mapping = Some(vec![0, 1, 2, 4, 3]);
+    assert_eq!(circuit.check_witness().unwrap_err(), err);
+
+    // witnesses that don't fit the circuit are reported, not indexed out of bounds
+    circuit.wire_mapping = Some(vec![0, 1, 2, 4, 5]);
+    assert_eq!(
+        circuit.check_witness(),
+        Err(WitnessError::Invalid(ValidationError::MappingOutOfRange {
+            wire: 4,
+            index: 5,
+            witness_len: 5,
+        }))
+    );
+    circuit.wire_mapping = None;
+    circuit.witness.as_mut().unwrap().pop();
+    assert_eq!(
+        circuit.check_witness(),
+        Err(WitnessError::Invalid(
+            ValidationError::WitnessLengthMismatch {
+                expected: 5,
+                found: 4,
+            }
+        ))
+    );
+    circuit.witness = None;
+    assert_eq!(circuit.check_witness(), Err(WitnessError::Missing));
+}
+
+#[test]
+fn constraint_chunks_in_parallel() {
+    use bellman_ce::worker::Worker;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let fr = |i: usize| <Bn256 as ScalarEngine>::Fr::from_str(&i.to_string()).unwrap();
+    let mut builder = R1CSBuilder::<Bn256>::new(10);
+    builder.set_public_count(1);
+    for i in 0..1000 {
+        let wires = 1 + i % 9;
+        let lc = (0..wires).map(|w| (w, fr(i + 1))).collect_vec();
+        builder.add_constraint(lc.clone(), lc[..1].to_vec(), lc[wires / 2..].to_vec());
+    }
+    let circuit = CircomCircuit {
+        r1cs: builder.build().unwrap(),
+        witness: None,
+        wire_mapping: None,
+    };
+    let constraints = circuit.r1cs.constraints();
+    assert_eq!(constraints.len(), 1000);
+
+    let total = AtomicUsize::new(0);
+    let worker = Worker::new();
+    worker.scope(constraints.len(), |scope, chunk_size| {
+        for chunk in constraints.chunks(chunk_size) {
+            let total = &total;
+            scope.spawn(move |_| {
+                let density = chunk
+                    .iter()
+                    .map(|c| c.a.len() + c.b.len() + c.c.len())
+                    .sum::<usize>();
+                total.fetch_add(density, Ordering::Relaxed);
+            });
+        }
+    });
+    let sequential = circuit.constraint_density_histogram().iter().sum::<usize>();
+    assert_eq!(total.into_inner(), sequential);
+}
+
+#[test]
+fn validation() {
+    let circuit = sample_circuit();
+    assert_eq!(circuit.validate(), Ok(()));
+    let setup = CircomCircuit {
+        witness: None,
+        ..circuit.clone()
+    };
+    assert_eq!(setup.validate(), Ok(()));
+
+    let mut out_of_range = circuit.clone();
+    out_of_range.r1cs.constraints[0].b[0].0 = 4;
+    assert_eq!(
+        out_of_range.validate(),
+        Err(ValidationError::WireOutOfRange {
+            constraint: 0,
+            wire: 4,
+            num_variables: 4
+        })
+    );
+    assert_eq!(
+        out_of_range.validate().unwrap_err().to_string(),
+        "constraint 0 references wire 4 out of 4"
+    );
+
+    let mut short = circuit.clone();
+    short.witness.as_mut().unwrap().pop();
+    assert_eq!(
+        short.validate(),
+        Err(ValidationError::WitnessLengthMismatch {
+            expected: 4,
+            found: 3
+        })
+    );
+    // with a mapping its length is what has to match, and it must stay inside the witness
+    short.wire_mapping = Some(vec![0, 1, 2, 3]);
+    assert_eq!(
+        short.validate(),
+        Err(ValidationError::MappingOutOfRange {
+            wire: 3,
+            index: 3,
+            witness_len: 3
+        })
+    );
+    short.wire_mapping = Some(vec![0, 1, 2, 2]);
+    assert_eq!(short.validate(), Ok(()));
+
+    let mut miscounted = circuit;
+    miscounted.r1cs.num_inputs = 3;
+    assert!(matches!(
+        miscounted.validate(),
+        Err(ValidationError::InputCountMismatch { .. })
+    ));
+}
+
+#[test]
+fn named_witness_check() {
+    use crate::sym_reader::{self, SAMPLE_SYM};
+
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let sym = sym_reader::read(SAMPLE_SYM.as_bytes()).unwrap();
+    assert_eq!(sample_circuit().check_witness_named(&sym), Ok(()));
+
+    let mut minus_two = fr("2");
+    minus_two.negate();
+    let r1cs = R1CSBuilder::<Bn256>::new(5)
+        .set_public_count(1)
+        .add_constraint(vec![(2, fr("1"))], vec![(3, fr("1"))], vec![(1, fr("1"))])
+        .add_constraint(
+            vec![(0, fr("5")), (2, minus_two), (3, fr("0"))],
+            vec![(0, fr("1"))],
+            vec![(4, fr("1"))],
+        )
+        .build()
+        .unwrap();
+    // wire 4 has no entry in the .sym file, and 5 - 2 * 3 isn't 7
+    let circuit = CircomCircuit {
+        r1cs,
+        witness: Some(vec![fr("1"), fr("12"), fr("3"), fr("4"), fr("7")]),
+        wire_mapping: None,
+    };
+    let err = circuit.check_witness_named(&sym).unwrap_err();
+    let term = |name: &str, coeff: &str| (name.to_string(), coeff.to_string());
+    assert_eq!(
+        err,
+        WitnessError::Unsatisfied(ConstraintError {
+            index: 1,
+            a: vec![term("one", "5"), term("main.a", "-2")],
+            b: vec![term("one", "1")],
+            c: vec![term("wire 4", "1")],
+        })
+    );
+    assert_eq!(
+        err.to_string(),
+        "constraint 1 is not satisfied: (5*one + -2*main.a) * (one) != (wire 4)"
+    );
+}
+
+#[test]
+fn r1cs_from_reader_and_file() {
+    use crate::r1cs_reader::SAMPLE_R1CS;
+    use crate::utils::with_temp_files;
+
+    let from_cursor =
+        r1cs_from_bin::<Bn256, _>(std::io::Cursor::new(SAMPLE_R1CS.to_vec())).unwrap();
+    let (from_file, path) =
+        with_temp_files("sample", &[("circuit.r1cs", &SAMPLE_R1CS[..])], |paths| {
+            (
+                r1cs_from_bin_file(paths[0].to_str().unwrap()),
+                paths[0].clone(),
+            )
+        });
+    let from_file = from_file.unwrap();
+    assert!(from_cursor.0 == from_file.0);
+    assert_eq!(from_cursor.1, from_file.1);
+
+    assert!(r1cs_from_bin_file::<Bn256>(path.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn wire_labels() {
+    use crate::r1cs_reader::SAMPLE_R1CS;
+
+    let (r1cs, _) = r1cs_from_bin::<Bn256, _>(std::io::Cursor::new(SAMPLE_R1CS.to_vec())).unwrap();
+    assert_eq!(r1cs.wire_to_label, vec![0, 3, 10, 11, 12, 15, 324]);
+    let circuit = CircomCircuit {
+        r1cs,
+        witness: None,
+        wire_mapping: None,
+    };
+    assert_eq!(circuit.label_of_wire(1), Some(3));
+    assert_eq!(circuit.label_of_wire(6), Some(324));
+    assert_eq!(circuit.label_of_wire(7), None);
+    assert_eq!(sample_circuit().label_of_wire(0), None);
+
+    // the map must have one entry per wire
+    let mut data = SAMPLE_R1CS.to_vec();
+    let map_size = data.len() - 7 * 8 - 8;
+    data[map_size] = 6 * 8;
+    data.truncate(data.len() - 8);
+    let err = r1cs_from_bin::<Bn256, _>(std::io::Cursor::new(data))
+        .err()
+        .unwrap();
+    assert!(err.to_string().starts_with("Invalid map section size"));
+}
+
+#[test]
+fn load_checks_primes() {
+    use crate::r1cs_reader::SAMPLE_R1CS;
+    use crate::utils::with_temp_files;
+    use crate::wtns_reader::SAMPLE_WTNS;
+
+    // a witness computed over the BLS12-381 scalar field
+    let mut wtns = SAMPLE_WTNS.to_vec();
+    wtns[28..60].copy_from_slice(&hex!(
+        "01000000fffffffffe5bfeff02a4bd5305d8a10908d83933487d9d2953a7ed73"
+    ));
+    let files = [
+        ("circuit.r1cs", &SAMPLE_R1CS[..]),
+        ("witness.wtns", &SAMPLE_WTNS[..]),
+        ("bls12.wtns", &wtns[..]),
+    ];
+    let (circuit, err) = with_temp_files("load", &files, |paths| {
+        let path = |i: usize| paths[i].to_str().unwrap();
+        (
+            load::<Bn256>(path(0), path(1)),
+            load::<Bn256>(path(0), path(2)).err().unwrap(),
+        )
+    });
+    let circuit = circuit.unwrap();
+    assert_eq!(circuit.r1cs.constraints.len(), 3);
+    assert_eq!(circuit.witness.unwrap().len(), 4);
+    assert_eq!(
+        err.to_string(),
+        "Witness prime 0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001 \
+         doesn't match the circuit prime \
+         0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001"
+    );
+}
+
+#[test]
+fn bls12_end_to_end() {
+    use crate::utils::with_temp_files;
+    use crate::wtns_reader::SAMPLE_WTNS;
+    use bellman_ce::pairing::bls12_381::Bls12;
+
+    // `sample_circuit` compiled over the BLS12-381 scalar field
+    let r1cs = hex!(
+        "
+        72316373
+        01000000
+        03000000
+        01000000 40000000 00000000
+        20000000
+        01000000 ffffffff fe5bfeff 02a4bd53 05d8a109 08d83933 487d9d29 53a7ed73
+        04000000
+        01000000
+        00000000
+        02000000
+        04000000 00000000
+        01000000
+        02000000 78000000 00000000
+        01000000
+        02000000 01000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+        01000000
+        03000000 01000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+        01000000
+        01000000 01000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+        03000000 20000000 00000000
+        00000000 00000000
+        01000000 00000000
+        02000000 00000000
+        03000000 00000000
+    "
+    );
+    let mut wtns = SAMPLE_WTNS.to_vec();
+    wtns[28..60].copy_from_slice(&r1cs[28..60]);
+
+    let files = [
+        ("circuit.r1cs", &r1cs[..]),
+        ("witness.wtns", &wtns[..]),
+        ("circuit.params", &b""[..]),
+    ];
+    let (circuit, mismatch, params) = with_temp_files("bls12", &files, |paths| {
+        let (r1cs_path, wtns_path) = (paths[0].to_str().unwrap(), paths[1].to_str().unwrap());
+        let params_path = paths[2].to_str().unwrap();
+        let circuit = load::<Bls12>(r1cs_path, wtns_path).unwrap();
+        let mismatch = load::<Bn256>(r1cs_path, wtns_path);
+
+        let setup = CircomCircuit {
+            witness: None,
+            ..circuit.clone()
+        };
+        let params = generate_random_parameters(setup, seeded_rng(7)).unwrap();
+        params.write(File::create(params_path).unwrap()).unwrap();
+        (circuit, mismatch, load_params_file::<Bls12>(params_path))
+    });
+    assert!(mismatch.is_err());
+
+    let inputs = circuit.get_public_inputs().unwrap();
+    assert_eq!(
+        inputs,
+        vec![<Bls12 as ScalarEngine>::Fr::from_str("12").unwrap()]
+    );
+    let proof = prove(circuit.clone(), &params, seeded_rng(8)).unwrap();
+    assert!(verify_circuit(&circuit, &params, &proof).unwrap());
+    assert!(verify(&params, &proof, &inputs).unwrap());
+
+    let mut wrong = circuit;
+    wrong.witness.as_mut().unwrap()[1] = <Bls12 as ScalarEngine>::Fr::from_str("13").unwrap();
+    assert!(!verify_circuit(&wrong, &params, &proof).unwrap());
+}
+
+#[test]
+fn public_inputs_from_files() {
+    use crate::r1cs_reader::SAMPLE_R1CS;
+    use crate::utils::with_temp_files;
+    use crate::wtns_reader::SAMPLE_WTNS;
+
+    let files = [
+        ("circuit.r1cs", &SAMPLE_R1CS[..]),
+        ("witness.wtns", &SAMPLE_WTNS[..]),
+    ];
+    let circuit = with_temp_files("public", &files, |paths| {
+        load::<Bn256>(paths[0].to_str().unwrap(), paths[1].to_str().unwrap())
+    });
+    let circuit = circuit.unwrap();
+
+    // one public output and two public inputs
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    assert_eq!(circuit.r1cs.num_inputs, 4);
+    assert_eq!(
+        circuit.get_public_inputs().unwrap(),
+        vec![fr("12"), fr("3"), fr("4")]
+    );
+    let setup = CircomCircuit {
+        witness: None,
+        ..circuit
+    };
+    assert_eq!(setup.get_public_inputs(), None);
+}
+
+#[test]
+fn setup_and_proving_modes() {
+    let r1cs = sample_circuit().r1cs;
+    let setup = CircomCircuitBuilder::new(r1cs.clone()).build().unwrap();
+    let params = generate_random_parameters(setup.clone(), create_rng()).unwrap();
+    assert!(matches!(
+        prove(setup, &params, create_rng()),
+        Err(SynthesisError::AssignmentMissing)
+    ));
+
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let witness = vec![fr("1"), fr("12"), fr("3"), fr("4")];
+    let circuit = CircomCircuitBuilder::new(r1cs.clone())
+        .witness(witness.clone())
+        .build()
+        .unwrap();
+    let proof = prove(circuit.clone(), &params, create_rng()).unwrap();
+    assert!(verify_circuit(&circuit, &params, &proof).unwrap());
+
+    assert!(CircomCircuitBuilder::new(r1cs.clone())
+        .witness(witness[..3].to_vec())
+        .build()
+        .is_err());
+    assert!(CircomCircuitBuilder::new(r1cs.clone())
+        .witness(witness.clone())
+        .wire_mapping(vec![0, 1, 2])
+        .build()
+        .is_err());
+    // the builder rejects whatever `validate` does, including too long a witness
+    let err = CircomCircuitBuilder::new(r1cs.clone())
+        .witness([&witness[..], &[fr("5")]].concat())
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(
+        err.to_string(),
+        "witness has 5 entries, the circuit has 4 wires"
+    );
+    assert!(CircomCircuitBuilder::new(r1cs)
+        .witness(witness)
+        .wire_mapping(vec![0, 1, 2, 4])
+        .build()
+        .is_err());
+}
+
+#[test]
+fn circuit_diagnostics() {
+    let sample = sample_circuit().diagnostics().unwrap();
+    // one constraint plus a row for the constant one and the public output
+    assert_eq!(sample.num_constraints, 1);
+    assert_eq!(sample.domain_constraints, 3);
+    assert_eq!(sample.domain_size, 4);
+    assert_eq!(sample.max_domain_size, 1 << 28);
+    assert!(sample.fits_domain());
+    let counts = prepare_prover(sample_circuit()).unwrap().operation_counts();
+    assert_eq!(sample.op_counts, counts);
+    // w2 is the only wire in A and w3 the only one in B
+    assert_eq!(
+        counts,
+        OpCounts {
+            h: 3,
+            l: 2,
+            a_inputs: 2,
+            a_aux: 1,
+            b_g1_inputs: 0,
+            b_g1_aux: 1,
+            b_g2_inputs: 0,
+            b_g2_aux: 1,
+        }
+    );
+    assert_eq!(sample.g1_terms, 3 + 2 + 2 + 1 + 1);
+    assert_eq!(sample.g2_terms, 1);
+    // the witness doesn't matter
+    let setup = CircomCircuit {
+        witness: None,
+        ..sample_circuit()
+    };
+    assert_eq!(setup.diagnostics().unwrap(), sample);
+
+    let one = Fr::one();
+    for &(n, domain_size) in [(5, 8), (6, 8), (7, 16), (30, 32)].iter() {
+        // x_{i+1} = x_i * x_i with x_n public
+        let mut builder = R1CSBuilder::<Bn256>::new(n + 2);
+        builder.set_public_count(1);
+        for i in 0..n {
+            let out = if i + 1 == n { 1 } else { i + 3 };
+            builder.add_constraint(vec![(i + 2, one)], vec![(i + 2, one)], vec![(out, one)]);
+        }
+        let diagnostics = CircomCircuitBuilder::new(builder.build().unwrap())
+            .build()
+            .unwrap()
+            .diagnostics()
+            .unwrap();
+        assert_eq!(diagnostics.num_constraints, n);
+        assert_eq!(diagnostics.domain_size, (n + 2).next_power_of_two());
+        assert_eq!(diagnostics.domain_size, domain_size);
+        assert_eq!(diagnostics.op_counts.h, domain_size - 1);
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_witness_check() {
+    let one = Fr::one();
+    let n = 10000;
+    // x_{i+1} = x_i * x_i with x_n public
+    let mut builder = R1CSBuilder::<Bn256>::new(n + 2);
+    builder.set_public_count(1);
+    for i in 0..n {
+        let out = if i + 1 == n { 1 } else { i + 3 };
+        builder.add_constraint(vec![(i + 2, one)], vec![(i + 2, one)], vec![(out, one)]);
+    }
+    let r1cs = builder.build().unwrap();
+    let mut witness = vec![one, Fr::zero(), Fr::from_str("3").unwrap()];
+    for _ in 1..n {
+        let mut x = *witness.last().unwrap();
+        x.square();
+        witness.push(x);
+    }
+    let mut out = *witness.last().unwrap();
+    out.square();
+    witness[1] = out;
+    let circuit = |witness: &Vec<Fr>| {
+        CircomCircuitBuilder::new(r1cs.clone())
+            .witness(witness.clone())
+            .build()
+            .unwrap()
+    };
+    assert_eq!(circuit(&witness).check_witness_parallel(), Ok(()));
+
+    // wire w > 2 is the output of constraint w - 3 and the input of w - 2. Every change adds
+    // a failure below the previous ones, spread over the chunks of up to 8 threads
+    let mut broken = witness.clone();
+    for &(wire, first_failure) in [(9000, 8997), (7001, 6998), (5003, 5000), (2, 0)].iter() {
+        broken[wire].double();
+        let circuit = circuit(&broken);
+        let err = circuit.check_witness().unwrap_err();
+        assert_eq!(err.constraint(), Some(first_failure));
+        assert_eq!(circuit.check_witness_parallel(), Err(err.clone()));
+        for cpus in 2..=8 {
+            let worker = bellman_ce::worker::Worker::new_with_cpus(cpus);
+            assert_eq!(circuit.check_witness_on(&worker), Err(err.clone()));
+        }
+    }
+    let mut broken = witness;
+    broken[1].double();
+    let err = circuit(&broken).check_witness_parallel().unwrap_err();
+    assert_eq!(err.constraint(), Some(n - 1));
+    broken.truncate(n);
+    let circuit = CircomCircuit {
+        r1cs,
+        witness: Some(broken),
+        wire_mapping: None,
+    };
+    assert_eq!(
+        circuit.check_witness_parallel(),
+        Err(WitnessError::Invalid(
+            ValidationError::WitnessLengthMismatch {
+                expected: n + 2,
+                found: n
+            }
+        ))
+    );
+}
+
+#[test]
+fn split_witness_halves() {
+    use crate::r1cs_reader::{read_file_header, SAMPLE_R1CS};
+    use crate::utils::with_temp_files;
+    use crate::wtns_reader::write_witness;
+
+    type Fr = <Bn256 as ScalarEngine>::Fr;
+    let header = read_file_header(std::io::Cursor::new(&SAMPLE_R1CS[..])).unwrap();
+    let witness = (1..=header.n_wires as u64)
+        .map(|i| Fr::from_str(&i.to_string()).unwrap())
+        .collect_vec();
+    let mut wtns = vec![];
+    write_witness(&witness, &scalar_modulus::<Bn256>(), &mut wtns).unwrap();
+
+    let files = [
+        ("circuit.r1cs", &SAMPLE_R1CS[..]),
+        ("witness.wtns", &wtns[..]),
+    ];
+    let circuit = with_temp_files("split", &files, |paths| {
+        load::<Bn256>(paths[0].to_str().unwrap(), paths[1].to_str().unwrap())
+    });
+    let circuit = circuit.unwrap();
+
+    let (public, private) = circuit.split_witness().unwrap();
+    let num_public = (header.n_pub_in + header.n_pub_out) as usize;
+    assert_eq!(public.len(), 1 + num_public);
+    assert_eq!(private.len(), header.n_wires as usize - 1 - num_public);
+    assert!(private.len() >= header.n_prv_in as usize);
+    assert_eq!([&public[..], &private[..]].concat(), witness);
+    assert_eq!(public[1..], circuit.get_public_inputs().unwrap()[..]);
+
+    let setup = CircomCircuit {
+        witness: None,
+        ..circuit
+    };
+    assert_eq!(setup.split_witness(), None);
+
+    // a wire mapping reorders both halves
+    let fr = |s: &str| Fr::from_str(s).unwrap();
+    let mut circuit = sample_circuit();
+    circuit.wire_mapping = Some(vec![0, 1, 3, 2]);
+    let (public, private) = circuit.split_witness().unwrap();
+    assert_eq!(public, vec![fr("1"), fr("12")]);
+    assert_eq!(private, vec![fr("4"), fr("3")]);
+
+    // short witnesses and mappings, or a mapping past the witness, have no split
+    circuit.wire_mapping = Some(vec![0, 1, 3, 4]);
+    assert_eq!(circuit.split_witness(), None);
+    circuit.wire_mapping = Some(vec![0, 1, 3]);
+    assert_eq!(circuit.split_witness(), None);
+    circuit.wire_mapping = None;
+    circuit.witness.as_mut().unwrap().pop();
+    assert_eq!(circuit.split_witness(), None);
+}
+
+#[test]
+fn public_inputs_out_of_range() {
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let mut circuit = sample_circuit();
+    circuit.wire_mapping = Some(vec![0, 3, 1, 2]);
+    assert_eq!(circuit.get_public_inputs(), Some(vec![fr("4")]));
+
+    // a mapping past the witness, a short mapping and a short witness have no public inputs
+    circuit.wire_mapping = Some(vec![0, 4, 1, 2]);
+    assert_eq!(circuit.get_public_inputs(), None);
+    circuit.wire_mapping = Some(vec![0]);
+    assert_eq!(circuit.get_public_inputs(), None);
+    circuit.wire_mapping = None;
+    circuit.witness.as_mut().unwrap().truncate(1);
+    assert_eq!(circuit.get_public_inputs(), None);
+}
+
+#[test]
+fn parallel_load() {
+    use crate::r1cs_reader::SAMPLE_R1CS;
+    use crate::utils::with_temp_files;
+    use crate::wtns_reader::write_witness;
+    use bellman_ce::pairing::bls12_381::Bls12;
+
+    type Fr = <Bn256 as ScalarEngine>::Fr;
+    let witness = (1..=7u64)
+        .map(|i| Fr::from_str(&i.to_string()).unwrap())
+        .collect_vec();
+    let mut wtns = vec![];
+    write_witness(&witness, &scalar_modulus::<Bn256>(), &mut wtns).unwrap();
+
+    let files = [
+        ("circuit.r1cs", &SAMPLE_R1CS[..]),
+        ("witness.wtns", &wtns[..]),
+    ];
+    let (sequential, parallel, wrong_curve, missing) =
+        with_temp_files("parallel", &files, |paths| {
+            let (r1cs_path, wtns_path) = (paths[0].to_str().unwrap(), paths[1].to_str().unwrap());
+            (
+                load::<Bn256>(r1cs_path, wtns_path),
+                load_parallel::<Bn256>(r1cs_path, wtns_path),
+                load_parallel::<Bls12>(r1cs_path, wtns_path),
+                load_parallel::<Bn256>(r1cs_path, "/nonexistent/zkutil.wtns"),
+            )
+        });
+
+    let (sequential, parallel) = (sequential.unwrap(), parallel.unwrap());
+    assert!(parallel.r1cs == sequential.r1cs);
+    assert_eq!(parallel.r1cs.sections, sequential.r1cs.sections);
+    assert_eq!(parallel.witness, sequential.witness);
+    assert_eq!(parallel.wire_mapping, sequential.wire_mapping);
+    assert!(wrong_curve.is_err());
+    assert!(matches!(missing, Err(ParseError::Io { offset: 0, .. })));
+}
+
+#[test]
+fn constraints_referencing_wire() {
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let r1cs = R1CSBuilder::<Bn256>::new(6)
+        .set_public_count(1)
+        .add_constraint(vec![(2, fr("1"))], vec![(2, fr("1"))], vec![(3, fr("1"))])
+        .add_constraint(vec![(3, fr("1"))], vec![(0, fr("1"))], vec![(4, fr("1"))])
+        .add_constraint(
+            vec![(4, fr("1")), (2, fr("0"))],
+            vec![(2, fr("1"))],
+            vec![(1, fr("1"))],
+        )
+        .build()
+        .unwrap();
+    let circuit = CircomCircuit {
+        r1cs,
+        witness: None,
+        wire_mapping: None,
+    };
+    assert_eq!(circuit.constraints_with_wire(2), vec![0, 2]);
+    assert_eq!(circuit.constraints_with_wire(3), vec![0, 1]);
+    assert_eq!(circuit.constraints_with_wire(4), vec![1, 2]);
+    assert_eq!(circuit.constraints_with_wire(0), vec![1]);
+    // wire 5 isn't referenced at all
+    assert!(circuit.constraints_with_wire(5).is_empty());
+    assert!(circuit.constraints_with_wire(100).is_empty());
+}
+
+#[cfg(not(feature = "nolog"))]
+#[test]
+fn proof_timings() {
+    // a squaring chain long enough for every phase to take measurable time
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let n = 64;
+    let mut builder = R1CSBuilder::<Bn256>::new(n + 2);
+    builder.set_public_count(1);
+    for i in 2..n + 1 {
+        builder.add_constraint(
+            vec![(i, fr("1"))],
+            vec![(i, fr("1"))],
+            vec![(i + 1, fr("1"))],
+        );
+    }
+    builder.add_constraint(
+        vec![(n + 1, fr("1"))],
+        vec![(0, fr("1"))],
+        vec![(1, fr("1"))],
+    );
+    let mut witness = vec![fr("1"), fr("0"), fr("3")];
+    for i in 2..n + 1 {
+        let mut square = witness[i];
+        square.square();
+        witness.push(square);
+    }
+    witness[1] = witness[n + 1];
+    let circuit = CircomCircuit {
+        r1cs: builder.build().unwrap(),
+        witness: Some(witness),
+        wire_mapping: None,
+    };
+    circuit.check_witness().unwrap();
+    let setup = CircomCircuit {
+        witness: None,
+        ..circuit.clone()
+    };
+    let params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+
+    let (proof, timings) = create_proof_timed(circuit.clone(), &params, seeded_rng(2)).unwrap();
+    assert!(verify_circuit(&circuit, &params, &proof).unwrap());
+    assert!(proof == prove_with_seed(circuit, &params, 2).unwrap());
+    assert!(timings.h_evaluation > Duration::from_secs(0));
+    assert!(timings.input_representation > Duration::from_secs(0));
+    assert!(timings.point_multiplication > Duration::from_secs(0));
+}
+
+#[test]
+fn params_for_other_circuit() {
+    use crate::utils::{read_params, write_params};
+
+    // the same shape as the sample circuit with another coefficient
+    let fr = |s: &str| <Bn256 as ScalarEngine>::Fr::from_str(s).unwrap();
+    let other = R1CSBuilder::<Bn256>::new(4)
+        .set_public_count(1)
+        .add_constraint(vec![(2, fr("2"))], vec![(3, fr("1"))], vec![(1, fr("1"))])
+        .build()
+        .unwrap();
+    let setup = CircomCircuit {
+        r1cs: other.clone(),
+        witness: None,
+        wire_mapping: None,
+    };
+    let other_params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+    let mut bytes = vec![];
+    write_params(&other_params, &other, &mut bytes, true).unwrap();
+    let (params, hash) = read_params(&mut &bytes[..]).unwrap();
+
+    let circuit = sample_circuit();
+    assert!(matches!(
+        prove_checked(circuit.clone(), &params, &hash, seeded_rng(2)),
+        Err(SynthesisError::CircuitHashMismatch)
+    ));
+    // what the check saves from
+    let proof = prove(circuit.clone(), &params, seeded_rng(2)).unwrap();
+    assert!(!verify_circuit(&circuit, &params, &proof).unwrap());
+
+    let params = sample_params();
+    let mut bytes = vec![];
+    write_params(&params, &circuit.r1cs, &mut bytes, true).unwrap();
+    let (params, hash) = read_params(&mut &bytes[..]).unwrap();
+    let proof = prove_checked(circuit.clone(), &params, &hash, seeded_rng(2)).unwrap();
+    assert!(verify_circuit(&circuit, &params, &proof).unwrap());
+}
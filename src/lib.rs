@@ -1,6 +1,6 @@
 #[macro_use]
 extern crate serde;
-#[macro_use]
+#[cfg_attr(test, macro_use)]
 extern crate hex_literal;
 extern crate byteorder;
 extern crate itertools;
@@ -9,6 +9,11 @@ extern crate num_traits;
 extern crate rand;
 
 pub mod circom_circuit;
+pub mod params_source;
+pub mod parse_error;
 pub mod r1cs_reader;
+mod sections;
+pub mod sym_reader;
 pub mod utils;
 pub mod wtns_reader;
+pub mod zkey_reader;
@@ -0,0 +1,264 @@
+//! Proving parameters that stay on disk.
+//!
+//! `StreamedParameters` indexes a `params.bin` file written by `Parameters::write` and
+//! implements bellman's `ParameterSource`, so the prover decodes query points from the file
+//! while computing each multiexp instead of holding every query in memory at once.
+use byteorder::{BigEndian, ReadBytesExt};
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use bellman_ce::{
+    groth16::{ParameterSource, VerifyingKey},
+    pairing::{CurveAffine, CurveProjective, EncodedPoint, Engine},
+    source::{Source, SourceBuilder},
+    SynthesisError,
+};
+
+/// A run of uncompressed points inside a parameters file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Section {
+    offset: u64,
+    len: usize,
+}
+
+/// Groth16 proving parameters backed by a file rather than by in-memory query vectors.
+///
+/// Only the verifying key and the section offsets are kept in memory after `open`.
+pub struct StreamedParameters<E: Engine> {
+    path: Arc<PathBuf>,
+    vk: VerifyingKey<E>,
+    h: Section,
+    l: Section,
+    a: Section,
+    b_g1: Section,
+    b_g2: Section,
+}
+
+fn point_size<G: CurveAffine>() -> usize {
+    G::Uncompressed::size()
+}
+
+fn decode_checked<G: CurveAffine>(repr: &G::Uncompressed) -> Result<G> {
+    repr.into_affine()
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+        .and_then(|p| if p.is_zero() {
+            Err(Error::new(ErrorKind::InvalidData, "point at infinity"))
+        } else {
+            Ok(p)
+        })
+}
+
+fn read_section<G: CurveAffine, R: Read + Seek>(reader: &mut R, checked: bool) -> Result<Section> {
+    let len = reader.read_u32::<BigEndian>()? as usize;
+    let offset = reader.stream_position()?;
+    if checked {
+        let mut repr = G::Uncompressed::empty();
+        for _ in 0..len {
+            reader.read_exact(repr.as_mut())?;
+            decode_checked::<G>(&repr)?;
+        }
+    } else {
+        let end = offset + (len * point_size::<G>()) as u64;
+        if reader.seek(SeekFrom::End(0))? < end {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "parameters file is truncated"));
+        }
+        reader.seek(SeekFrom::Start(end))?;
+    }
+    Ok(Section { offset, len })
+}
+
+impl<E: Engine> StreamedParameters<E> {
+    /// Indexes the parameters file at `path`. With `checked` set every point is decoded and
+    /// validated once here, the same way `Parameters::read` does; later reads skip the checks.
+    pub fn open<P: AsRef<Path>>(path: P, checked: bool) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut reader = BufReader::new(File::open(&path)?);
+        let vk = VerifyingKey::<E>::read(&mut reader)?;
+        let h = read_section::<E::G1Affine, _>(&mut reader, checked)?;
+        let l = read_section::<E::G1Affine, _>(&mut reader, checked)?;
+        let a = read_section::<E::G1Affine, _>(&mut reader, checked)?;
+        let b_g1 = read_section::<E::G1Affine, _>(&mut reader, checked)?;
+        let b_g2 = read_section::<E::G2Affine, _>(&mut reader, checked)?;
+        Ok(StreamedParameters { path: Arc::new(path), vk, h, l, a, b_g1, b_g2 })
+    }
+
+    pub fn vk(&self) -> &VerifyingKey<E> {
+        &self.vk
+    }
+
+    fn builder<G: CurveAffine>(&self, section: Section, start: usize) -> FileSourceBuilder<G> {
+        FileSourceBuilder {
+            path: self.path.clone(),
+            section,
+            start,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Hands out readers over one section of a parameters file; each multiexp chunk opens its own.
+pub struct FileSourceBuilder<G: CurveAffine> {
+    path: Arc<PathBuf>,
+    section: Section,
+    start: usize,
+    _marker: PhantomData<G>,
+}
+
+impl<G: CurveAffine> Clone for FileSourceBuilder<G> {
+    fn clone(&self) -> Self {
+        FileSourceBuilder {
+            path: self.path.clone(),
+            section: self.section,
+            start: self.start,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<G: CurveAffine> SourceBuilder<G> for FileSourceBuilder<G> {
+    type Source = FileSource<G>;
+
+    fn new(self) -> FileSource<G> {
+        FileSource {
+            path: self.path,
+            section: self.section,
+            pos: self.start,
+            reader: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Sequential reader over a section. The file is opened on first use and skipped ranges
+/// are seeked over rather than decoded.
+pub struct FileSource<G: CurveAffine> {
+    path: Arc<PathBuf>,
+    section: Section,
+    pos: usize,
+    // Reader together with the index of the point it is positioned at.
+    reader: Option<(BufReader<File>, usize)>,
+    _marker: PhantomData<G>,
+}
+
+impl<G: CurveAffine> Source<G> for FileSource<G> {
+    fn add_assign_mixed(
+        &mut self,
+        to: &mut <G as CurveAffine>::Projective,
+    ) -> std::result::Result<(), SynthesisError> {
+        if self.section.len <= self.pos {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "expected more bases when adding from source",
+            )
+            .into());
+        }
+
+        if self.reader.is_none() {
+            self.reader = Some((BufReader::new(File::open(&*self.path)?), usize::MAX));
+        }
+        let (reader, at) = self.reader.as_mut().unwrap();
+        if *at != self.pos {
+            let offset = self.section.offset + (self.pos * point_size::<G>()) as u64;
+            reader.seek(SeekFrom::Start(offset))?;
+        }
+
+        let mut repr = G::Uncompressed::empty();
+        reader.read_exact(repr.as_mut())?;
+        let point = repr
+            .into_affine_unchecked()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        if point.is_zero() {
+            return Err(SynthesisError::UnexpectedIdentity);
+        }
+        to.add_assign_mixed(&point);
+
+        self.pos += 1;
+        *at = self.pos;
+
+        Ok(())
+    }
+
+    fn skip(&mut self, amt: usize) -> std::result::Result<(), SynthesisError> {
+        if self.section.len <= self.pos {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "expected more bases skipping from source",
+            )
+            .into());
+        }
+
+        self.pos += amt;
+
+        Ok(())
+    }
+}
+
+impl<E: Engine> ParameterSource<E> for &StreamedParameters<E> {
+    type G1Builder = FileSourceBuilder<E::G1Affine>;
+    type G2Builder = FileSourceBuilder<E::G2Affine>;
+
+    fn get_vk(&mut self, _: usize) -> std::result::Result<VerifyingKey<E>, SynthesisError> {
+        Ok(self.vk.clone())
+    }
+
+    fn get_h(&mut self, _: usize) -> std::result::Result<Self::G1Builder, SynthesisError> {
+        Ok(self.builder(self.h, 0))
+    }
+
+    fn get_l(&mut self, _: usize) -> std::result::Result<Self::G1Builder, SynthesisError> {
+        Ok(self.builder(self.l, 0))
+    }
+
+    fn get_a(
+        &mut self,
+        num_inputs: usize,
+        num_aux: usize,
+    ) -> std::result::Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        // Same consistency check as for in-memory `Parameters`
+        if self.a.len != num_inputs + num_aux {
+            return Err(SynthesisError::InconsistentParameters);
+        }
+        Ok((self.builder(self.a, 0), self.builder(self.a, num_inputs)))
+    }
+
+    fn get_b_g1(
+        &mut self,
+        num_inputs: usize,
+        _: usize,
+    ) -> std::result::Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        Ok((self.builder(self.b_g1, 0), self.builder(self.b_g1, num_inputs)))
+    }
+
+    fn get_b_g2(
+        &mut self,
+        num_inputs: usize,
+        _: usize,
+    ) -> std::result::Result<(Self::G2Builder, Self::G2Builder), SynthesisError> {
+        Ok((self.builder(self.b_g2, 0), self.builder(self.b_g2, num_inputs)))
+    }
+}
+
+#[cfg(test)]
+use bellman_ce::{groth16::create_random_proof, pairing::bn256::Bn256};
+#[cfg(test)]
+use crate::circom_circuit::{create_rng, sample_circuit, sample_params, verify};
+
+#[test]
+fn prove_from_streamed_params() {
+    use crate::utils::with_temp_files;
+
+    let params = sample_params();
+    let mut written = vec![];
+    params.write(&mut written).unwrap();
+
+    let circuit = sample_circuit();
+    let inputs = circuit.get_public_inputs().unwrap();
+    let proof = with_temp_files("streamed", &[("params.bin", &written[..])], |paths| {
+        let streamed = StreamedParameters::<Bn256>::open(&paths[0], true).unwrap();
+        assert!(*streamed.vk() == params.vk);
+        create_random_proof(circuit, &streamed, &mut create_rng()).unwrap()
+    });
+    assert!(verify(&params, &proof, &inputs).unwrap());
+}
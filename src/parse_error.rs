@@ -0,0 +1,120 @@
+//! Errors returned by the binary circom file readers.
+use std::fmt;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+
+/// A failure to parse an `.r1cs` or `.wtns` file. Every variant carries the byte offset at
+/// which parsing stopped.
+#[derive(Debug)]
+pub enum ParseError {
+    BadMagic { offset: u64 },
+    UnsupportedVersion { offset: u64, version: u32 },
+    /// The data ended in the middle of a section
+    TruncatedSection { offset: u64 },
+    /// The file uses field elements of a size other than 32 bytes
+    FieldSizeMismatch { offset: u64, field_size: u32 },
+    /// Any other structural problem, e.g. a bad section size or a non-canonical field element
+    Malformed { offset: u64, reason: String },
+    /// The verifying key expects a different number of public inputs than were given
+    WrongInputCount { offset: u64, expected: usize, found: usize },
+    Io { offset: u64, error: io::Error },
+}
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
+impl ParseError {
+    pub fn offset(&self) -> u64 {
+        match self {
+            ParseError::BadMagic { offset }
+            | ParseError::UnsupportedVersion { offset, .. }
+            | ParseError::TruncatedSection { offset }
+            | ParseError::FieldSizeMismatch { offset, .. }
+            | ParseError::Malformed { offset, .. }
+            | ParseError::WrongInputCount { offset, .. }
+            | ParseError::Io { offset, .. } => *offset,
+        }
+    }
+
+    pub(crate) fn malformed<S: Into<String>>(offset: u64, reason: S) -> Self {
+        ParseError::Malformed { offset, reason: reason.into() }
+    }
+
+    pub(crate) fn from_io(error: io::Error, offset: u64) -> Self {
+        match error.kind() {
+            ErrorKind::UnexpectedEof => ParseError::TruncatedSection { offset },
+            ErrorKind::InvalidData => ParseError::malformed(offset, error.to_string()),
+            _ => ParseError::Io { offset, error },
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::BadMagic { .. } => write!(f, "invalid magic number")?,
+            ParseError::UnsupportedVersion { version, .. } => write!(f, "unsupported version {}", version)?,
+            ParseError::TruncatedSection { .. } => write!(f, "unexpected end of data")?,
+            ParseError::FieldSizeMismatch { field_size, .. } => {
+                write!(f, "unsupported field size {}, only 32-byte fields are supported", field_size)?
+            }
+            ParseError::Malformed { reason, .. } => write!(f, "{}", reason)?,
+            ParseError::WrongInputCount { expected, found, .. } => {
+                write!(f, "verifying key expects {} public inputs, got {}", expected, found)?
+            }
+            ParseError::Io { error, .. } => write!(f, "I/O error: {}", error)?,
+        }
+        write!(f, " at offset {}", self.offset())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for io::Error {
+    fn from(e: ParseError) -> io::Error {
+        match e {
+            ParseError::Io { error, .. } => error,
+            ParseError::TruncatedSection { .. } => io::Error::new(ErrorKind::UnexpectedEof, e.to_string()),
+            _ => io::Error::new(ErrorKind::InvalidData, e.to_string()),
+        }
+    }
+}
+
+/// Attaches the reader offset to errors of lower level reads, e.g.
+/// `reader.read_u32::<LittleEndian>().at(reader.offset)?`. The offset argument is evaluated
+/// after the read, so it points at where the read stopped.
+pub(crate) trait AtOffset<T> {
+    fn at(self, offset: u64) -> ParseResult<T>;
+}
+
+impl<T> AtOffset<T> for io::Result<T> {
+    fn at(self, offset: u64) -> ParseResult<T> {
+        self.map_err(|e| ParseError::from_io(e, offset))
+    }
+}
+
+/// Reader that keeps track of its position, so errors can report where they happened
+/// even for sources that don't implement `Seek`.
+pub(crate) struct OffsetReader<R> {
+    inner: R,
+    pub offset: u64,
+}
+
+impl<R> OffsetReader<R> {
+    pub fn new(inner: R, offset: u64) -> Self {
+        OffsetReader { inner, offset }
+    }
+}
+
+impl<R: Read> Read for OffsetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for OffsetReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.offset = self.inner.seek(pos)?;
+        Ok(self.offset)
+    }
+}
@@ -1,17 +1,18 @@
 #![allow(unused_variables, dead_code)]
 use byteorder::{ReadBytesExt, LittleEndian};
-use std::{collections::HashMap, io::{Error, ErrorKind, Read, Result, Seek, SeekFrom}};
-use bellman_ce::pairing::{
-    Engine,
-    bn256::Bn256,
-    ff::{
-        Field, PrimeField, PrimeFieldRepr,
-    }
-};
-use crate::circom_circuit::Constraint;
+use std::{io::{Error, ErrorKind, Read, Result, Seek, SeekFrom}, marker::PhantomData};
+use num_bigint::BigUint;
+use bellman_ce::pairing::Engine;
+use crate::circom_circuit::{Constraint, CustomGate, CustomGateUse};
+use crate::parse_error::{AtOffset, OffsetReader, ParseError, ParseResult};
+use crate::sections::{self, Sections};
+use crate::utils::{detect_curve, engine_curve, fr_from_le_bytes, Curve};
 #[cfg(test)]
 use std::io::{BufReader, Cursor};
+#[cfg(test)]
+use bellman_ce::pairing::bn256::Bn256;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Header {
     pub field_size: u32,
     pub prime_size: Vec<u8>,
@@ -23,19 +24,34 @@ pub struct Header {
     pub n_constraints: u32,
 }
 
+impl Header {
+    /// The prime of the field the circuit was compiled for
+    pub fn prime(&self) -> BigUint {
+        BigUint::from_bytes_le(&self.prime_size)
+    }
+}
+
 pub struct R1CSFile<E: Engine> {
     pub version: u32,
     pub header: Header,
+    pub curve: Curve,
     pub constraints: Vec<Constraint<E>>,
     pub wire_mapping: Vec<u64>,
+    pub custom_gates: Vec<CustomGate<E>>,
+    pub custom_gate_uses: Vec<CustomGateUse>,
+    /// Type, file offset of the data and size of every section, in file order, including
+    /// sections of unknown types that were skipped
+    pub sections: Vec<(u32, u64, u64)>,
 }
 
-fn read_field<R: Read, E: Engine>(mut reader: R) -> Result<E::Fr> {
-    let mut repr = E::Fr::zero().into_repr();
-    repr.read_le(&mut reader)?;
-    let fr = E::Fr::from_repr(repr)
-        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
-    Ok(fr)
+fn read_field<R: Read, E: Engine>(mut reader: R, header: &Header) -> Result<E::Fr> {
+    // the header is checked to declare 32-byte fields before any element is read
+    let mut bytes = [0u8; 32];
+    reader.read_exact(&mut bytes)?;
+    fr_from_le_bytes(&bytes, header.field_size as usize).map_err(|e| match e {
+        ParseError::Malformed { reason, .. } => Error::new(ErrorKind::InvalidData, reason),
+        e => e.into(),
+    })
 }
 
 fn read_header<R: Read>(mut reader: R, size: u64) -> Result<Header> {
@@ -64,25 +80,83 @@ fn read_constraint_vec<R: Read, E:Engine>(mut reader: R, header: &Header) -> Res
     for _ in 0..n_vec {
         vec.push((
             reader.read_u32::<LittleEndian>()? as usize,
-            read_field::<&mut R, E>(&mut reader)?,
+            read_field::<&mut R, E>(&mut reader, header)?,
         ));
     }
     Ok(vec)
 }
 
+fn read_constraint<R: Read, E: Engine>(mut reader: R, header: &Header) -> Result<Constraint<E>> {
+    Ok(Constraint {
+        a: read_constraint_vec::<&mut R, E>(&mut reader, header)?,
+        b: read_constraint_vec::<&mut R, E>(&mut reader, header)?,
+        c: read_constraint_vec::<&mut R, E>(&mut reader, header)?,
+    })
+}
+
+// Upper bound on the constraints allocated for up front, so a header claiming billions of
+// them can't exhaust memory before the section turns out to be too short
+const MAX_PREALLOCATED_CONSTRAINTS: usize = 1 << 16;
+
 fn read_constraints<R: Read, E: Engine>(mut reader: R, size: u64, header: &Header) -> Result<Vec<Constraint<E>>> {
     // todo check section size
-    let mut vec = Vec::with_capacity(header.n_constraints as usize);
+    let mut vec = Vec::with_capacity((header.n_constraints as usize).min(MAX_PREALLOCATED_CONSTRAINTS));
     for _ in 0..header.n_constraints {
-        vec.push((
-             read_constraint_vec::<&mut R, E>(&mut reader, &header)?,
-             read_constraint_vec::<&mut R, E>(&mut reader, &header)?,
-             read_constraint_vec::<&mut R, E>(&mut reader, &header)?,
-        ));
+        vec.push(read_constraint::<&mut R, E>(&mut reader, header)?);
     }
     Ok(vec)
 }
 
+/// Decodes the constraints section on all cores. The section is read into memory first and
+/// split at constraint boundaries, which can be found from the term counts alone.
+#[cfg(feature = "parallel")]
+fn read_constraints_parallel<R: Read, E: Engine>(reader: &mut OffsetReader<R>, size: u64, header: &Header) -> ParseResult<Vec<Constraint<E>>> {
+    use bellman_ce::worker::Worker;
+    use byteorder::ByteOrder;
+
+    let section_offset = reader.offset;
+    let mut section = vec![0u8; size as usize];
+    reader.read_exact(&mut section).at(reader.offset)?;
+
+    let n_constraints = header.n_constraints as usize;
+    let term_size = 4 + header.field_size as usize;
+    let truncated = || ParseError::TruncatedSection { offset: section_offset + size };
+    let mut starts = Vec::with_capacity(n_constraints.min(MAX_PREALLOCATED_CONSTRAINTS));
+    let mut pos = 0;
+    for _ in 0..n_constraints {
+        starts.push(pos);
+        for _ in 0..3 {
+            // pos stays within the section, so pos + 4 can't overflow
+            let n_terms = section.get(pos..pos + 4).map(LittleEndian::read_u32).ok_or_else(truncated)?;
+            pos = (n_terms as usize).checked_mul(term_size)
+                .and_then(|terms| terms.checked_add(pos + 4))
+                .filter(|end| *end <= section.len())
+                .ok_or_else(truncated)?;
+        }
+    }
+
+    let worker = Worker::new();
+    let chunk_size = worker.get_chunk_size(n_constraints);
+    let mut chunks = (0..n_constraints.div_ceil(chunk_size)).map(|_| Ok(vec![])).collect::<Vec<_>>();
+    worker.scope(n_constraints, |scope, chunk_size| {
+        for (starts, result) in starts.chunks(chunk_size).zip(chunks.iter_mut()) {
+            let section = &section;
+            scope.spawn(move |_| {
+                *result = starts.iter().map(|start| {
+                    let mut reader = OffsetReader::new(&section[*start..], section_offset + *start as u64);
+                    read_constraint::<_, E>(&mut reader, header).at(reader.offset)
+                }).collect::<ParseResult<Vec<_>>>();
+            });
+        }
+    });
+
+    let mut constraints = Vec::with_capacity(n_constraints.min(MAX_PREALLOCATED_CONSTRAINTS));
+    for chunk in chunks {
+        constraints.extend(chunk?);
+    }
+    Ok(constraints)
+}
+
 fn read_map<R: Read>(mut reader: R, size: u64, header: &Header) -> Result<Vec<u64>> {
     if size != header.n_wires as u64 * 8 {
         return Err(Error::new(ErrorKind::InvalidData, "Invalid map section size"))
@@ -97,113 +171,409 @@ fn read_map<R: Read>(mut reader: R, size: u64, header: &Header) -> Result<Vec<u6
     Ok(vec)
 }
 
-pub fn read<R: Read + Seek>(mut reader: R) -> Result<R1CSFile<Bn256>> {
-    let mut magic = [0u8; 4];
-    reader.read_exact(&mut magic)?;
-    if magic != [0x72, 0x31, 0x63, 0x73] { // magic = "r1cs"
-        return Err(Error::new(ErrorKind::InvalidData, "Invalid magic number"))
+fn read_string<R: Read>(mut reader: R) -> Result<String> {
+    let mut bytes = vec![];
+    loop {
+        match reader.read_u8()? {
+            0 => break,
+            byte => bytes.push(byte),
+        }
     }
+    String::from_utf8(bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
 
-    let version = reader.read_u32::<LittleEndian>()?;
-    if version != 1 {
-        return Err(Error::new(ErrorKind::InvalidData, "Unsupported version"))
+fn read_custom_gates<R: Read, E: Engine>(mut reader: R, header: &Header) -> Result<Vec<CustomGate<E>>> {
+    let n_gates = reader.read_u32::<LittleEndian>()?;
+    let mut vec = Vec::with_capacity(n_gates as usize);
+    for _ in 0..n_gates {
+        let template_name = read_string(&mut reader)?;
+        let n_parameters = reader.read_u32::<LittleEndian>()?;
+        let mut parameters = Vec::with_capacity(n_parameters as usize);
+        for _ in 0..n_parameters {
+            parameters.push(read_field::<&mut R, E>(&mut reader, header)?);
+        }
+        vec.push(CustomGate { template_name, parameters });
     }
+    Ok(vec)
+}
 
-    let num_sections = reader.read_u32::<LittleEndian>()?;
+fn read_custom_gate_uses<R: Read>(mut reader: R, n_gates: usize) -> Result<Vec<CustomGateUse>> {
+    let n_uses = reader.read_u32::<LittleEndian>()?;
+    let mut vec = Vec::with_capacity(n_uses as usize);
+    for _ in 0..n_uses {
+        let gate = reader.read_u32::<LittleEndian>()? as usize;
+        if gate >= n_gates {
+            return Err(Error::new(ErrorKind::InvalidData, "Custom gate use references an unknown gate"))
+        }
+        let n_signals = reader.read_u32::<LittleEndian>()?;
+        let mut signals = Vec::with_capacity(n_signals as usize);
+        for _ in 0..n_signals {
+            signals.push(reader.read_u64::<LittleEndian>()?);
+        }
+        vec.push(CustomGateUse { gate, signals });
+    }
+    Ok(vec)
+}
 
-    // section type -> file offset
-    let mut sec_offsets = HashMap::<u32, u64>::new();
-    let mut sec_sizes = HashMap::<u32, u64>::new();
+const HEADER_TYPE: u32 = 1;
+const CONSTRAINT_TYPE: u32 = 2;
+const WIRE2LABEL_TYPE: u32 = 3;
+const CUSTOM_GATES_TYPE: u32 = 4;
+const CUSTOM_GATE_USES_TYPE: u32 = 5;
 
-    // get file offset of each section
-    for _ in 0..num_sections {
-        let sec_type = reader.read_u32::<LittleEndian>()?;
-        let sec_size = reader.read_u64::<LittleEndian>()?;
-        let offset = reader.seek(SeekFrom::Current(0))?;
-        sec_offsets.insert(sec_type, offset);
-        sec_sizes.insert(sec_type, sec_size);
-        reader.seek(SeekFrom::Current(sec_size as i64))?;
+/// Name of section type `sec_type` in the R1CS format spec, `None` for types it doesn't define
+pub fn section_name(sec_type: u32) -> Option<&'static str> {
+    match sec_type {
+        HEADER_TYPE => Some("header"),
+        CONSTRAINT_TYPE => Some("constraints"),
+        WIRE2LABEL_TYPE => Some("wire2label"),
+        CUSTOM_GATES_TYPE => Some("custom gates list"),
+        CUSTOM_GATE_USES_TYPE => Some("custom gates application"),
+        _ => None,
     }
+}
 
-    let header_type = 1;
-    let constraint_type = 2;
-    let wire2label_type = 3;
+fn read_sections<R: Read + Seek>(reader: &mut OffsetReader<R>) -> ParseResult<Sections> {
+    // magic = "r1cs", version 2 adds the optional custom gates sections, the rest of the
+    // layout is unchanged
+    sections::read_sections(reader, b"r1cs", &[1, 2])
+}
 
-    reader.seek(SeekFrom::Start(*sec_offsets.get(&header_type).unwrap()))?;
-    let header = read_header(&mut reader, *sec_sizes.get(&header_type).unwrap())?;
-    if header.field_size != 32 {
-        return Err(Error::new(ErrorKind::InvalidData, "This parser only supports 32-byte fields"))
+/// Reads the header section and determines the curve from the prime it declares
+fn read_header_section<R: Read + Seek>(reader: &mut OffsetReader<R>, sections: &Sections) -> ParseResult<(Header, Curve)> {
+    let (offset, size) = sections.get(HEADER_TYPE)?;
+    reader.seek(SeekFrom::Start(offset)).at(offset)?;
+    let field_size = reader.read_u32::<LittleEndian>().at(reader.offset)?;
+    if field_size != 32 {
+        return Err(ParseError::FieldSizeMismatch { offset, field_size })
     }
-    if header.prime_size != hex!("010000f093f5e1439170b97948e833285d588181b64550b829a031e1724e6430") {
-        return Err(Error::new(ErrorKind::InvalidData, "This parser only supports bn256"))
+    reader.seek(SeekFrom::Start(offset)).at(offset)?;
+    let header = read_header(&mut *reader, size).at(reader.offset)?;
+    let prime = BigUint::from_bytes_le(&header.prime_size);
+    let curve = detect_curve(&prime).ok_or_else(|| ParseError::malformed(
+        offset + 4,
+        format!("Unsupported prime {}, expected the BN254 or BLS12-381 scalar field", prime),
+    ))?;
+    Ok((header, curve))
+}
+
+/// Returns the curve an r1cs file was compiled for, so callers can pick the matching `Engine`
+pub fn read_curve<R: Read + Seek>(mut reader: R) -> ParseResult<Curve> {
+    let start = reader.stream_position().at(0)?;
+    let mut reader = OffsetReader::new(&mut reader, start);
+    let sections = read_sections(&mut reader)?;
+    Ok(read_header_section(&mut reader, &sections)?.1)
+}
+
+fn check_engine<E: Engine>(curve: Curve, sections: &Sections) -> ParseResult<()> {
+    if engine_curve::<E>() != Some(curve) {
+        return Err(ParseError::malformed(
+            sections.get(HEADER_TYPE)?.0 + 4,
+            format!("Circuit is compiled for {}, which doesn't match the engine", curve),
+        ))
     }
+    Ok(())
+}
 
-    reader.seek(SeekFrom::Start(*sec_offsets.get(&constraint_type).unwrap()))?;
-    let constraints = read_constraints::<&mut R, Bn256>(&mut reader, *sec_sizes.get(&constraint_type).unwrap(), &header)?;
+/// Reads only the header section, e.g. to size buffers before streaming the constraints
+pub fn read_file_header<R: Read + Seek>(mut reader: R) -> ParseResult<Header> {
+    let start = reader.stream_position().at(0)?;
+    let mut reader = OffsetReader::new(&mut reader, start);
+    let sections = read_sections(&mut reader)?;
+    Ok(read_header_section(&mut reader, &sections)?.0)
+}
 
-    reader.seek(SeekFrom::Start(*sec_offsets.get(&wire2label_type).unwrap()))?;
-    let wire_mapping = read_map(&mut reader, *sec_sizes.get(&wire2label_type).unwrap(), &header)?;
+/// Returns the header of the r1cs file at `path`. Only the section table and the header are
+/// read, the constraints are seeked over however large they are.
+pub fn read_header_only<P: AsRef<std::path::Path>>(path: P) -> ParseResult<Header> {
+    let file = std::fs::File::open(path).at(0)?;
+    read_file_header(std::io::BufReader::new(file))
+}
+
+struct ConstraintsIter<E: Engine, R> {
+    reader: OffsetReader<R>,
+    header: Option<Header>,
+    remaining: u32,
+    section_end: u64,
+    // error found while locating the constraints section, yielded as the only item
+    error: Option<ParseError>,
+    _marker: PhantomData<E>,
+}
+
+impl<E: Engine, R: Read + Seek> ConstraintsIter<E, R> {
+    fn locate(&mut self) -> ParseResult<()> {
+        self.reader.stream_position().at(0)?;
+        let sections = read_sections(&mut self.reader)?;
+        let (header, curve) = read_header_section(&mut self.reader, &sections)?;
+        check_engine::<E>(curve, &sections)?;
+        let (offset, size) = sections.get(CONSTRAINT_TYPE)?;
+        self.reader.seek(SeekFrom::Start(offset)).at(offset)?;
+        self.remaining = header.n_constraints;
+        self.section_end = offset + size;
+        self.header = Some(header);
+        Ok(())
+    }
+}
+
+impl<E: Engine, R: Read + Seek> Iterator for ConstraintsIter<E, R> {
+    type Item = ParseResult<Constraint<E>>;
 
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+        let header = self.header.as_ref()?;
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let constraint = read_constraint::<_, E>(&mut self.reader, header).at(self.reader.offset);
+        if constraint.is_err() {
+            self.remaining = 0;
+        } else if self.remaining == 0 && self.reader.offset != self.section_end {
+            return Some(Err(ParseError::malformed(self.reader.offset, "Invalid constraints section size")));
+        }
+        Some(constraint)
+    }
 
-    Ok(R1CSFile { version, header, constraints, wire_mapping })
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.remaining as usize + self.error.is_some() as usize;
+        (n, Some(n))
+    }
 }
 
+/// Yields the constraints one at a time without loading the whole section into memory.
+/// Problems with the file layout are reported as the first and only item; a malformed
+/// constraint ends the iteration after its error.
+pub fn constraints_iter<E: Engine, R: Read + Seek>(reader: R) -> impl Iterator<Item = ParseResult<Constraint<E>>> {
+    let mut iter = ConstraintsIter::<E, R> {
+        reader: OffsetReader::new(reader, 0),
+        header: None,
+        remaining: 0,
+        section_end: 0,
+        error: None,
+        _marker: PhantomData,
+    };
+    if let Err(e) = iter.locate() {
+        iter.error = Some(e);
+    }
+    iter
+}
+
+/// An r1cs file whose section table and header have been parsed, with the constraints only
+/// read from the underlying source once `force_load_constraints` is called. Cheap to hold for
+/// many circuits when only some of them end up being proven.
+pub struct LazyR1cs<E: Engine, R> {
+    reader: OffsetReader<R>,
+    sections: Sections,
+    header: Header,
+    curve: Curve,
+    constraints: Option<Vec<Constraint<E>>>,
+}
+
+impl<E: Engine, R: Read + Seek> LazyR1cs<E, R> {
+    pub fn version(&self) -> u32 {
+        self.sections.version
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    /// The constraints, if they have been loaded
+    pub fn constraints(&self) -> Option<&[Constraint<E>]> {
+        self.constraints.as_deref()
+    }
+
+    /// Seeks to the constraints section and reads it, unless that was already done
+    pub fn force_load_constraints(&mut self) -> ParseResult<&[Constraint<E>]> {
+        if self.constraints.is_none() {
+            let reader = &mut self.reader;
+            let (offset, size) = self.sections.get(CONSTRAINT_TYPE)?;
+            reader.seek(SeekFrom::Start(offset)).at(offset)?;
+            #[cfg(not(feature = "parallel"))]
+            let constraints = read_constraints::<_, E>(&mut *reader, size, &self.header).at(reader.offset)?;
+            #[cfg(feature = "parallel")]
+            let constraints = read_constraints_parallel::<_, E>(reader, size, &self.header)?;
+            self.constraints = Some(constraints);
+        }
+        Ok(self.constraints.as_deref().unwrap_or_default())
+    }
+
+    /// Loads the constraints if needed and reads the remaining sections
+    pub fn into_file(mut self) -> ParseResult<R1CSFile<E>> {
+        self.force_load_constraints()?;
+        let LazyR1cs { mut reader, sections, header, curve, constraints } = self;
+        let version = sections.version;
+        let constraints = constraints.unwrap_or_default();
+
+        let (offset, size) = sections.get(WIRE2LABEL_TYPE)?;
+        reader.seek(SeekFrom::Start(offset)).at(offset)?;
+        let wire_mapping = read_map(&mut reader, size, &header).at(reader.offset)?;
+
+        let mut custom_gates = vec![];
+        let mut custom_gate_uses = vec![];
+        if version >= 2 && sections.contains(CUSTOM_GATES_TYPE) {
+            let (offset, size) = sections.get(CUSTOM_GATES_TYPE)?;
+            reader.seek(SeekFrom::Start(offset)).at(offset)?;
+            custom_gates = read_custom_gates::<_, E>(&mut reader, &header).at(reader.offset)?;
+            if reader.offset != offset + size {
+                return Err(ParseError::malformed(reader.offset, "Invalid custom gates section size"))
+            }
+        }
+        // uses are checked against the declared gates, so any use in a file without
+        // a custom gates section is rejected
+        if version >= 2 && sections.contains(CUSTOM_GATE_USES_TYPE) {
+            let (offset, size) = sections.get(CUSTOM_GATE_USES_TYPE)?;
+            reader.seek(SeekFrom::Start(offset)).at(offset)?;
+            custom_gate_uses = read_custom_gate_uses(&mut reader, custom_gates.len()).at(reader.offset)?;
+            if reader.offset != offset + size {
+                return Err(ParseError::malformed(reader.offset, "Invalid custom gate uses section size"))
+            }
+        }
+
+        Ok(R1CSFile { version, header, curve, constraints, wire_mapping, custom_gates, custom_gate_uses, sections: sections.table })
+    }
+}
+
+/// Parses the section table and the header, leaving the constraints to
+/// `LazyR1cs::force_load_constraints`
+pub fn read_lazy<E: Engine, R: Read + Seek>(mut reader: R) -> ParseResult<LazyR1cs<E, R>> {
+    let start = reader.stream_position().at(0)?;
+    let mut reader = OffsetReader::new(reader, start);
+    let sections = read_sections(&mut reader)?;
+    let (header, curve) = read_header_section(&mut reader, &sections)?;
+    check_engine::<E>(curve, &sections)?;
+    Ok(LazyR1cs { reader, sections, header, curve, constraints: None })
+}
+
+pub fn read<E: Engine, R: Read + Seek>(reader: R) -> ParseResult<R1CSFile<E>> {
+    read_lazy(reader)?.into_file()
+}
+
+#[cfg(all(feature = "mmap", unix))]
+mod mmap {
+    use std::{fs::File, io, ops::Deref, os::unix::io::AsRawFd, ptr, slice};
+
+    /// Read-only private mapping of a whole file
+    pub(super) struct Mmap {
+        ptr: *mut libc::c_void,
+        len: usize,
+    }
+
+    impl Mmap {
+        /// Unsafe because the mapped bytes change if the file is modified while mapped
+        pub(super) unsafe fn map(file: &File) -> io::Result<Mmap> {
+            let len = file.metadata()?.len() as usize;
+            if len == 0 {
+                // mmap rejects empty mappings
+                return Ok(Mmap { ptr: ptr::null_mut(), len });
+            }
+            let ptr = libc::mmap(ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0);
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Mmap { ptr, len })
+        }
+    }
+
+    impl Deref for Mmap {
+        type Target = [u8];
+
+        fn deref(&self) -> &[u8] {
+            if self.len == 0 {
+                return &[];
+            }
+            unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Mmap {
+        fn drop(&mut self) {
+            if self.len != 0 {
+                unsafe { libc::munmap(self.ptr, self.len) };
+            }
+        }
+    }
+}
+
+/// Same as `read`, but parses the file from a memory mapping instead of copying it through
+/// a buffered reader, which keeps peak memory close to the size of the parsed constraints.
+///
+/// # Safety caveat
+///
+/// The file must not be modified or truncated while it's being parsed. Changes made by other
+/// processes show up in the mapping, and truncation makes reading the mapping fail with
+/// `SIGBUS`.
+#[cfg(all(feature = "mmap", unix))]
+pub fn read_r1cs_mmap<E: Engine, P: AsRef<std::path::Path>>(path: P) -> ParseResult<R1CSFile<E>> {
+    let file = std::fs::File::open(path).at(0)?;
+    let map = unsafe { mmap::Mmap::map(&file) }.at(0)?;
+    read(std::io::Cursor::new(&map[..]))
+}
+
+#[cfg(test)]
+pub(crate) const SAMPLE_R1CS: [u8; 816] = hex!("
+    72316373
+    01000000
+    03000000
+    01000000 40000000 00000000
+    20000000
+    010000f0 93f5e143 9170b979 48e83328 5d588181 b64550b8 29a031e1 724e6430
+    07000000
+    01000000
+    02000000
+    03000000
+    e8030000 00000000
+    03000000
+    02000000 88020000 00000000
+    02000000
+    05000000 03000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    06000000 08000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    03000000
+    00000000 02000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    02000000 14000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    03000000 0C000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    02000000
+    00000000 05000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    02000000 07000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    03000000
+    01000000 04000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    04000000 08000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    05000000 03000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    02000000
+    03000000 2C000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    06000000 06000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    00000000
+    01000000
+    06000000 04000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    03000000
+    00000000 06000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    02000000 0B000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    03000000 05000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    01000000
+    06000000 58020000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    03000000 38000000 00000000
+    00000000 00000000
+    03000000 00000000
+    0a000000 00000000
+    0b000000 00000000
+    0c000000 00000000
+    0f000000 00000000
+    44010000 00000000
+");
+
 #[test]
 fn sample() {
-    let data = hex!("
-        72316373
-        01000000
-        03000000
-        01000000 40000000 00000000
-        20000000
-        010000f0 93f5e143 9170b979 48e83328 5d588181 b64550b8 29a031e1 724e6430
-        07000000
-        01000000
-        02000000
-        03000000
-        e8030000 00000000
-        03000000
-        02000000 88020000 00000000
-        02000000
-        05000000 03000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        06000000 08000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        03000000
-        00000000 02000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        02000000 14000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        03000000 0C000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        02000000
-        00000000 05000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        02000000 07000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        03000000
-        01000000 04000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        04000000 08000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        05000000 03000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        02000000
-        03000000 2C000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        06000000 06000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        00000000
-        01000000
-        06000000 04000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        03000000
-        00000000 06000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        02000000 0B000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        03000000 05000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        01000000
-        06000000 58020000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
-        03000000 38000000 00000000
-        00000000 00000000
-        03000000 00000000
-        0a000000 00000000
-        0b000000 00000000
-        0c000000 00000000
-        0f000000 00000000
-        44010000 00000000
-    ");
-
     use bellman_ce::pairing::ff;
-    let reader = BufReader::new(Cursor::new(&data[..]));
-    let file = read(reader).unwrap();
+    let reader = BufReader::new(Cursor::new(&SAMPLE_R1CS[..]));
+    let file = read::<Bn256, _>(reader).unwrap();
     assert_eq!(file.version, 1);
+    assert_eq!(file.curve, Curve::Bn254);
 
     assert_eq!(file.header.field_size, 32);
     assert_eq!(file.header.prime_size, &hex!("010000f093f5e1439170b97948e833285d588181b64550b829a031e1724e6430"));
@@ -215,13 +585,351 @@ fn sample() {
     assert_eq!(file.header.n_constraints, 3);
 
     assert_eq!(file.constraints.len(), 3);
-    assert_eq!(file.constraints[0].0.len(), 2);
-    assert_eq!(file.constraints[0].0[0].0, 5);
-    assert_eq!(file.constraints[0].0[0].1, ff::from_hex("0x03").unwrap());
-    assert_eq!(file.constraints[2].1[0].0, 0);
-    assert_eq!(file.constraints[2].1[0].1, ff::from_hex("0x06").unwrap());
-    assert_eq!(file.constraints[1].2.len(), 0);
+    assert_eq!(file.constraints[0].a.len(), 2);
+    assert_eq!(file.constraints[0].a[0].0, 5);
+    assert_eq!(file.constraints[0].a[0].1, ff::from_hex("0x03").unwrap());
+    assert_eq!(file.constraints[2].b[0].0, 0);
+    assert_eq!(file.constraints[2].b[0].1, ff::from_hex("0x06").unwrap());
+    assert_eq!(file.constraints[1].c.len(), 0);
 
     assert_eq!(file.wire_mapping.len(), 7);
     assert_eq!(file.wire_mapping[1], 3);
-}
\ No newline at end of file
+}
+#[test]
+fn custom_gates_v2() {
+    use bellman_ce::pairing::ff;
+    // the v1 sample bumped to version 2 with three extra sections appended
+    let mut data = SAMPLE_R1CS.to_vec();
+    data[4..12].copy_from_slice(&hex!("02000000 06000000"));
+    data.extend_from_slice(&hex!("
+        04000000 2d000000 00000000
+        01000000
+        434d756c 00
+        01000000
+        05000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+        09000000 03000000 00000000
+        aabbcc
+        05000000 24000000 00000000
+        01000000
+        00000000
+        03000000
+        01000000 00000000
+        02000000 00000000
+        03000000 00000000
+    "));
+
+    let file = read::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    assert_eq!(file.version, 2);
+    assert_eq!(file.constraints.len(), 3);
+    assert_eq!(file.wire_mapping[1], 3);
+    assert_eq!(file.custom_gates.len(), 1);
+    assert_eq!(file.custom_gates[0].template_name, "CMul");
+    assert_eq!(file.custom_gates[0].parameters, vec![ff::from_hex("0x05").unwrap()]);
+    assert_eq!(file.custom_gate_uses, vec![CustomGateUse { gate: 0, signals: vec![1, 2, 3] }]);
+
+    // v1 files ignore custom gate sections
+    data[4..8].copy_from_slice(&hex!("01000000"));
+    let file = read::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    assert!(file.custom_gates.is_empty());
+    assert!(file.custom_gate_uses.is_empty());
+
+    // a use pointing past the declared gates is rejected
+    data[4..8].copy_from_slice(&hex!("02000000"));
+    let use_gate = data.len() - 32;
+    data[use_gate] = 1;
+    assert!(read::<Bn256, _>(Cursor::new(&data[..])).is_err());
+}
+
+// src/testdata/custom_gates.r1cs was assembled by hand from the description of circom's v2
+// format, without circom, which isn't available to compile a circuit with custom gates. It has
+// 5 wires, one constraint, the gates CMul() and CAddConst(7) and a use of each. Until it's
+// replaced by compiler output, this test and `section_table` only check the reader against
+// that reading of the format, not against files circom writes.
+#[test]
+fn custom_gates_fixture() {
+    use bellman_ce::pairing::ff;
+    use crate::circom_circuit::r1cs_from_bin_file;
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/testdata/custom_gates.r1cs");
+    let (r1cs, wire_mapping) = r1cs_from_bin_file::<Bn256>(path).unwrap();
+    assert_eq!(r1cs.num_variables, 5);
+    assert_eq!(r1cs.constraints.len(), 1);
+    assert_eq!(wire_mapping, vec![0, 1, 2, 3, 4]);
+    let names = r1cs.custom_gates.iter().map(|gate| gate.template_name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["CMul", "CAddConst"]);
+    assert!(r1cs.custom_gates[0].parameters.is_empty());
+    assert_eq!(r1cs.custom_gates[1].parameters, vec![ff::from_hex("0x07").unwrap()]);
+    assert_eq!(r1cs.custom_gate_uses, vec![
+        CustomGateUse { gate: 0, signals: vec![2, 3, 1] },
+        CustomGateUse { gate: 1, signals: vec![1, 4] },
+    ]);
+
+    // the same uses without the gates section they refer to
+    let data = std::fs::read(path).unwrap();
+    let gates_section = 0x110;
+    let uses_section = 0x157;
+    assert_eq!(&data[gates_section..gates_section + 4], &hex!("04000000"));
+    let mut without_gates = data[..gates_section].to_vec();
+    without_gates.extend_from_slice(&data[uses_section..]);
+    without_gates[8..12].copy_from_slice(&hex!("04000000"));
+    let err = read::<Bn256, _>(Cursor::new(&without_gates[..])).err().unwrap();
+    assert!(err.to_string().contains("unknown gate"), "{}", err);
+}
+
+#[test]
+fn curve_from_prime() {
+    use bellman_ce::pairing::bls12_381::Bls12;
+    assert_eq!(read_curve(Cursor::new(&SAMPLE_R1CS[..])).unwrap(), Curve::Bn254);
+
+    // the sample only uses small coefficients, so it's valid over the BLS12-381 scalar field too
+    let prime_offset = 4 + 4 + 4 + 12 + 4;
+    let mut data = SAMPLE_R1CS.to_vec();
+    data[prime_offset..prime_offset + 32].copy_from_slice(&hex!("01000000fffffffffe5bfeff02a4bd5305d8a10908d83933487d9d2953a7ed73"));
+    assert_eq!(read_curve(Cursor::new(&data[..])).unwrap(), Curve::Bls12_381);
+    let file = read::<Bls12, _>(Cursor::new(&data[..])).unwrap();
+    assert_eq!(file.curve, Curve::Bls12_381);
+    assert_eq!(file.constraints.len(), 3);
+    assert!(read::<Bn256, _>(Cursor::new(&data[..])).is_err());
+
+    data[prime_offset] ^= 1;
+    let err = read_curve(Cursor::new(&data[..])).unwrap_err();
+    assert!(err.to_string().starts_with("Unsupported prime"));
+}
+
+#[test]
+fn parse_errors() {
+    let parse = |data: &[u8]| read::<Bn256, _>(Cursor::new(data.to_vec())).err().unwrap();
+
+    let mut data = SAMPLE_R1CS.to_vec();
+    data[0] = 0;
+    assert!(matches!(parse(&data), ParseError::BadMagic { offset: 0 }));
+
+    let mut data = SAMPLE_R1CS.to_vec();
+    data[4] = 3;
+    assert!(matches!(parse(&data), ParseError::UnsupportedVersion { offset: 4, version: 3 }));
+
+    let mut data = SAMPLE_R1CS.to_vec();
+    data[24] = 48;
+    assert!(matches!(parse(&data), ParseError::FieldSizeMismatch { offset: 24, field_size: 48 }));
+
+    assert!(matches!(parse(&SAMPLE_R1CS[..300]), ParseError::TruncatedSection { offset: 300 }));
+    assert!(matches!(parse(&SAMPLE_R1CS[..6]), ParseError::TruncatedSection { offset: 6 }));
+
+    // a constraint coefficient that isn't below the modulus
+    let mut data = SAMPLE_R1CS.to_vec();
+    data[0x6c..0x8c].copy_from_slice(&[0xff; 32]);
+    let err = parse(&data);
+    assert!(matches!(err, ParseError::Malformed { offset: 0x8c, .. }));
+    assert!(err.to_string().ends_with("at offset 140"));
+}
+
+#[test]
+fn section_sizes_past_end() {
+    let with_constraints_size = |size: u64| {
+        let mut data = SAMPLE_R1CS.to_vec();
+        data[92..100].copy_from_slice(&size.to_le_bytes());
+        data
+    };
+
+    for size in [SAMPLE_R1CS.len() as u64, u64::MAX - 50].iter() {
+        let data = with_constraints_size(*size);
+        let err = read::<Bn256, _>(Cursor::new(&data)).err().unwrap();
+        assert!(matches!(err, ParseError::TruncatedSection { offset: 816 }));
+        let mut constraints = constraints_iter::<Bn256, _>(Cursor::new(&data));
+        assert!(matches!(constraints.next(), Some(Err(ParseError::TruncatedSection { offset: 816 }))));
+        assert!(constraints.next().is_none());
+    }
+}
+
+/// An r1cs file with `n` constraints `w1 * w2 = w1 + i` and one public output
+#[cfg(test)]
+fn medium_r1cs(n: u32) -> Vec<u8> {
+    use byteorder::WriteBytesExt;
+    let field = |x: u64| {
+        let mut bytes = x.to_le_bytes().to_vec();
+        bytes.resize(32, 0);
+        bytes
+    };
+    let mut constraints = vec![];
+    for i in 0..n {
+        let lcs: [&[(u32, u64)]; 3] = [&[(1, 1)], &[(2, 1)], &[(1, 1), (0, i as u64)]];
+        for lc in lcs.iter() {
+            constraints.write_u32::<LittleEndian>(lc.len() as u32).unwrap();
+            for (wire, coeff) in lc.iter() {
+                constraints.write_u32::<LittleEndian>(*wire).unwrap();
+                constraints.extend(field(*coeff));
+            }
+        }
+    }
+
+    let mut data = SAMPLE_R1CS[..0x58].to_vec();
+    data[0x3c..0x40].copy_from_slice(&3u32.to_le_bytes());
+    data[0x54..0x58].copy_from_slice(&n.to_le_bytes());
+    data.write_u32::<LittleEndian>(CONSTRAINT_TYPE).unwrap();
+    data.write_u64::<LittleEndian>(constraints.len() as u64).unwrap();
+    data.extend(constraints);
+    data.write_u32::<LittleEndian>(WIRE2LABEL_TYPE).unwrap();
+    data.write_u64::<LittleEndian>(24).unwrap();
+    for label in 0..3u64 {
+        data.write_u64::<LittleEndian>(label).unwrap();
+    }
+    data
+}
+
+#[test]
+fn streamed_constraints() {
+    let data = medium_r1cs(1000);
+    let header = read_file_header(Cursor::new(&data[..])).unwrap();
+    assert_eq!(header.n_constraints, 1000);
+    let file = read::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    let streamed = constraints_iter::<Bn256, _>(Cursor::new(&data[..]));
+    assert_eq!(streamed.size_hint(), (1000, Some(1000)));
+    let streamed = streamed.collect::<ParseResult<Vec<_>>>().unwrap();
+    assert!(streamed == file.constraints);
+
+    let mut errors = constraints_iter::<Bn256, _>(Cursor::new(&data[..100]));
+    assert!(matches!(errors.next(), Some(Err(ParseError::TruncatedSection { .. }))));
+    assert!(errors.next().is_none());
+
+    // the section is declared one byte longer than its constraints
+    let mut data = medium_r1cs(1000);
+    let size = (&data[0x5c..0x64]).read_u64::<LittleEndian>().unwrap();
+    data[0x5c..0x64].copy_from_slice(&(size + 1).to_le_bytes());
+    data.insert(0x64 + size as usize, 0);
+    let last = constraints_iter::<Bn256, _>(Cursor::new(&data[..])).last().unwrap();
+    assert!(last.unwrap_err().to_string().starts_with("Invalid constraints section size"));
+}
+
+#[test]
+fn lazy_constraints() {
+    let data = medium_r1cs(50);
+    let mut lazy = read_lazy::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    fn check_header<R: Read + Seek>(lazy: &LazyR1cs<Bn256, R>) {
+        assert_eq!(lazy.version(), 1);
+        assert_eq!(lazy.curve(), Curve::Bn254);
+        let header = lazy.header();
+        assert_eq!(
+            (header.n_wires, header.n_pub_out, header.n_pub_in, header.n_prv_in, header.n_labels, header.n_constraints),
+            (3, 1, 2, 3, 1000, 50)
+        );
+    }
+    check_header(&lazy);
+    assert!(lazy.constraints().is_none());
+
+    let file = read::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    assert!(lazy.force_load_constraints().unwrap() == &file.constraints[..]);
+    check_header(&lazy);
+    assert!(lazy.constraints().unwrap() == &file.constraints[..]);
+    // a second call returns what was already read
+    assert_eq!(lazy.force_load_constraints().unwrap().len(), 50);
+    let forced = lazy.into_file().unwrap();
+    assert!(forced.constraints == file.constraints);
+    assert_eq!(forced.wire_mapping, file.wire_mapping);
+
+    // a corrupt constraints section is only noticed when forced
+    let mut data = data;
+    data[0x6c..0x8c].copy_from_slice(&[0xff; 32]);
+    let mut lazy = read_lazy::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    check_header(&lazy);
+    assert!(matches!(lazy.force_load_constraints(), Err(ParseError::Malformed { offset: 0x8c, .. })));
+    assert!(lazy.constraints().is_none());
+}
+
+#[test]
+fn header_only() {
+    use crate::utils::with_temp_files;
+
+    let medium = medium_r1cs(20000);
+    let files = [("sample.r1cs", &SAMPLE_R1CS[..]), ("medium.r1cs", &medium[..])];
+    let (sample, medium, file, path) = with_temp_files("header", &files, |paths| {
+        (
+            read_header_only(&paths[0]).unwrap(),
+            read_header_only(&paths[1]).unwrap(),
+            read::<Bn256, _>(BufReader::new(std::fs::File::open(&paths[1]).unwrap())).unwrap(),
+            paths[0].clone(),
+        )
+    });
+    assert_eq!(
+        (sample.n_wires, sample.n_pub_out, sample.n_pub_in, sample.n_prv_in, sample.n_labels, sample.n_constraints),
+        (7, 1, 2, 3, 1000, 3)
+    );
+    assert_eq!(medium, file.header);
+    assert!(matches!(read_header_only(&path), Err(ParseError::Io { .. })));
+}
+
+#[cfg(all(feature = "mmap", unix))]
+#[test]
+fn mmap_matches_read() {
+    use crate::utils::with_temp_files;
+
+    let data = medium_r1cs(100);
+    let files = [("medium.r1cs", &data[..]), ("empty.r1cs", &b""[..])];
+    let (mapped, empty) = with_temp_files("mmap", &files, |paths| {
+        (read_r1cs_mmap::<Bn256, _>(&paths[0]), read_r1cs_mmap::<Bn256, _>(&paths[1]))
+    });
+
+    let mapped = mapped.unwrap();
+    let file = read::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    assert!(mapped.constraints == file.constraints);
+    assert_eq!(mapped.wire_mapping, file.wire_mapping);
+    assert_eq!(mapped.header.n_constraints, 100);
+    assert!(matches!(empty, Err(ParseError::TruncatedSection { offset: 0 })));
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_matches_sequential() {
+    let data = medium_r1cs(20000);
+    let parallel = read::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    let sequential = constraints_iter::<Bn256, _>(Cursor::new(&data[..])).collect::<ParseResult<Vec<_>>>().unwrap();
+    assert_eq!(parallel.constraints.len(), 20000);
+    assert!(parallel.constraints == sequential);
+
+    // errors still point at the failing coefficient: every constraint has four terms of
+    // 36 bytes and three term counts, the first coefficient follows a count and a wire
+    let mut data = data;
+    let coeff = 0x64 + (4 * 36 + 3 * 4) * 1234 + 8;
+    data[coeff..coeff + 32].copy_from_slice(&[0xff; 32]);
+    assert!(matches!(
+        read::<Bn256, _>(Cursor::new(&data[..])),
+        Err(ParseError::Malformed { offset, .. }) if offset == coeff as u64 + 32
+    ));
+
+    // a header claiming more constraints than the section holds, and a term count running
+    // far past its end, are truncated sections rather than huge allocations
+    let n_constraints = 0x18 + 60;
+    let constraints_end = 0x64 + (4 * 36 + 3 * 4) * 20000;
+    let mut data = medium_r1cs(20000);
+    data[n_constraints..n_constraints + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(
+        read::<Bn256, _>(Cursor::new(&data[..])),
+        Err(ParseError::TruncatedSection { offset }) if offset == constraints_end as u64
+    ));
+    let mut data = medium_r1cs(20000);
+    data[0x64..0x68].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(
+        read::<Bn256, _>(Cursor::new(&data[..])),
+        Err(ParseError::TruncatedSection { offset }) if offset == constraints_end as u64
+    ));
+}
+
+#[test]
+fn section_table() {
+    use crate::circom_circuit::r1cs_from_bin;
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/testdata/custom_gates.r1cs");
+    let mut data = std::fs::read(path).unwrap();
+    let file = read::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    assert_eq!(file.sections.iter().map(|(ty, _, _)| *ty).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    assert_eq!(file.sections[0], (1, 0x18, 64));
+    assert_eq!(file.sections[4], (5, 0x163, 60));
+
+    // an unknown section is skipped but still listed
+    data.extend_from_slice(&hex!("2a000000 0300000000000000 010203"));
+    data[8..12].copy_from_slice(&hex!("06000000"));
+    let (r1cs, _) = r1cs_from_bin::<Bn256, _>(Cursor::new(&data[..])).unwrap();
+    assert_eq!(r1cs.sections.len(), 6);
+    assert_eq!(r1cs.sections[5], (42, 415 + 12, 3));
+    let summary = r1cs.section_summary();
+    assert_eq!(summary.lines().next(), Some("1 (header) at 0x18, 64 bytes"));
+    assert_eq!(summary.lines().last(), Some("42 (unknown) at 0x1ab, 3 bytes"));
+    assert_eq!(section_name(5), Some("custom gates application"));
+}
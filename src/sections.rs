@@ -0,0 +1,71 @@
+//! Section table shared by the binary circom formats (`.r1cs` and `.zkey`): a magic, a
+//! version and a list of (type, size) prefixed sections.
+use byteorder::{ReadBytesExt, LittleEndian};
+use std::{collections::HashMap, io::{Read, Seek, SeekFrom}};
+use crate::parse_error::{AtOffset, OffsetReader, ParseError, ParseResult};
+
+pub(crate) struct Sections {
+    pub version: u32,
+    // section type -> (file offset, size)
+    sections: HashMap<u32, (u64, u64)>,
+    // (type, offset, size) of every section in file order
+    pub table: Vec<(u32, u64, u64)>,
+    // end of the section table, reported for sections that are missing altogether
+    pub table_end: u64,
+}
+
+impl Sections {
+    pub fn contains(&self, sec_type: u32) -> bool {
+        self.sections.contains_key(&sec_type)
+    }
+
+    pub fn get(&self, sec_type: u32) -> ParseResult<(u64, u64)> {
+        self.sections.get(&sec_type).copied()
+            .ok_or_else(|| ParseError::malformed(self.table_end, format!("Missing section {}", sec_type)))
+    }
+}
+
+/// Reads the magic, the version and the offset of each section. Sections of unknown types are
+/// kept in the table for the caller to skip. Every section starts where the previous one ends,
+/// so once each one is checked to end within the file they can't overlap, and nothing is
+/// decoded from a section that runs past the end
+pub(crate) fn read_sections<R: Read + Seek>(
+    reader: &mut OffsetReader<R>,
+    expected_magic: &[u8; 4],
+    versions: &[u32],
+) -> ParseResult<Sections> {
+    let start = reader.offset;
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).at(reader.offset)?;
+    if magic != *expected_magic {
+        return Err(ParseError::BadMagic { offset: start })
+    }
+
+    let version = reader.read_u32::<LittleEndian>().at(reader.offset)?;
+    if !versions.contains(&version) {
+        return Err(ParseError::UnsupportedVersion { offset: start + 4, version })
+    }
+
+    let num_sections = reader.read_u32::<LittleEndian>().at(reader.offset)?;
+
+    let data_start = reader.offset;
+    let data_end = reader.seek(SeekFrom::End(0)).at(data_start)?;
+    reader.seek(SeekFrom::Start(data_start)).at(data_start)?;
+
+    let mut sections = HashMap::new();
+    let mut table = vec![];
+    for _ in 0..num_sections {
+        let sec_type = reader.read_u32::<LittleEndian>().at(reader.offset)?;
+        let sec_size = reader.read_u64::<LittleEndian>().at(reader.offset)?;
+        let offset = reader.offset;
+        let end = match offset.checked_add(sec_size) {
+            Some(end) if end <= data_end => end,
+            _ => return Err(ParseError::TruncatedSection { offset: data_end }),
+        };
+        sections.insert(sec_type, (offset, sec_size));
+        table.push((sec_type, offset, sec_size));
+        reader.seek(SeekFrom::Start(end)).at(offset)?;
+    }
+
+    Ok(Sections { version, sections, table, table_end: reader.offset })
+}
@@ -0,0 +1,125 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use crate::parse_error::{AtOffset, ParseError, ParseResult};
+
+/// Signal names from circom's `.sym` output. A wire can carry several names, e.g. a
+/// component output and the signal it's assigned to; the first one listed is its main name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymTable {
+    names: BTreeMap<usize, String>,
+    wires: HashMap<String, usize>,
+}
+
+impl SymTable {
+    /// Main name of the signal held by `wire`. Wire 0, the constant one, isn't listed in
+    /// `.sym` files.
+    pub fn name_of_wire(&self, wire: usize) -> Option<&str> {
+        self.names.get(&wire).map(String::as_str)
+    }
+
+    /// Wire holding the signal `name`, under any of its names. `None` for signals the
+    /// optimizer removed.
+    pub fn wire_of_name(&self, name: &str) -> Option<usize> {
+        self.wires.get(name).copied()
+    }
+
+    /// Number of wires that have a name
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// Parses `labelIdx,varIdx,componentIdx,name` lines. Signals removed by the optimizer have
+/// varIdx -1 and are skipped, as are anonymous signals with an empty name. If a name is
+/// listed twice, later lines for it are ignored.
+pub fn read<R: BufRead>(mut reader: R) -> ParseResult<SymTable> {
+    let mut names = BTreeMap::new();
+    let mut wires = HashMap::new();
+    let mut offset = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let len = reader.read_line(&mut line).at(offset)?;
+        if len == 0 {
+            break;
+        }
+        let fields = line.trim_end_matches(['\n', '\r']).splitn(4, ',').collect::<Vec<_>>();
+        match fields[..] {
+            [""] => {}
+            [_, "-1", _, _] | [_, _, _, ""] => {}
+            [_, wire, _, name] => {
+                let wire = wire.parse::<usize>().map_err(|_| ParseError::malformed(offset, format!("Invalid wire index {:?}", wire)))?;
+                if !wires.contains_key(name) {
+                    names.entry(wire).or_insert_with(|| name.to_string());
+                    wires.insert(name.to_string(), wire);
+                }
+            }
+            _ => return Err(ParseError::malformed(offset, "Expected labelIdx,varIdx,componentIdx,name")),
+        }
+        offset += len as u64;
+    }
+    Ok(SymTable { names, wires })
+}
+
+pub fn read_sym<P: AsRef<Path>>(path: P) -> ParseResult<SymTable> {
+    let file = File::open(path).at(0)?;
+    read(BufReader::new(file))
+}
+
+#[cfg(test)]
+pub(crate) const SAMPLE_SYM: &str = "1,1,0,main.out\n2,2,0,main.a\n3,3,0,main.b\n";
+
+#[test]
+fn sample() {
+    let sym = read(SAMPLE_SYM.as_bytes()).unwrap();
+    assert_eq!(sym.len(), 3);
+    assert_eq!(sym.name_of_wire(0), None);
+    assert_eq!(sym.name_of_wire(2), Some("main.a"));
+    assert_eq!(sym.name_of_wire(4), None);
+    assert_eq!(sym.wire_of_name("main.b"), Some(3));
+}
+
+#[test]
+fn fixture_lookups() {
+    let sym = read_sym(Path::new(env!("CARGO_MANIFEST_DIR")).join("src/testdata/multiplier.sym")).unwrap();
+    assert_eq!(sym.len(), 4);
+    let wires = [(1, "main.c"), (2, "main.a"), (3, "main.b"), (4, "main.mul.out")];
+    for (wire, name) in wires.iter() {
+        assert_eq!(sym.name_of_wire(*wire), Some(*name));
+        assert_eq!(sym.wire_of_name(name), Some(*wire));
+    }
+    // aliases of main.c and main.a
+    assert_eq!(sym.wire_of_name("main.mul.c"), Some(1));
+    assert_eq!(sym.wire_of_name("main.mul.a"), Some(2));
+    // removed by the optimizer
+    assert_eq!(sym.wire_of_name("main.tmp"), None);
+    assert_eq!(sym.wire_of_name(""), None);
+    assert_eq!(sym.name_of_wire(5), None);
+}
+
+#[test]
+fn duplicates_and_anonymous_signals() {
+    let sym = read(&b"1,1,0,main.out\r\n2,-1,0,main.tmp\r\n3,1,1,main.sub.out\r\n4,2,1,main.sub.in[0]\r\n5,3,1,\r\n6,4,0,main.out\r\n"[..]).unwrap();
+    assert_eq!(sym.len(), 2);
+    assert_eq!(sym.name_of_wire(1), Some("main.out"));
+    assert_eq!(sym.name_of_wire(2), Some("main.sub.in[0]"));
+    assert_eq!(sym.name_of_wire(3), None);
+    assert_eq!(sym.wire_of_name("main.out"), Some(1));
+    assert_eq!(sym.wire_of_name("main.sub.out"), Some(1));
+    assert_eq!(sym.name_of_wire(4), None);
+}
+
+#[test]
+fn parse_errors() {
+    let err = read(&b"1,1,0,main.out\n2,x,0,main.a\n"[..]).unwrap_err();
+    assert!(matches!(err, ParseError::Malformed { offset: 15, .. }));
+    assert_eq!(err.to_string(), "Invalid wire index \"x\" at offset 15");
+    let err = read(&b"1,1,0,main.out\n2,2\n"[..]).unwrap_err();
+    assert!(matches!(err, ParseError::Malformed { offset: 15, .. }));
+}
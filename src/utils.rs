@@ -5,20 +5,39 @@ extern crate num_bigint;
 extern crate num_traits;
 
 use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+use std::sync::Arc;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use itertools::Itertools;
 use num_bigint::BigUint;
 use num_traits::Num;
+use rand::{ChaChaRng, OsRng, Rand, Rng, SeedableRng};
+use crate::circom_circuit::{filter_params, generate_random_parameters, CircomCircuit, R1CS};
+use crate::parse_error::{ParseError, ParseResult};
 use bellman_ce::{
-    groth16::Proof,
+    groth16::{create_proof, prepare_verifying_key, verify_proof, Parameters, Proof, PreparedVerifyingKey, VerifyingKey},
+    SynthesisError,
     pairing::{
-        ff::PrimeField,
+        ff::{Field, PrimeField, PrimeFieldRepr, ScalarEngine},
         CurveAffine,
+        CurveProjective,
+        EncodedPoint,
+        Engine,
         bn256::{
+            Fr,
+            G1,
             G1Affine,
             G2Affine,
+            Fq,
+            FqRepr,
+            Fq2,
+            Fq6,
             Fq12,
             Bn256,
         },
+        bls12_381::Bls12,
     },
 };
 
@@ -30,6 +49,156 @@ pub fn repr_to_hex<T: Display>(r: T) -> String {
     format!("{}", r)[2..].to_string()
 }
 
+pub fn repr_to_biguint<R: PrimeFieldRepr>(r: &R) -> BigUint {
+    let bytes = r.as_ref().iter().flat_map(|limb| limb.to_le_bytes().to_vec()).collect_vec();
+    BigUint::from_bytes_le(&bytes)
+}
+
+/// Modulus of the field `F` as a `BigUint`
+pub fn field_modulus<F: PrimeField>() -> BigUint {
+    repr_to_biguint(&F::char())
+}
+
+/// Modulus of the scalar field of engine `E`, which public inputs and witness values must be
+/// below
+pub fn scalar_modulus<E: ScalarEngine>() -> BigUint {
+    field_modulus::<E::Fr>()
+}
+
+/// Converts a `BigUint` into a field element, returning `None` if it's not below the modulus
+pub fn biguint_to_fr<F: PrimeField>(x: &BigUint) -> Option<F> {
+    let mut repr = F::Repr::default();
+    let digits = x.to_u64_digits();
+    if digits.len() > repr.as_ref().len() {
+        return None;
+    }
+    repr.as_mut()[..digits.len()].copy_from_slice(&digits);
+    F::from_repr(repr).ok()
+}
+
+/// Whether `a == b`, comparing their canonical representations in time that doesn't depend
+/// on where they differ, for checks on private witness values
+pub fn fr_ct_eq<F: PrimeField>(a: &F, b: &F) -> bool {
+    let (a, b) = (a.into_repr(), b.into_repr());
+    a.as_ref().iter().zip(b.as_ref().iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Decimal representation of a field element, with elements above `p / 2`
+/// shown as their negative representative (`x - p`)
+pub fn fr_to_signed_decimal<F: PrimeField>(x: &F) -> String {
+    let value = repr_to_biguint(&x.into_repr());
+    let modulus = field_modulus::<F>();
+    if value > &modulus >> 1 {
+        format!("-{}", modulus - value)
+    } else {
+        value.to_str_radix(10)
+    }
+}
+
+/// Big-endian hex of a field element, `0x` followed by 64 digits
+pub fn fr_to_hex(x: &Fr) -> String {
+    format!("{}", x.into_repr())
+}
+
+/// Decimal representation of a field element in `0..p`
+pub fn fr_to_dec(x: &Fr) -> String {
+    repr_to_biguint(&x.into_repr()).to_str_radix(10)
+}
+
+pub(crate) fn field_from_digits<F: PrimeField>(s: &str, radix: u32) -> ParseResult<F> {
+    // from_str_radix would also take a sign and `_` separators
+    if s.is_empty() || !s.chars().all(|c| c.is_digit(radix)) {
+        return Err(ParseError::malformed(0, format!("{:?} is not a base {} number", s, radix)));
+    }
+    let value = BigUint::from_str_radix(s, radix).map_err(|e| ParseError::malformed(0, e.to_string()))?;
+    biguint_to_fr(&value).ok_or_else(|| ParseError::malformed(0, format!("{} is not below the field modulus", s)))
+}
+
+/// Decodes a field element stored as `field_size` little-endian bytes, the layout of `.r1cs`
+/// and `.wtns` files. Slices of another length and values not below the modulus are
+/// rejected rather than truncated or reduced.
+pub fn fr_from_le_bytes<F: PrimeField>(bytes: &[u8], field_size: usize) -> ParseResult<F> {
+    if bytes.len() != field_size {
+        return Err(ParseError::malformed(0, format!("Expected a {}-byte field element, got {} bytes", field_size, bytes.len())));
+    }
+    let not_in_field = || ParseError::malformed(0, "Field element is not below the modulus");
+    let mut repr = F::Repr::default();
+    let repr_size = repr.as_ref().len() * 8;
+    let (low, high) = bytes.split_at(field_size.min(repr_size));
+    if high.iter().any(|b| *b != 0) {
+        return Err(not_in_field());
+    }
+    repr.read_le(low.chain(io::repeat(0))).map_err(|e| ParseError::malformed(0, e.to_string()))?;
+    F::from_repr(repr).map_err(|_| not_in_field())
+}
+
+/// Parses big-endian hex, with or without a `0x` prefix. Values not below the modulus are
+/// rejected rather than reduced.
+pub fn fr_from_hex(s: &str) -> ParseResult<Fr> {
+    field_from_digits(s.strip_prefix("0x").unwrap_or(s), 16)
+}
+
+/// Parses a decimal number, rejecting values not below the modulus
+pub fn fr_from_dec(s: &str) -> ParseResult<Fr> {
+    field_from_digits(s, 10)
+}
+
+/// Curves whose scalar field primes appear in circom artifacts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    Bn254,
+    Bls12_381,
+}
+
+impl Curve {
+    pub fn scalar_modulus(&self) -> BigUint {
+        match self {
+            Curve::Bn254 => scalar_modulus::<Bn256>(),
+            Curve::Bls12_381 => scalar_modulus::<Bls12>(),
+        }
+    }
+}
+
+impl Display for Curve {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Curve::Bn254 => write!(f, "BN254"),
+            Curve::Bls12_381 => write!(f, "BLS12-381"),
+        }
+    }
+}
+
+/// Returns the curve whose scalar field has modulus `prime`
+pub fn detect_curve(prime: &BigUint) -> Option<Curve> {
+    [Curve::Bn254, Curve::Bls12_381].iter().copied().find(|c| &c.scalar_modulus() == prime)
+}
+
+/// Returns the curve matching the scalar field of engine `E`
+pub fn engine_curve<E: ScalarEngine>() -> Option<Curve> {
+    detect_curve(&scalar_modulus::<E>())
+}
+
+/// Size of the BN254 evaluation domain for `num_constraints` constraints, counting the
+/// `input * 0 = 0` constraint `prepare_prover` adds per input (see
+/// `circom_circuit::effective_constraint_count`). `prepare_prover` fails the same way for
+/// circuits too large for the field's two-adicity, before any FFT or multiexp is done.
+pub fn required_domain_size(num_constraints: usize) -> Result<usize, SynthesisError> {
+    bellman_ce::domain::required_domain_size::<Fr>(num_constraints)
+}
+
+/// ChaCha20 RNG keyed with `seed`, for byte-identical proofs across runs.
+///
+/// For tests and debugging only: anyone who knows the seed can recompute the blinding
+/// factors `r` and `s` of a proof, which removes its zero-knowledge guarantees, and proofs
+/// of the same statement made with the same seed are trivially linkable.
+pub fn create_rng_from_seed(seed: &[u8; 32]) -> ChaChaRng {
+    let mut key = [0u32; 8];
+    for (word, bytes) in key.iter_mut().zip(seed.chunks(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    ChaChaRng::from_seed(&key)
+}
+
 pub fn proof_to_hex(proof: &Proof<Bn256>) -> String {
     let a = proof.a.into_xy_unchecked();
     let b = proof.b.into_xy_unchecked();
@@ -40,6 +209,111 @@ pub fn proof_to_hex(proof: &Proof<Bn256>) -> String {
         .join("")
 }
 
+/// Names of the proof coordinates in the order used by `proof_to_hex` and `proof_to_evm_bytes`
+pub const PROOF_COORDINATES: [&str; 8] = ["a.x", "a.y", "b.x.c1", "b.x.c0", "b.y.c1", "b.y.c0", "c.x", "c.y"];
+
+/// Fixed-size binary encoding in the layout of `proof_to_hex` and the EVM precompiles: the 8
+/// coordinates of `PROOF_COORDINATES`, uncompressed, each as a 32-byte big-endian integer.
+///
+/// | bytes    | coordinate |
+/// |----------|------------|
+/// | 0..32    | a.x        |
+/// | 32..64   | a.y        |
+/// | 64..96   | b.x.c1     |
+/// | 96..128  | b.x.c0     |
+/// | 128..160 | b.y.c1     |
+/// | 160..192 | b.y.c0     |
+/// | 192..224 | c.x        |
+/// | 224..256 | c.y        |
+///
+/// `proof_to_bytes` has a compressed encoding of 128 bytes.
+pub fn proof_to_evm_bytes(proof: &Proof<Bn256>) -> [u8; 256] {
+    let a = proof.a.into_xy_unchecked();
+    let b = proof.b.into_xy_unchecked();
+    let c = proof.c.into_xy_unchecked();
+    let mut bytes = [0u8; 256];
+    for (e, chunk) in [a.0, a.1, b.0.c1, b.0.c0, b.1.c1, b.1.c0, c.0, c.1].iter().zip(bytes.chunks_mut(32)) {
+        e.into_repr().write_be(chunk).unwrap();
+    }
+    bytes
+}
+
+/// Decodes the output of `proof_to_evm_bytes`, checking that every coordinate is below the
+/// modulus and every point is on the curve and in the prime-order subgroup. Like `read_proof`,
+/// points at infinity (all-zero coordinates) are rejected. Errors report the offset of the
+/// offending coordinate or point.
+pub fn proof_from_evm_bytes(bytes: &[u8]) -> ParseResult<Proof<Bn256>> {
+    if bytes.len() != 256 {
+        return Err(ParseError::malformed(0, format!("Expected a 256-byte proof, got {} bytes", bytes.len())));
+    }
+    let coordinate = |i: usize| -> ParseResult<Fq> {
+        let mut repr = FqRepr::default();
+        repr.read_be(&bytes[i * 32..(i + 1) * 32]).map_err(|e| ParseError::from_io(e, i as u64 * 32))?;
+        Fq::from_repr(repr).map_err(|_| ParseError::malformed(i as u64 * 32, format!("{} is not below the modulus", PROOF_COORDINATES[i])))
+    };
+    fn point<G: CurveAffine>(x: G::Base, y: G::Base, offset: u64, name: &str) -> ParseResult<G> {
+        let p = G::from_xy_checked(x, y)
+            .and_then(|p| p.into_uncompressed().into_affine())
+            .map_err(|e| ParseError::malformed(offset, format!("{}: {}", name, e)))?;
+        if p.is_zero() {
+            return Err(ParseError::malformed(offset, format!("{} is the point at infinity", name)));
+        }
+        check_point(p, name, offset)?;
+        Ok(p)
+    }
+    let fq2 = |c1: usize, c0: usize| -> ParseResult<Fq2> { Ok(Fq2 { c0: coordinate(c0)?, c1: coordinate(c1)? }) };
+    Ok(Proof {
+        a: point(coordinate(0)?, coordinate(1)?, 0, "a")?,
+        b: point(fq2(2, 3)?, fq2(4, 5)?, 64, "b")?,
+        c: point(coordinate(6)?, coordinate(7)?, 192, "c")?,
+    })
+}
+
+/// Compressed fixed-size binary encoding: each point as its x coordinate, a 32-byte big-endian
+/// integer per `Fq`, with two flags in the top bits of its first byte. Bit 7 is set when y is
+/// the lexicographically larger of y and -y, bit 6 marks the point at infinity.
+///
+/// | bytes   | contents              |
+/// |---------|-----------------------|
+/// | 0..32   | a.x and a's flags     |
+/// | 32..64  | b.x.c1 and b's flags  |
+/// | 64..96  | b.x.c0                |
+/// | 96..128 | c.x and c's flags     |
+///
+/// `proof_to_evm_bytes` has the uncompressed 256-byte layout of the EVM precompiles.
+pub fn proof_to_bytes(proof: &Proof<Bn256>) -> [u8; 128] {
+    let mut bytes = [0u8; 128];
+    bytes[..32].copy_from_slice(proof.a.into_compressed().as_ref());
+    bytes[32..96].copy_from_slice(proof.b.into_compressed().as_ref());
+    bytes[96..].copy_from_slice(proof.c.into_compressed().as_ref());
+    bytes
+}
+
+/// Decodes the output of `proof_to_bytes`, checking that every x coordinate is below the
+/// modulus and every point is in the prime-order subgroup. Points at infinity are rejected as
+/// by `proof_from_evm_bytes`. Errors report the offset of the offending point.
+pub fn proof_from_bytes(bytes: &[u8]) -> ParseResult<Proof<Bn256>> {
+    if bytes.len() != 128 {
+        return Err(ParseError::malformed(0, format!("Expected a 128-byte proof, got {} bytes", bytes.len())));
+    }
+    fn point<G: CurveAffine>(bytes: &[u8], offset: usize, name: &str) -> ParseResult<G> {
+        let mut encoded = G::Compressed::empty();
+        let len = encoded.as_ref().len();
+        encoded.as_mut().copy_from_slice(&bytes[offset..offset + len]);
+        let p = encoded.into_affine().map_err(|e| ParseError::malformed(offset as u64, format!("{}: {}", name, e)))?;
+        if p.is_zero() {
+            return Err(ParseError::malformed(offset as u64, format!("{} is the point at infinity", name)));
+        }
+        check_point(p, name, offset as u64)?;
+        Ok(p)
+    }
+    Ok(Proof {
+        a: point(bytes, 0, "a")?,
+        b: point(bytes, 32, "b")?,
+        c: point(bytes, 96, "c")?,
+    })
+}
+
 pub fn p1_to_vec(p: &G1Affine) -> Vec<String> {
     let xy = p.into_xy_unchecked();
     vec![
@@ -68,6 +342,341 @@ pub fn p2_to_vec(p: &G2Affine) -> Vec<Vec<String>> {
     ]
 }
 
+/// Transaction data calling `verifyProof(a, b, c, input)` on a contract from
+/// `export_solidity_verifier`: the function selector followed by the ABI encoding of the
+/// static arrays, i.e. `proof_to_evm_bytes` (G2 coordinates as `[c1, c0]`, as the precompile
+/// expects) and one big-endian word per public input.
+pub fn proof_to_eth_calldata(proof: &Proof<Bn256>, public_inputs: &[Fr]) -> String {
+    let signature = format!("verifyProof(uint256[2],uint256[2][2],uint256[2],uint256[{}])", public_inputs.len());
+    let mut calldata = tiny_keccak::keccak256(signature.as_bytes())[..4].to_vec();
+    calldata.extend(proof_to_evm_bytes(proof).iter());
+    for input in public_inputs {
+        input.into_repr().write_be(&mut calldata).unwrap();
+    }
+    format!("0x{}", calldata.iter().map(|b| format!("{:02x}", b)).join(""))
+}
+
+/// Proof in the `proof.json` layout written by `snarkjs groth16 prove`: projective-style
+/// `[x, y, "1"]` G1 points, G2 coordinates as `[c0, c1]` pairs and decimal strings throughout.
+pub fn proof_to_snarkjs_json(proof: &Proof<Bn256>) -> serde_json::Value {
+    serde_json::json!({
+        "pi_a": p1_to_vec(&proof.a),
+        "pi_b": p2_to_vec(&proof.b),
+        "pi_c": p1_to_vec(&proof.c),
+        "protocol": "groth16",
+        "curve": "bn128",
+    })
+}
+
+/// Verifying key in the `verification_key.json` layout written by `snarkjs zkey export
+/// verificationkey`, with the same point encoding as `proof_to_snarkjs_json`. `nPublic` is the
+/// number of IC points minus the one for the constant input.
+pub fn vk_to_snarkjs_json(vk: &VerifyingKey<Bn256>) -> serde_json::Value {
+    serde_json::json!({
+        "protocol": "groth16",
+        "curve": "bn128",
+        "nPublic": vk.ic.len().saturating_sub(1),
+        "vk_alpha_1": p1_to_vec(&vk.alpha_g1),
+        "vk_beta_2": p2_to_vec(&vk.beta_g2),
+        "vk_gamma_2": p2_to_vec(&vk.gamma_g2),
+        "vk_delta_2": p2_to_vec(&vk.delta_g2),
+        "IC": vk.ic.iter().map(p1_to_vec).collect_vec(),
+    })
+}
+
+/// Dumps `r1cs` in the layout of `snarkjs r1cs export json`, with every coefficient as a full
+/// decimal string. `R1CS` doesn't tell public outputs from public inputs, so all public
+/// signals are counted in `nPubInputs` and `nOutputs` is 0; `nPrvInputs` and `nLabels` aren't
+/// known and are left out. The result can be read back with `r1cs_from_json`.
+pub fn r1cs_to_json<E: Engine>(r1cs: &R1CS<E>) -> serde_json::Value {
+    r1cs_to_json_with_signs(r1cs, false)
+}
+
+/// Same as `r1cs_to_json`, with `signed` rendering coefficients above `p / 2` as their
+/// negative representative like `fr_to_signed_decimal`, so `p - 1` shows as `-1`. Meant for
+/// reading, neither snarkjs nor `r1cs_from_json` accept negative coefficients.
+pub fn r1cs_to_json_with_signs<E: Engine>(r1cs: &R1CS<E>, signed: bool) -> serde_json::Value {
+    let decimal = |x: &E::Fr| repr_to_biguint(&x.into_repr()).to_str_radix(10);
+    let coeff_decimal = |x: &E::Fr| if signed { fr_to_signed_decimal(x) } else { decimal(x) };
+    let lc_json = |lc: &[(usize, E::Fr)]| {
+        lc.iter().map(|(index, coeff)| (index.to_string(), coeff_decimal(coeff))).collect::<std::collections::BTreeMap<_, _>>()
+    };
+    let constraints = r1cs.constraints.iter()
+        .map(|constraint| serde_json::json!([lc_json(&constraint.a), lc_json(&constraint.b), lc_json(&constraint.c)]))
+        .collect_vec();
+    let mut json = serde_json::json!({
+        "n8": (E::Fr::NUM_BITS as usize).div_ceil(64) * 8,
+        "prime": scalar_modulus::<E>().to_str_radix(10),
+        "nVars": r1cs.num_variables,
+        "nOutputs": 0,
+        "nPubInputs": r1cs.num_inputs - 1,
+        "nConstraints": r1cs.constraints.len(),
+        "useCustomGates": !r1cs.custom_gates.is_empty(),
+        "constraints": constraints,
+        "map": r1cs.wire_to_label,
+    });
+    if !r1cs.custom_gates.is_empty() {
+        json["customGates"] = r1cs.custom_gates.iter()
+            .map(|gate| serde_json::json!({
+                "templateName": gate.template_name,
+                "parameters": gate.parameters.iter().map(decimal).collect_vec(),
+            }))
+            .collect();
+        json["customGatesUses"] = r1cs.custom_gate_uses.iter()
+            .map(|gate_use| serde_json::json!({ "id": gate_use.gate, "signals": gate_use.signals }))
+            .collect();
+    }
+    json
+}
+
+fn json_fq(value: &serde_json::Value, path: &str) -> ParseResult<Fq> {
+    let s = value.as_str().ok_or_else(|| ParseError::malformed(0, format!("{} is not a string", path)))?;
+    BigUint::from_str_radix(s, 10)
+        .ok()
+        .and_then(|x| biguint_to_fr::<Fq>(&x))
+        .ok_or_else(|| ParseError::malformed(0, format!("{} is not a valid field element", path)))
+}
+
+// Builds the point from affine coordinates, checking that it is on the curve. Subgroup
+// membership is left to `validate_proof_points`
+fn json_point<G: CurveAffine>(x: G::Base, y: G::Base, z: &serde_json::Value, path: &str) -> ParseResult<G> {
+    if *z != serde_json::json!("1") && *z != serde_json::json!(["1", "0"]) {
+        return Err(ParseError::malformed(0, format!("{} is not a finite affine point", path)));
+    }
+    G::from_xy_checked(x, y)
+        .and_then(|p| p.into_uncompressed().into_affine())
+        .map_err(|e| ParseError::malformed(0, format!("{}: {}", path, e)))
+}
+
+fn json_g1(p: &serde_json::Value, path: &str) -> ParseResult<G1Affine> {
+    let x = json_fq(&p[0], &format!("{}[0]", path))?;
+    let y = json_fq(&p[1], &format!("{}[1]", path))?;
+    json_point(x, y, &p[2], path)
+}
+
+// snarkjs stores each G2 coordinate as [c0, c1], the reverse of the EVM precompile order
+fn json_g2(p: &serde_json::Value, path: &str) -> ParseResult<G2Affine> {
+    let fq2 = |i: usize| -> ParseResult<Fq2> {
+        Ok(Fq2 {
+            c0: json_fq(&p[i][0], &format!("{}[{}][0]", path, i))?,
+            c1: json_fq(&p[i][1], &format!("{}[{}][1]", path, i))?,
+        })
+    };
+    json_point(fq2(0)?, fq2(1)?, &p[2], path)
+}
+
+/// Reads a proof in the snarkjs `proof.json` layout produced by `proof_to_snarkjs_json`,
+/// checking that every point is on the curve and in the right subgroup. JSON has no
+/// meaningful byte offsets, so errors report offset 0 and name the offending field instead.
+pub fn proof_from_snarkjs_json(value: &serde_json::Value) -> ParseResult<Proof<Bn256>> {
+    let proof = Proof {
+        a: json_g1(&value["pi_a"], "pi_a")?,
+        b: json_g2(&value["pi_b"], "pi_b")?,
+        c: json_g1(&value["pi_c"], "pi_c")?,
+    };
+    validate_proof_points(&proof)?;
+    Ok(proof)
+}
+
+/// Checks that the points of `proof` are on their curves and in the prime-order subgroup,
+/// which points from an untrusted source or built with `from_xy_unchecked` needn't be.
+/// Decoding a point only checks that it's on the curve. That is enough in G1, whose cofactor
+/// is 1 on BN254, but not in G2, so every point is also multiplied by the group order.
+pub fn validate_proof_points(proof: &Proof<Bn256>) -> ParseResult<()> {
+    check_point(proof.a, "pi_a", 0)?;
+    check_point(proof.b, "pi_b", 0)?;
+    check_point(proof.c, "pi_c", 0)
+}
+
+fn check_point<G: CurveAffine>(p: G, name: &str, offset: u64) -> ParseResult<()> {
+    let (x, y) = p.into_xy_unchecked();
+    if G::from_xy_checked(x, y).is_err() {
+        return Err(ParseError::malformed(offset, format!("{} is not on the curve", name)));
+    }
+    if !in_prime_order_subgroup(&p) {
+        return Err(ParseError::malformed(offset, format!("{} is not in the prime-order subgroup", name)));
+    }
+    Ok(())
+}
+
+pub(crate) fn in_prime_order_subgroup<G: CurveAffine>(p: &G) -> bool {
+    p.mul(G::Scalar::char()).is_zero()
+}
+
+// The binary decoders only check that points are on the curve
+fn check_g2_subgroup<'a, I: IntoIterator<Item = &'a G2Affine>>(points: I) -> io::Result<()> {
+    if points.into_iter().all(in_prime_order_subgroup) {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidData, "G2 point is not in the prime-order subgroup"))
+    }
+}
+
+// `verification_key.json` has no beta_1 and delta_1, which verification doesn't need; they
+// are left at zero. Reads the output of `vk_to_snarkjs_json` as well as `verification_key_json`
+fn vk_from_snarkjs_json(value: &serde_json::Value) -> ParseResult<VerifyingKey<Bn256>> {
+    let ic = value["IC"].as_array().ok_or_else(|| ParseError::malformed(0, "IC is not an array"))?;
+    if ic.is_empty() {
+        return Err(ParseError::malformed(0, "IC is empty"));
+    }
+    Ok(VerifyingKey {
+        alpha_g1: json_g1(&value["vk_alpha_1"], "vk_alpha_1")?,
+        beta_g1: G1Affine::zero(),
+        beta_g2: json_g2(&value["vk_beta_2"], "vk_beta_2")?,
+        gamma_g2: json_g2(&value["vk_gamma_2"], "vk_gamma_2")?,
+        delta_g1: G1Affine::zero(),
+        delta_g2: json_g2(&value["vk_delta_2"], "vk_delta_2")?,
+        ic: ic.iter().enumerate().map(|(i, p)| json_g1(p, &format!("IC[{}]", i))).collect::<ParseResult<_>>()?,
+    })
+}
+
+fn read_json_file<P: AsRef<Path>>(path: P) -> ParseResult<serde_json::Value> {
+    let file = File::open(&path).map_err(|e| ParseError::Io { offset: 0, error: e })?;
+    serde_json::from_reader(io::BufReader::new(file))
+        .map_err(|e| ParseError::malformed(0, format!("{}: {}", path.as_ref().display(), e)))
+}
+
+/// Checks the proof in `proof_path` (snarkjs `proof.json` layout) against the verifying key in
+/// `vk_path` (snarkjs `verification_key.json` layout, as written by `verification_key_json`).
+/// A `public_inputs` slice of the wrong length gives `ParseError::WrongInputCount`.
+pub fn verify_proof_from_files<P: AsRef<Path>, Q: AsRef<Path>>(vk_path: P, proof_path: Q, public_inputs: &[Fr]) -> ParseResult<bool> {
+    let vk = vk_from_snarkjs_json(&read_json_file(vk_path)?)?;
+    let proof = proof_from_snarkjs_json(&read_json_file(proof_path)?)?;
+    verify_parsed(&vk, &proof, public_inputs)
+}
+
+fn verify_parsed(vk: &VerifyingKey<Bn256>, proof: &Proof<Bn256>, public_inputs: &[Fr]) -> ParseResult<bool> {
+    if vk.ic.len() != public_inputs.len() + 1 {
+        return Err(ParseError::WrongInputCount { offset: 0, expected: vk.ic.len().saturating_sub(1), found: public_inputs.len() });
+    }
+    verify_proof(&prepare_verifying_key(vk), proof, public_inputs)
+        .map_err(|e| ParseError::malformed(0, e.to_string()))
+}
+
+/// Verifies artifacts produced entirely by snarkjs: `vk_json` as written by `snarkjs zkey
+/// export verificationkey`, `proof_json` and `public_json` as written by `snarkjs groth16
+/// prove`. Keys for another protocol or curve are rejected, as are public inputs whose count
+/// doesn't match `nPublic`.
+pub fn verify_with_snarkjs_vk(vk_json: &serde_json::Value, proof_json: &serde_json::Value, public_json: &serde_json::Value) -> ParseResult<bool> {
+    if vk_json["protocol"] != "groth16" {
+        return Err(ParseError::malformed(0, format!("protocol is {}, expected \"groth16\"", vk_json["protocol"])));
+    }
+    if vk_json["curve"] != "bn128" {
+        return Err(ParseError::malformed(0, format!("curve is {}, expected \"bn128\"", vk_json["curve"])));
+    }
+    let vk = vk_from_snarkjs_json(vk_json)?;
+    if vk_json["nPublic"] != vk.ic.len() - 1 {
+        return Err(ParseError::malformed(0, format!("nPublic is {}, but IC has {} points", vk_json["nPublic"], vk.ic.len())));
+    }
+    let proof = proof_from_snarkjs_json(proof_json)?;
+    let public_inputs = json_public(public_json, "public")?;
+    verify_parsed(&vk, &proof, &public_inputs)
+}
+
+// Public inputs as a JSON array of decimal strings, errors name entries as `path[i]`
+fn json_public(value: &serde_json::Value, path: &str) -> ParseResult<Vec<Fr>> {
+    let entries = value.as_array().ok_or_else(|| ParseError::malformed(0, format!("{} is not an array", path)))?;
+    entries.iter().enumerate().map(|(i, entry)| {
+        let s = entry.as_str().ok_or_else(|| ParseError::malformed(0, format!("{}[{}] is not a string", path, i)))?;
+        fr_from_dec(s).map_err(|e| match e {
+            ParseError::Malformed { reason, .. } => ParseError::malformed(0, format!("{}[{}]: {}", path, i, reason)),
+            e => e,
+        })
+    }).collect()
+}
+
+/// Reads the snarkjs `public.json` array of decimal strings, keeping the order given. Entries
+/// not below the modulus are rejected rather than reduced.
+pub fn read_public_json(reader: &mut impl Read) -> ParseResult<Vec<Fr>> {
+    let value: serde_json::Value = serde_json::from_reader(reader).map_err(|e| ParseError::malformed(0, e.to_string()))?;
+    if !value.is_array() {
+        return Err(ParseError::malformed(0, "public inputs are not an array"))
+    }
+    json_public(&value, "public")
+}
+
+/// Encodes proofs with their public inputs as an array of `{"proof", "public"}` objects, in
+/// the layouts of snarkjs's `proof.json` and `public.json`, e.g. to store a batch for
+/// `batch_verify`
+pub fn proofs_to_json(proofs: &[(Proof<Bn256>, Vec<Fr>)]) -> serde_json::Value {
+    proofs.iter().map(|(proof, public)| serde_json::json!({
+        "proof": proof_to_snarkjs_json(proof),
+        "public": public.iter().map(fr_to_dec).collect::<Vec<_>>(),
+    })).collect()
+}
+
+/// Reads the output of `proofs_to_json`, with the same checks as `proof_from_snarkjs_json`
+/// and `read_public_json`. Errors name the offending entry, e.g. `[2].proof: pi_a: ...`.
+pub fn proofs_from_json(value: &serde_json::Value) -> ParseResult<Vec<(Proof<Bn256>, Vec<Fr>)>> {
+    let entries = value.as_array().ok_or_else(|| ParseError::malformed(0, "proofs are not an array"))?;
+    entries.iter().enumerate().map(|(i, entry)| {
+        let proof = proof_from_snarkjs_json(&entry["proof"]).map_err(|e| match e {
+            ParseError::Malformed { reason, .. } => ParseError::malformed(0, format!("[{}].proof: {}", i, reason)),
+            e => e,
+        })?;
+        Ok((proof, json_public(&entry["public"], &format!("[{}].public", i))?))
+    }).collect()
+}
+
+/// `verify_proof_from_files` with the public inputs read from a snarkjs `public.json`
+pub fn verify_from_snarkjs_inputs<P: AsRef<Path>, Q: AsRef<Path>, S: AsRef<Path>>(vk_path: P, proof_path: Q, public_path: S) -> ParseResult<bool> {
+    let file = File::open(public_path).map_err(|e| ParseError::Io { offset: 0, error: e })?;
+    let public_inputs = read_public_json(&mut io::BufReader::new(file))?;
+    verify_proof_from_files(vk_path, proof_path, &public_inputs)
+}
+
+/// Why `create_proof_with_blinding_hex` failed
+#[derive(Debug)]
+pub enum BlindingError {
+    /// `r` or `s` isn't a valid hex field element, the reason names which one
+    Scalar(ParseError),
+    /// Proving failed, e.g. the circuit has no witness
+    Synthesis(SynthesisError),
+}
+
+impl From<SynthesisError> for BlindingError {
+    fn from(e: SynthesisError) -> Self {
+        BlindingError::Synthesis(e)
+    }
+}
+
+impl Display for BlindingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BlindingError::Scalar(e) => e.fmt(f),
+            BlindingError::Synthesis(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for BlindingError {}
+
+/// Proves `circuit` with the blinding factors `r` and `s` pinned to the given hex values (as
+/// accepted by `fr_from_hex`) instead of drawn at random, to reproduce test vectors of other
+/// Groth16 implementations.
+pub fn create_proof_with_blinding_hex(circuit: CircomCircuit<Bn256>, params: &Parameters<Bn256>, r_hex: &str, s_hex: &str) -> Result<Proof<Bn256>, BlindingError> {
+    let scalar = |name: &str, hex: &str| fr_from_hex(hex).map_err(|e| BlindingError::Scalar(match e {
+        ParseError::Malformed { reason, .. } => ParseError::malformed(0, format!("{}: {}", name, reason)),
+        e => e,
+    }));
+    let r = scalar("r", r_hex)?;
+    let s = scalar("s", s_hex)?;
+    if circuit.witness.is_none() {
+        return Err(SynthesisError::AssignmentMissing.into());
+    }
+    let mut params = params.clone();
+    filter_params(&mut params);
+    Ok(create_proof(circuit, &params, r, s)?)
+}
+
+/// Generates Groth16 parameters for `circuit` with randomness from `rng`, via
+/// `circom_circuit::generate_random_parameters`. The circuit is synthesized in setup mode, so
+/// its witness, if any, is ignored.
+pub fn generate_params<R: Rng>(circuit: CircomCircuit<Bn256>, rng: &mut R) -> Result<Parameters<Bn256>, SynthesisError> {
+    let setup = CircomCircuit { witness: None, ..circuit };
+    generate_random_parameters(setup, rng)
+}
+
 pub fn pairing_to_vec(p: &Fq12) -> Vec<Vec<Vec<String>>> {
     vec![
         vec![
@@ -100,3 +709,1251 @@ pub fn pairing_to_vec(p: &Fq12) -> Vec<Vec<Vec<String>>> {
         ],
     ]
 }
+
+
+fn write_fq<W: Write>(mut writer: W, fq: &Fq) -> io::Result<()> {
+    fq.into_repr().write_be(&mut writer)
+}
+
+fn read_fq<R: Read>(mut reader: R) -> io::Result<Fq> {
+    let mut repr = FqRepr::default();
+    repr.read_be(&mut reader)?;
+    Fq::from_repr(repr).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_fq2<R: Read>(mut reader: R) -> io::Result<Fq2> {
+    Ok(Fq2 { c0: read_fq(&mut reader)?, c1: read_fq(&mut reader)? })
+}
+
+fn read_fq6<R: Read>(mut reader: R) -> io::Result<Fq6> {
+    Ok(Fq6 {
+        c0: read_fq2(&mut reader)?,
+        c1: read_fq2(&mut reader)?,
+        c2: read_fq2(&mut reader)?,
+    })
+}
+
+/// Serializes a prepared verifying key including the precomputed `e(alpha, beta)`
+/// pairing, so that loading it doesn't require evaluating any pairings.
+///
+/// Layout: 12 big-endian `Fq` coefficients of `e(alpha, beta)`, uncompressed
+/// `gamma_g2` and `delta_g2`, a big-endian `u32` IC length and uncompressed IC points.
+pub fn write_prepared_vk<W: Write>(pvk: &PreparedVerifyingKey<Bn256>, mut writer: W) -> io::Result<()> {
+    let gt = pvk.alpha_g1_beta_g2();
+    for fq2 in [gt.c0.c0, gt.c0.c1, gt.c0.c2, gt.c1.c0, gt.c1.c1, gt.c1.c2].iter() {
+        write_fq(&mut writer, &fq2.c0)?;
+        write_fq(&mut writer, &fq2.c1)?;
+    }
+    writer.write_all(pvk.gamma_g2().into_uncompressed().as_ref())?;
+    writer.write_all(pvk.delta_g2().into_uncompressed().as_ref())?;
+    writer.write_u32::<BigEndian>(pvk.ic().len() as u32)?;
+    for ic in pvk.ic() {
+        writer.write_all(ic.into_uncompressed().as_ref())?;
+    }
+    Ok(())
+}
+
+/// Reads a prepared verifying key written by `write_prepared_vk`. The `e(alpha, beta)`
+/// element is checked to lie in the order-r subgroup of the target group, which
+/// rejects corrupted keys without recomputing the pairing. `gamma` and `delta` are checked
+/// to be in the prime-order subgroup of G2, like the points `read_params` reads.
+pub fn read_prepared_vk<R: Read>(mut reader: R) -> io::Result<PreparedVerifyingKey<Bn256>> {
+    let gt = Fq12 { c0: read_fq6(&mut reader)?, c1: read_fq6(&mut reader)? };
+    if gt.is_zero() || gt.pow(<Bn256 as ScalarEngine>::Fr::char()) != Fq12::one() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid alpha*beta pairing element"));
+    }
+
+    let mut g2_repr = <G2Affine as CurveAffine>::Uncompressed::empty();
+    reader.read_exact(g2_repr.as_mut())?;
+    let gamma_g2 = g2_repr.into_affine().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    reader.read_exact(g2_repr.as_mut())?;
+    let delta_g2 = g2_repr.into_affine().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    check_g2_subgroup(&[gamma_g2, delta_g2])?;
+
+    let ic_len = reader.read_u32::<BigEndian>()? as usize;
+    // there is always the IC point of the constant input
+    if ic_len == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Empty IC"));
+    }
+    let mut ic = Vec::with_capacity(ic_len.min(MAX_PREALLOCATED_POINTS));
+    let mut g1_repr = <G1Affine as CurveAffine>::Uncompressed::empty();
+    for _ in 0..ic_len {
+        reader.read_exact(g1_repr.as_mut())?;
+        ic.push(g1_repr.into_affine().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?);
+    }
+
+    Ok(PreparedVerifyingKey::from_parts(gt, gamma_g2, delta_g2, ic))
+}
+
+fn sol_g1(p: &G1Affine) -> io::Result<String> {
+    if p.is_zero() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "verifying key contains the point at infinity"));
+    }
+    let xy = p.into_xy_unchecked();
+    Ok(format!("{},\n            {}", repr_to_big(xy.0.into_repr()), repr_to_big(xy.1.into_repr())))
+}
+
+// The EVM pairing precompile expects the imaginary coefficient of each G2 coordinate first
+fn sol_g2(p: &G2Affine) -> io::Result<String> {
+    if p.is_zero() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "verifying key contains the point at infinity"));
+    }
+    let xy = p.into_xy_unchecked();
+    Ok(format!(
+        "[{},\n             {}],\n            [{},\n             {}]",
+        repr_to_big(xy.0.c1.into_repr()),
+        repr_to_big(xy.0.c0.into_repr()),
+        repr_to_big(xy.1.c1.into_repr()),
+        repr_to_big(xy.1.c0.into_repr()),
+    ))
+}
+
+/// Writes a Solidity Groth16 verifier for `vk` based on the snarkjs template, so the contract
+/// exposes the same `verifyProof(a, b, c, input)` ABI as one generated by `snarkjs zkey export
+/// solidityverifier`. `pragma` is the compiler version requirement, e.g. `^0.6.11`.
+pub fn export_solidity_verifier<W: Write>(vk: &VerifyingKey<Bn256>, pragma: &str, writer: &mut W) -> io::Result<()> {
+    let template = include_str!("verifier_snarkjs.sol");
+    if vk.ic.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "verifying key has no IC points"));
+    }
+    let mut ic_points = vec![];
+    for (i, p) in vk.ic.iter().enumerate() {
+        ic_points.push(format!("vk.IC[{}] = Pairing.G1Point(\n            {}\n        );\n", i, sol_g1(p)?));
+    }
+    let contract = template
+        .replace("<%pragma%>", pragma)
+        .replace("<%vk_alpha1%>", &sol_g1(&vk.alpha_g1)?)
+        .replace("<%vk_beta2%>", &sol_g2(&vk.beta_g2)?)
+        .replace("<%vk_gamma2%>", &sol_g2(&vk.gamma_g2)?)
+        .replace("<%vk_delta2%>", &sol_g2(&vk.delta_g2)?)
+        .replace("<%vk_ic_length%>", &vk.ic.len().to_string())
+        .replace("<%vk_input_length%>", &(vk.ic.len() - 1).to_string())
+        .replace("<%vk_ic_pts%>", &ic_points.join("        "));
+    writer.write_all(contract.as_bytes())
+}
+
+/// Verifying key prepared once, for checking many proofs against it without redoing the
+/// `gamma_g2`/`delta_g2` negations and line precomputation each time. The prepared key is
+/// shared behind an `Arc`, so clones are cheap and can be handed to other threads.
+#[derive(Clone)]
+pub struct PreparedVk(Arc<PreparedVerifyingKey<Bn256>>);
+
+impl PreparedVk {
+    pub fn new(vk: &VerifyingKey<Bn256>) -> Self {
+        PreparedVk(Arc::new(prepare_verifying_key(vk)))
+    }
+
+    pub fn verify(&self, proof: &Proof<Bn256>, public_inputs: &[Fr]) -> Result<bool, SynthesisError> {
+        verify_proof(&self.0, proof, public_inputs)
+    }
+
+    pub fn prepared(&self) -> &PreparedVerifyingKey<Bn256> {
+        &self.0
+    }
+}
+
+impl From<PreparedVerifyingKey<Bn256>> for PreparedVk {
+    fn from(pvk: PreparedVerifyingKey<Bn256>) -> Self {
+        PreparedVk(Arc::new(pvk))
+    }
+}
+
+/// The public input term of the verification equation, `IC[0] + sum(public_inputs[i] * IC[i + 1])`,
+/// which is paired with gamma. `verify_proof` computes the same point internally.
+pub fn compute_vk_x(vk: &VerifyingKey<Bn256>, public_inputs: &[Fr]) -> Result<G1Affine, SynthesisError> {
+    if public_inputs.len() + 1 != vk.ic.len() {
+        return Err(SynthesisError::InputCountMismatch { expected: vk.ic.len().saturating_sub(1), got: public_inputs.len() });
+    }
+    let mut acc = vk.ic[0].into_projective();
+    for (input, base) in public_inputs.iter().zip(vk.ic.iter().skip(1)) {
+        acc.add_assign(&base.mul(input.into_repr()));
+    }
+    Ok(acc.into_affine())
+}
+
+/// Verifies many proofs against one key with a single multi-pairing.
+///
+/// Each proof equation is scaled by a fresh random scalar before they are summed, so invalid
+/// proofs can't be crafted to cancel each other out. The batch is accepted only if every
+/// proof would pass `verify_proof`, except with negligible probability.
+pub fn batch_verify(vk: &PreparedVerifyingKey<Bn256>, items: &[(Proof<Bn256>, Vec<Fr>)]) -> Result<bool, SynthesisError> {
+    // IC[0] is the point of the constant input, a key without it can't be valid
+    if vk.ic().is_empty() {
+        return Err(SynthesisError::MalformedVerifyingKey);
+    }
+    let mut rng = OsRng::new()?;
+    // Coefficients of the IC points in the combined input term, IC[0] gets the sum of all scalars
+    let mut ic_coeffs = vec![Fr::zero(); vk.ic().len()];
+    let mut c_acc = G1::zero();
+    let mut ab_pairs = Vec::with_capacity(items.len());
+    for (proof, inputs) in items {
+        if inputs.len() + 1 != vk.ic().len() {
+            return Err(SynthesisError::InputCountMismatch { expected: vk.ic().len() - 1, got: inputs.len() });
+        }
+        let r = Fr::rand(&mut rng);
+        ic_coeffs[0].add_assign(&r);
+        for (coeff, input) in ic_coeffs.iter_mut().skip(1).zip(inputs) {
+            let mut term = *input;
+            term.mul_assign(&r);
+            coeff.add_assign(&term);
+        }
+        c_acc.add_assign(&proof.c.mul(r.into_repr()));
+        ab_pairs.push((proof.a.mul(r.into_repr()).into_affine().prepare(), proof.b.prepare()));
+    }
+
+    let mut ic_acc = G1::zero();
+    for (coeff, base) in ic_coeffs.iter().zip(vk.ic()) {
+        ic_acc.add_assign(&base.mul(coeff.into_repr()));
+    }
+
+    // sum(r * A * B) - sum(r * inputs) * gamma - sum(r * C) * delta = sum(r) * alpha * beta
+    let mut neg_gamma = *vk.gamma_g2();
+    neg_gamma.negate();
+    let mut neg_delta = *vk.delta_g2();
+    neg_delta.negate();
+    let (neg_gamma, neg_delta) = (neg_gamma.prepare(), neg_delta.prepare());
+    let ic_acc = ic_acc.into_affine().prepare();
+    let c_acc = c_acc.into_affine().prepare();
+    let mut terms = ab_pairs.iter().map(|(a, b)| (a, b)).collect::<Vec<_>>();
+    terms.push((&ic_acc, &neg_gamma));
+    terms.push((&c_acc, &neg_delta));
+
+    let lhs = Bn256::final_exponentiation(&Bn256::miller_loop(terms.iter()))
+        .ok_or(SynthesisError::UnexpectedIdentity)?;
+    Ok(lhs == vk.alpha_g1_beta_g2().pow(ic_coeffs[0].into_repr()))
+}
+
+/// Version byte of the format written by `write_params`
+pub const PARAMS_VERSION: u8 = 1;
+
+/// Fingerprint of the constraint system, see `R1CS::hash`. This is the value `write_params`
+/// embeds, so comparing it with the hash returned by `read_params` tells whether the
+/// parameters still match the circuit.
+pub fn r1cs_hash<E: Engine>(r1cs: &R1CS<E>) -> [u8; 32] {
+    r1cs.hash()
+}
+
+// Values of the flag byte `write_params` and `write_proof` put ahead of the points
+const UNCOMPRESSED: u8 = 0;
+const COMPRESSED: u8 = 1;
+
+fn write_compression_flag<W: Write>(writer: &mut W, compress: bool) -> io::Result<()> {
+    writer.write_u8(if compress { COMPRESSED } else { UNCOMPRESSED })
+}
+
+fn read_compression_flag<R: Read>(reader: &mut R) -> io::Result<bool> {
+    match reader.read_u8()? {
+        UNCOMPRESSED => Ok(false),
+        COMPRESSED => Ok(true),
+        flag => Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid compression flag {}", flag))),
+    }
+}
+
+fn write_point<W: Write, G: CurveAffine>(writer: &mut W, p: &G, compress: bool) -> io::Result<()> {
+    if compress {
+        writer.write_all(p.into_compressed().as_ref())
+    } else {
+        writer.write_all(p.into_uncompressed().as_ref())
+    }
+}
+
+// Both encodings are checked to be on the curve. That puts G1 points in the prime-order
+// subgroup, G2 points are checked by the callers
+fn read_point<R: Read, G: CurveAffine>(reader: &mut R, compressed: bool) -> io::Result<G> {
+    let point = if compressed {
+        let mut repr = G::Compressed::empty();
+        reader.read_exact(repr.as_mut())?;
+        repr.into_affine()
+    } else {
+        let mut repr = G::Uncompressed::empty();
+        reader.read_exact(repr.as_mut())?;
+        repr.into_affine()
+    };
+    point.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_points<W: Write, G: CurveAffine>(writer: &mut W, points: &[G], compress: bool) -> io::Result<()> {
+    writer.write_u32::<BigEndian>(points.len() as u32)?;
+    for p in points {
+        write_point(writer, p, compress)?;
+    }
+    Ok(())
+}
+
+// Points preallocated for a query, the count comes from the file, so larger queries grow the
+// vector as their points are actually read
+const MAX_PREALLOCATED_POINTS: usize = 1 << 16;
+
+fn read_points<R: Read, G: CurveAffine>(reader: &mut R, compressed: bool) -> io::Result<Vec<G>> {
+    let len = reader.read_u32::<BigEndian>()? as usize;
+    let mut points = Vec::with_capacity(len.min(MAX_PREALLOCATED_POINTS));
+    for _ in 0..len {
+        points.push(read_point(reader, compressed)?);
+    }
+    Ok(points)
+}
+
+/// Writes proving parameters, with compressed query points if `compress` is set. That's about
+/// half the size of `Parameters::write`; uncompressed points are for readers that can't
+/// decompress them. The file starts with `PARAMS_VERSION`, the compression flag and
+/// `r1cs_hash(r1cs)` so that `read_params` callers can tell which circuit the parameters
+/// belong to. The verifying key is always uncompressed, as written by `VerifyingKey::write`.
+pub fn write_params<W: Write>(params: &Parameters<Bn256>, r1cs: &R1CS<Bn256>, writer: &mut W, compress: bool) -> io::Result<()> {
+    writer.write_u8(PARAMS_VERSION)?;
+    write_compression_flag(writer, compress)?;
+    writer.write_all(&r1cs_hash(r1cs))?;
+    params.vk.write(&mut *writer)?;
+    write_points(writer, &params.h, compress)?;
+    write_points(writer, &params.l, compress)?;
+    write_points(writer, &params.a, compress)?;
+    write_points(writer, &params.b_g1, compress)?;
+    write_points(writer, &params.b_g2, compress)
+}
+
+/// Reads parameters written by `write_params` in either form, returning them together with
+/// the hash of the R1CS they were generated for. Compare it to `r1cs_hash`
+/// to detect a mismatch. Every point is checked to be on the curve and in the right subgroup.
+pub fn read_params<R: Read>(reader: &mut R) -> io::Result<(Parameters<Bn256>, [u8; 32])> {
+    let version = reader.read_u8()?;
+    if version != PARAMS_VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported params version {}", version)))
+    }
+    let compressed = read_compression_flag(reader)?;
+    let mut r1cs_hash = [0u8; 32];
+    reader.read_exact(&mut r1cs_hash)?;
+    let vk = VerifyingKey::read(&mut *reader)?;
+    check_g2_subgroup(&[vk.beta_g2, vk.gamma_g2, vk.delta_g2])?;
+    let params = Parameters {
+        vk,
+        h: std::sync::Arc::new(read_points(reader, compressed)?),
+        l: std::sync::Arc::new(read_points(reader, compressed)?),
+        a: std::sync::Arc::new(read_points(reader, compressed)?),
+        b_g1: std::sync::Arc::new(read_points(reader, compressed)?),
+        b_g2: std::sync::Arc::new(read_points(reader, compressed)?),
+    };
+    check_g2_subgroup(params.b_g2.iter())?;
+    Ok((params, r1cs_hash))
+}
+
+/// Writes a proof after a flag byte telling whether its points are compressed, as in
+/// `Proof::write`, or uncompressed for verifiers that only accept those
+pub fn write_proof<W: Write>(proof: &Proof<Bn256>, writer: &mut W, compress: bool) -> io::Result<()> {
+    write_compression_flag(writer, compress)?;
+    write_point(writer, &proof.a, compress)?;
+    write_point(writer, &proof.b, compress)?;
+    write_point(writer, &proof.c, compress)
+}
+
+/// Reads a proof written by `write_proof` in either form. Like `Proof::read`, points at
+/// infinity are rejected, and `b` is checked to be in the prime-order subgroup.
+pub fn read_proof<R: Read>(reader: &mut R) -> io::Result<Proof<Bn256>> {
+    let compressed = read_compression_flag(reader)?;
+    let proof: Proof<Bn256> = Proof {
+        a: read_point(reader, compressed)?,
+        b: read_point(reader, compressed)?,
+        c: read_point(reader, compressed)?,
+    };
+    if proof.a.is_zero() || proof.b.is_zero() || proof.c.is_zero() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "point at infinity"));
+    }
+    check_g2_subgroup(&[proof.b])?;
+    Ok(proof)
+}
+
+/// Reads the proving parameters of a snarkjs groth16 `.zkey`. Prove with
+/// `circom_circuit::prove_zkey`, see `zkey_reader` for why the usual prover doesn't fit.
+pub fn read_zkey<R: Read + Seek>(reader: &mut R) -> ParseResult<(Parameters<Bn256>, VerifyingKey<Bn256>)> {
+    crate::zkey_reader::read(reader)
+}
+
+/// Indexes a `.zkey` without loading its `A`, `B` and `C` points, which
+/// `circom_circuit::prove_zkey_streaming` then reads from the file while proving.
+pub fn load_zkey_streaming<P: AsRef<Path>>(path: P) -> ParseResult<crate::zkey_reader::StreamedZkey> {
+    crate::zkey_reader::load_streaming(path)
+}
+
+/// Writes parameters laid out as `read_zkey` returns them as a `.zkey` for `r1cs`, encoding
+/// the points as they are written.
+pub fn write_zkey_streaming<W: Write>(params: &Parameters<Bn256>, r1cs: &R1CS<Bn256>, writer: W) -> io::Result<()> {
+    crate::zkey_reader::write_streaming(params, r1cs, writer)
+}
+
+#[test]
+fn domain_size_boundary() {
+    assert_eq!(required_domain_size(0).unwrap(), 1);
+    assert_eq!(required_domain_size(5).unwrap(), 8);
+    assert_eq!(required_domain_size(1 << 20).unwrap(), 1 << 20);
+    assert_eq!(required_domain_size((1 << Fr::S) - 1).unwrap(), 1 << Fr::S);
+    let err = required_domain_size(1 << Fr::S).unwrap_err();
+    assert!(matches!(err, SynthesisError::DomainTooLarge { constraints, max_constraints }
+        if constraints == 1 << 28 && max_constraints == (1 << 28) - 1));
+    assert_eq!(err.to_string(), "too many constraints for the scalar field's two-adicity: \
+        268435456 constraints, at most 268435455 fit in an evaluation domain");
+}
+
+#[test]
+fn known_curves() {
+    use bellman_ce::pairing::bls12_381::Bls12;
+
+    assert_eq!(engine_curve::<Bn256>(), Some(Curve::Bn254));
+    assert_eq!(engine_curve::<Bls12>(), Some(Curve::Bls12_381));
+    assert_eq!(detect_curve(&BigUint::from(7u32)), None);
+    assert_eq!(format!("expected {}, got {}", Curve::Bn254, Curve::Bls12_381), "expected BN254, got BLS12-381");
+}
+
+#[test]
+fn scalar_moduli() {
+    use bellman_ce::pairing::bls12_381::Bls12;
+
+    let bn254 = "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+    assert_eq!(scalar_modulus::<Bn256>().to_str_radix(10), bn254);
+    assert_eq!(scalar_modulus::<Bn256>(), field_modulus::<Fr>());
+    assert_eq!(Curve::Bn254.scalar_modulus().to_str_radix(10), bn254);
+    let bls12_381 = "52435875175126190479447740508185965837690552500527637822603658699938581184513";
+    assert_eq!(scalar_modulus::<Bls12>().to_str_radix(10), bls12_381);
+    assert_eq!(Curve::Bls12_381.scalar_modulus().to_str_radix(10), bls12_381);
+}
+
+#[test]
+fn constant_time_equality() {
+    use crate::circom_circuit::seeded_rng;
+
+    let mut rng = seeded_rng(3);
+    for _ in 0..100 {
+        let a = Fr::rand(&mut rng);
+        let b = if rng.gen() { a } else { Fr::rand(&mut rng) };
+        assert_eq!(fr_ct_eq(&a, &b), a == b);
+        assert!(fr_ct_eq(&a, &a));
+    }
+    let mut one_bit_off = Fr::one();
+    one_bit_off.double();
+    assert!(!fr_ct_eq(&Fr::one(), &one_bit_off));
+    assert!(fr_ct_eq(&Fr::zero(), &Fr::zero()));
+}
+
+#[test]
+fn fr_string_round_trips() {
+    let mut minus_one = Fr::one();
+    minus_one.negate();
+    let mut rng = create_rng_from_seed(&[75; 32]);
+    let mut values = vec![Fr::zero(), Fr::one(), minus_one];
+    values.extend((0..4).map(|_| Fr::rand(&mut rng)));
+    for x in values.iter() {
+        let hex = fr_to_hex(x);
+        assert_eq!(hex.len(), 66);
+        assert_eq!(fr_from_hex(&hex).unwrap(), *x);
+        assert_eq!(fr_from_hex(&hex[2..]).unwrap(), *x);
+        assert_eq!(fr_from_dec(&fr_to_dec(x)).unwrap(), *x);
+    }
+    assert_eq!(fr_to_hex(&Fr::one()), format!("0x{:064x}", 1));
+    assert_eq!(fr_to_dec(&minus_one), "21888242871839275222246405745257275088548364400416034343698204186575808495616");
+
+    let modulus = field_modulus::<Fr>();
+    assert!(fr_from_dec(&modulus.to_str_radix(10)).is_err());
+    assert!(fr_from_hex(&modulus.to_str_radix(16)).is_err());
+    for bad in ["", "0x", "-1", "+1", "1_0", "12a"].iter() {
+        assert!(fr_from_dec(bad).is_err(), "{:?}", bad);
+    }
+    assert!(fr_from_hex("0xg").is_err());
+}
+
+#[test]
+fn fr_le_bytes() {
+    let mut twelve = [0u8; 32];
+    twelve[0] = 12;
+    assert_eq!(fr_from_le_bytes::<Fr>(&twelve, 32).unwrap(), fr_from_dec("12").unwrap());
+    let mut minus_one = Fr::one();
+    minus_one.negate();
+    let mut bytes = (field_modulus::<Fr>() - 1u32).to_bytes_le();
+    assert_eq!(fr_from_le_bytes::<Fr>(&bytes, 32).unwrap(), minus_one);
+    // wider fields are fine as long as the extra bytes are zero
+    bytes.resize(48, 0);
+    assert_eq!(fr_from_le_bytes::<Fr>(&bytes, 48).unwrap(), minus_one);
+    bytes[40] = 1;
+    assert!(fr_from_le_bytes::<Fr>(&bytes, 48).is_err());
+
+    let modulus = field_modulus::<Fr>().to_bytes_le();
+    let err = fr_from_le_bytes::<Fr>(&modulus, 32).unwrap_err();
+    assert_eq!(err.to_string(), "Field element is not below the modulus at offset 0");
+    assert!(fr_from_le_bytes::<Fr>(&[0xff; 32], 32).is_err());
+
+    let err = fr_from_le_bytes::<Fr>(&twelve[..31], 32).unwrap_err();
+    assert_eq!(err.to_string(), "Expected a 32-byte field element, got 31 bytes at offset 0");
+    assert!(fr_from_le_bytes::<Fr>(&twelve, 31).is_err());
+}
+
+#[test]
+fn solidity_verifier_golden() {
+    use crate::circom_circuit::{generate_random_parameters, sample_circuit, seeded_rng, CircomCircuit};
+
+    let setup = CircomCircuit { witness: None, ..sample_circuit() };
+    let params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+    let mut contract = vec![];
+    export_solidity_verifier(&params.vk, "^0.6.11", &mut contract).unwrap();
+
+    let normalize = |s: &str| s.split_whitespace().join(" ");
+    let expected = include_str!("testdata/verifier_golden.sol");
+    assert_eq!(normalize(&String::from_utf8(contract).unwrap()), normalize(expected));
+}
+
+#[test]
+fn eth_calldata() {
+    use crate::circom_circuit::{generate_random_parameters, prove_with_seed, sample_circuit, seeded_rng, CircomCircuit};
+
+    let setup = CircomCircuit { witness: None, ..sample_circuit() };
+    let params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+    let proof = prove_with_seed(sample_circuit(), &params, 2).unwrap();
+    let calldata = proof_to_eth_calldata(&proof, &sample_circuit().get_public_inputs().unwrap());
+    assert_eq!(calldata, concat!(
+        "0x43753b4d",
+        "0ed845b39e83bf5a71d550ab9af79bd87fd2aebe4c5ff699c08a31b1f9d26c52",
+        "051d2f172c4c55b129ee9ca52d12f046ea5f8d9c234cd28f2725ec35365fa805",
+        "10c9ca897d4d9c8f7ba9ccf048ee43bf6f0e3f14cc1a057d7b5908de7c7efb67",
+        "0c5e4c99454fb265f2fbb2fc53d79eb38edd15c45b2f233df5b227819f2680c3",
+        "1d91ef1a4c627b24149897a7bd1acd36a2e72c1c3b5ffb0cd6c0c707c5af9d65",
+        "2a3f13827f68c81c7662fa8747c3f4ee5adb9197241cbe9cb2a58824c47fe95b",
+        "1b43b4b74d5e3c480b0f7b86361947309c8e2fa116b8d48c06c95cdbfcf17f77",
+        "2bfcdcbe5aebf91554154910cdf902e708fc8e88f419b613c64ee3148f02ba4f",
+        "000000000000000000000000000000000000000000000000000000000000000c",
+    ));
+
+    // the selector depends on the length of the input array
+    let two_inputs = proof_to_eth_calldata(&proof, &[Fr::one(), Fr::one()]);
+    assert_eq!(two_inputs.len(), 2 + 8 + 64 * 10);
+    assert_ne!(two_inputs[..10], calldata[..10]);
+}
+
+#[test]
+fn snarkjs_proof_json() {
+    use crate::circom_circuit::{generate_random_parameters, prove_with_seed, sample_circuit, seeded_rng, CircomCircuit};
+
+    let setup = CircomCircuit { witness: None, ..sample_circuit() };
+    let params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+    let proof = prove_with_seed(sample_circuit(), &params, 2).unwrap();
+
+    let json = proof_to_snarkjs_json(&proof);
+    let expected: serde_json::Value = serde_json::from_str(include_str!("testdata/proof_snarkjs.json")).unwrap();
+    for field in ["pi_a", "pi_b", "pi_c", "protocol", "curve"].iter() {
+        assert_eq!(json[field], expected[field], "{} differs", field);
+    }
+    assert_eq!(json, expected);
+}
+
+#[test]
+fn snarkjs_vk_json() {
+    use crate::circom_circuit::{generate_random_parameters, sample_circuit, seeded_rng, CircomCircuit};
+
+    let setup = CircomCircuit { witness: None, ..sample_circuit() };
+    let params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+
+    let json = vk_to_snarkjs_json(&params.vk);
+    let expected: serde_json::Value = serde_json::from_str(include_str!("testdata/verification_key_snarkjs.json")).unwrap();
+    for field in ["protocol", "curve", "nPublic", "vk_alpha_1", "vk_beta_2", "vk_gamma_2", "vk_delta_2", "IC"].iter() {
+        assert_eq!(json[field], expected[field], "{} differs", field);
+    }
+    assert_eq!(json, expected);
+
+    let vk = vk_from_snarkjs_json(&json).unwrap();
+    assert!(vk.alpha_g1 == params.vk.alpha_g1 && vk.beta_g2 == params.vk.beta_g2);
+    assert!(vk.gamma_g2 == params.vk.gamma_g2 && vk.delta_g2 == params.vk.delta_g2);
+    assert!(vk.ic == params.vk.ic);
+}
+
+#[test]
+fn empty_ic() {
+    use bellman_ce::groth16::prepare_verifying_key;
+    use crate::circom_circuit::sample_params;
+
+    let mut vk = sample_params().vk;
+    vk.ic.clear();
+    assert_eq!(vk_to_snarkjs_json(&vk)["nPublic"], 0);
+    assert!(export_solidity_verifier(&vk, "^0.6.11", &mut vec![]).is_err());
+
+    let pvk = prepare_verifying_key(&vk);
+    assert!(matches!(batch_verify(&pvk, &[]), Err(SynthesisError::MalformedVerifyingKey)));
+    let mut bytes = vec![];
+    write_prepared_vk(&pvk, &mut bytes).unwrap();
+    assert_eq!(read_prepared_vk(&bytes[..]).err().unwrap().to_string(), "Empty IC");
+
+    let json = |s: &str| serde_json::from_str::<serde_json::Value>(s).unwrap();
+    let mut vk_json = json(include_str!("testdata/verification_key_snarkjs.json"));
+    vk_json["IC"] = serde_json::json!([]);
+    vk_json["nPublic"] = serde_json::json!(0);
+    let proof = json(include_str!("testdata/proof_snarkjs.json"));
+    let err = verify_with_snarkjs_vk(&vk_json, &proof, &serde_json::json!([])).unwrap_err();
+    assert_eq!(err.to_string(), "IC is empty at offset 0");
+}
+
+#[test]
+fn r1cs_json_dump() {
+    use crate::circom_circuit::{r1cs_from_json, R1CSBuilder};
+
+    let fr = |s: &str| Fr::from_str(s).unwrap();
+    let mut minus_one = Fr::one();
+    minus_one.negate();
+    let r1cs = R1CSBuilder::<Bn256>::new(5)
+        .set_public_count(1)
+        .add_constraint(vec![(2, fr("1"))], vec![(3, fr("1"))], vec![(1, fr("1"))])
+        .add_constraint(vec![(0, fr("7")), (4, minus_one)], vec![(0, fr("1"))], vec![(2, fr("12345678901234567890"))])
+        .build()
+        .unwrap();
+    let json = r1cs_to_json(&r1cs);
+    let p_minus_one = "21888242871839275222246405745257275088548364400416034343698204186575808495616";
+    assert_eq!(json, serde_json::json!({
+        "n8": 32,
+        "prime": "21888242871839275222246405745257275088548364400416034343698204186575808495617",
+        "nVars": 5,
+        "nOutputs": 0,
+        "nPubInputs": 1,
+        "nConstraints": 2,
+        "useCustomGates": false,
+        "constraints": [
+            [{"2": "1"}, {"3": "1"}, {"1": "1"}],
+            [{"0": "7", "4": p_minus_one}, {"0": "1"}, {"2": "12345678901234567890"}],
+        ],
+        "map": [],
+    }));
+    assert!(r1cs_from_json::<Bn256, _>(json.to_string().as_bytes()) == r1cs);
+}
+
+#[test]
+fn r1cs_json_signed_coefficients() {
+    use crate::circom_circuit::R1CSBuilder;
+
+    let fr = |s: &str| Fr::from_str(s).unwrap();
+    let mut minus_one = Fr::one();
+    minus_one.negate();
+    let r1cs = R1CSBuilder::<Bn256>::new(5)
+        .set_public_count(1)
+        .add_constraint(vec![(0, fr("7")), (4, minus_one)], vec![(0, fr("1"))], vec![(2, fr("12345678901234567890"))])
+        .build()
+        .unwrap();
+    let json = r1cs_to_json(&r1cs);
+    assert_eq!(r1cs_to_json_with_signs(&r1cs, false), json);
+
+    // coefficients above half the modulus are written as negatives, the rest as they are
+    let signed = r1cs_to_json_with_signs(&r1cs, true);
+    assert_eq!(signed["constraints"][0][0], serde_json::json!({"0": "7", "4": "-1"}));
+    assert_eq!(signed["constraints"][0][2], serde_json::json!({"2": "12345678901234567890"}));
+    assert_eq!(signed["prime"], json["prime"]);
+}
+
+/// Writes each `(file, contents)` to `zkutil-{name}-{pid}-{file}` in the temp dir, calls `f`
+/// with the paths and returns what it did. The files are removed afterwards even if `f` panics,
+/// `f` may remove them itself
+#[cfg(test)]
+pub(crate) fn with_temp_files<T>(name: &str, files: &[(&str, &[u8])], f: impl FnOnce(&[std::path::PathBuf]) -> T) -> T {
+    struct TempFiles(Vec<std::path::PathBuf>);
+
+    impl Drop for TempFiles {
+        fn drop(&mut self) {
+            for path in &self.0 {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    let paths = TempFiles(files.iter().map(|(file, _)| std::env::temp_dir().join(format!("zkutil-{}-{}-{}", name, std::process::id(), file))).collect_vec());
+    for (path, (_, contents)) in paths.0.iter().zip(files) {
+        std::fs::write(path, contents).unwrap();
+    }
+    f(&paths.0)
+}
+
+/// A point on the twist with the smallest x, the cofactor of G2 makes it almost surely not of
+/// prime order
+#[cfg(test)]
+pub(crate) fn off_subgroup_g2() -> G2Affine {
+    use bellman_ce::pairing::ff::SqrtField;
+
+    let mut x = Fq2::zero();
+    loop {
+        x.add_assign(&Fq2::one());
+        let mut rhs = x;
+        rhs.square();
+        rhs.mul_assign(&x);
+        rhs.add_assign(&G2Affine::b_coeff());
+        if let Some(y) = rhs.sqrt() {
+            let p = G2Affine::from_xy_checked(x, y).unwrap();
+            assert!(!in_prime_order_subgroup(&p));
+            return p;
+        }
+    }
+}
+
+#[test]
+fn proof_subgroup_checks() {
+    use crate::circom_circuit::{prove_with_seed, sample_circuit, sample_params};
+
+    let proof = prove_with_seed(sample_circuit(), &sample_params(), 3).unwrap();
+    validate_proof_points(&proof).unwrap();
+
+    let off_subgroup = off_subgroup_g2();
+    let crafted = Proof { b: off_subgroup, ..proof.clone() };
+    let err = validate_proof_points(&crafted).unwrap_err();
+    assert_eq!(err.to_string(), "pi_b is not in the prime-order subgroup at offset 0");
+    let json = proof_to_snarkjs_json(&crafted);
+    let err = proof_from_snarkjs_json(&json).unwrap_err();
+    assert_eq!(err.to_string(), "pi_b is not in the prime-order subgroup at offset 0");
+    let err = proof_from_evm_bytes(&proof_to_evm_bytes(&crafted)).unwrap_err();
+    assert_eq!(err.to_string(), "b is not in the prime-order subgroup at offset 64");
+    let err = proof_from_bytes(&proof_to_bytes(&crafted)).unwrap_err();
+    assert_eq!(err.to_string(), "b is not in the prime-order subgroup at offset 32");
+    for compress in [false, true].iter() {
+        let mut bytes = vec![];
+        write_proof(&crafted, &mut bytes, *compress).unwrap();
+        let err = read_proof(&mut &bytes[..]).unwrap_err();
+        assert_eq!(err.to_string(), "G2 point is not in the prime-order subgroup");
+    }
+
+    let (x, y) = proof.c.into_xy_unchecked();
+    let mut y = y;
+    y.double();
+    let off_curve = Proof { c: G1Affine::from_xy_unchecked(x, y), ..proof };
+    let err = validate_proof_points(&off_curve).unwrap_err();
+    assert_eq!(err.to_string(), "pi_c is not on the curve at offset 0");
+}
+
+#[test]
+fn snarkjs_proof_import() {
+    use crate::circom_circuit::{generate_random_parameters, sample_circuit, seeded_rng, verify, CircomCircuit};
+
+    let setup = CircomCircuit { witness: None, ..sample_circuit() };
+    let params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+    let json: serde_json::Value = serde_json::from_str(include_str!("testdata/proof_snarkjs.json")).unwrap();
+
+    let proof = proof_from_snarkjs_json(&json).unwrap();
+    assert!(verify(&params, &proof, &sample_circuit().get_public_inputs().unwrap()).unwrap());
+    assert_eq!(proof_to_snarkjs_json(&proof), json);
+
+    let mut swapped = json.clone();
+    swapped["pi_b"][0] = serde_json::json!([json["pi_b"][0][1], json["pi_b"][0][0]]);
+    assert!(proof_from_snarkjs_json(&swapped).unwrap_err().to_string().starts_with("pi_b: "));
+
+    let mut off_curve = json.clone();
+    off_curve["pi_a"][1] = serde_json::json!("1");
+    assert!(proof_from_snarkjs_json(&off_curve).unwrap_err().to_string().starts_with("pi_a: "));
+
+    let mut missing = json;
+    missing["pi_c"] = serde_json::json!([]);
+    assert!(matches!(proof_from_snarkjs_json(&missing), Err(ParseError::Malformed { .. })));
+}
+
+#[test]
+fn temp_files_removed_on_panic() {
+    let mut leaked = None;
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        with_temp_files("panic", &[("file.bin", &b"contents"[..])], |paths| {
+            leaked = Some(paths[0].clone());
+            panic!("test failure");
+        })
+    }));
+    assert!(result.is_err());
+    assert!(!leaked.unwrap().exists());
+}
+
+#[test]
+fn verify_from_files() {
+    use crate::circom_circuit::{prove_with_seed, sample_circuit, sample_params, proof_to_json_file, verification_key_json_file};
+
+    let params = sample_params();
+    let proof = prove_with_seed(sample_circuit(), &params, 7).unwrap();
+    let inputs = sample_circuit().get_public_inputs().unwrap();
+    let files = [("vk.json", &b""[..]), ("proof.json", &b""[..])];
+    let (valid, wrong_input, wrong_count, invalid, paths) = with_temp_files("verify", &files, |paths| {
+        let (vk_path, proof_path) = (&paths[0], &paths[1]);
+        verification_key_json_file(&params, vk_path.to_str().unwrap()).unwrap();
+        proof_to_json_file(&proof, proof_path.to_str().unwrap()).unwrap();
+
+        let valid = verify_proof_from_files(vk_path, proof_path, &inputs);
+        let wrong_input = verify_proof_from_files(vk_path, proof_path, &[Fr::one()]);
+        let wrong_count = verify_proof_from_files(vk_path, proof_path, &[inputs[0], inputs[0]]);
+        let other_proof = prove_with_seed(sample_circuit(), &sample_params(), 7).unwrap();
+        proof_to_json_file(&other_proof, proof_path.to_str().unwrap()).unwrap();
+        let invalid = verify_proof_from_files(vk_path, proof_path, &inputs);
+        (valid, wrong_input, wrong_count, invalid, paths.to_vec())
+    });
+    let missing = verify_proof_from_files(&paths[0], &paths[1], &inputs);
+
+    assert!(valid.unwrap());
+    assert!(!wrong_input.unwrap());
+    assert!(!invalid.unwrap());
+    assert!(matches!(wrong_count, Err(ParseError::WrongInputCount { expected: 1, found: 2, .. })));
+    assert!(matches!(missing, Err(ParseError::Io { .. })));
+}
+
+#[test]
+fn snarkjs_public_json() {
+    let testdata = |name: &str| Path::new(env!("CARGO_MANIFEST_DIR")).join("src/testdata").join(name);
+    let public = testdata("public_snarkjs.json");
+    let inputs = read_public_json(&mut File::open(&public).unwrap()).unwrap();
+    assert_eq!(inputs, vec![Fr::from_str("12").unwrap()]);
+    let verified = verify_from_snarkjs_inputs(testdata("verification_key_snarkjs.json"), testdata("proof_snarkjs.json"), &public);
+    assert!(verified.unwrap());
+
+    let ordered = read_public_json(&mut &br#"["3", "0", "12"]"#[..]).unwrap();
+    assert_eq!(ordered, vec![Fr::from_str("3").unwrap(), Fr::zero(), Fr::from_str("12").unwrap()]);
+    let modulus = field_modulus::<Fr>().to_string();
+    let err = read_public_json(&mut format!(r#"["1", "{}"]"#, modulus).as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), format!("public[1]: {} is not below the field modulus at offset 0", modulus));
+    let err = read_public_json(&mut &br#"["1", 2]"#[..]).unwrap_err();
+    assert_eq!(err.to_string(), "public[1] is not a string at offset 0");
+    let err = read_public_json(&mut &br#"{"0": "1"}"#[..]).unwrap_err();
+    assert_eq!(err.to_string(), "public inputs are not an array at offset 0");
+}
+
+#[test]
+fn snarkjs_conformance() {
+    let json = |s: &str| serde_json::from_str::<serde_json::Value>(s).unwrap();
+    let vk = json(include_str!("testdata/verification_key_snarkjs.json"));
+    let proof = json(include_str!("testdata/proof_snarkjs.json"));
+    let public = json(include_str!("testdata/public_snarkjs.json"));
+    assert!(verify_with_snarkjs_vk(&vk, &proof, &public).unwrap());
+    assert!(!verify_with_snarkjs_vk(&vk, &proof, &serde_json::json!(["13"])).unwrap());
+    let err = verify_with_snarkjs_vk(&vk, &proof, &serde_json::json!([])).unwrap_err();
+    assert!(matches!(err, ParseError::WrongInputCount { expected: 1, found: 0, .. }));
+
+    let mut plonk = vk.clone();
+    plonk["protocol"] = serde_json::json!("plonk");
+    let err = verify_with_snarkjs_vk(&plonk, &proof, &public).unwrap_err();
+    assert_eq!(err.to_string(), "protocol is \"plonk\", expected \"groth16\" at offset 0");
+    let mut bls = vk.clone();
+    bls["curve"] = serde_json::json!("bls12381");
+    assert!(verify_with_snarkjs_vk(&bls, &proof, &public).is_err());
+    let mut miscounted = vk.clone();
+    miscounted["nPublic"] = serde_json::json!(2);
+    let err = verify_with_snarkjs_vk(&miscounted, &proof, &public).unwrap_err();
+    assert_eq!(err.to_string(), "nPublic is 2, but IC has 2 points at offset 0");
+    let mut off_curve = proof;
+    off_curve["pi_c"][1] = serde_json::json!("1");
+    let err = verify_with_snarkjs_vk(&vk, &off_curve, &public).unwrap_err();
+    assert!(err.to_string().starts_with("pi_c: "));
+}
+
+#[test]
+fn proofs_json_round_trip() {
+    use crate::circom_circuit::{generate_random_parameters, prove, sample_circuit, sample_params, seeded_rng, verify, R1CSBuilder};
+
+    let params = sample_params();
+    let inputs = sample_circuit().get_public_inputs().unwrap();
+    // x * x = y with nothing public
+    let r1cs = R1CSBuilder::<Bn256>::new(3)
+        .add_constraint(vec![(1, Fr::one())], vec![(1, Fr::one())], vec![(2, Fr::one())])
+        .build()
+        .unwrap();
+    let private = CircomCircuit { r1cs, witness: Some(vec![Fr::one(), Fr::from_str("3").unwrap(), Fr::from_str("9").unwrap()]), wire_mapping: None };
+    let private_params = generate_random_parameters(CircomCircuit { witness: None, ..private.clone() }, seeded_rng(3)).unwrap();
+
+    let proofs = vec![
+        (prove(sample_circuit(), &params, seeded_rng(1)).unwrap(), inputs.clone()),
+        (prove(private.clone(), &private_params, seeded_rng(2)).unwrap(), vec![]),
+        (prove(sample_circuit(), &params, seeded_rng(3)).unwrap(), inputs.clone()),
+    ];
+    let json = proofs_to_json(&proofs);
+    assert_eq!(json[0]["proof"], proof_to_snarkjs_json(&proofs[0].0));
+    assert_eq!(json[0]["public"], serde_json::json!(["12"]));
+    assert_eq!(json[1]["public"], serde_json::json!([]));
+
+    let read = proofs_from_json(&serde_json::from_str(&json.to_string()).unwrap()).unwrap();
+    assert_eq!(read.len(), 3);
+    for ((proof, public), (expected_proof, expected_public)) in read.iter().zip(proofs.iter()) {
+        assert!(proof == expected_proof);
+        assert_eq!(public, expected_public);
+    }
+    assert!(verify(&params, &read[0].0, &read[0].1).unwrap());
+    assert!(verify(&private_params, &read[1].0, &read[1].1).unwrap());
+    assert!(proofs_from_json(&serde_json::json!([])).unwrap().is_empty());
+
+    let mut bad_point = json.clone();
+    bad_point[2]["proof"]["pi_a"][1] = serde_json::json!("1");
+    assert!(proofs_from_json(&bad_point).unwrap_err().to_string().starts_with("[2].proof: pi_a: "));
+    let mut bad_input = json.clone();
+    bad_input[0]["public"] = serde_json::json!([12]);
+    assert_eq!(proofs_from_json(&bad_input).unwrap_err().to_string(), "[0].public[0] is not a string at offset 0");
+    let mut missing = json;
+    missing[1].as_object_mut().unwrap().remove("public");
+    assert_eq!(proofs_from_json(&missing).unwrap_err().to_string(), "[1].public is not an array at offset 0");
+}
+
+#[test]
+fn pinned_blinding() {
+    use crate::circom_circuit::{generate_random_parameters, sample_circuit, seeded_rng, verify};
+
+    let setup = CircomCircuit { witness: None, ..sample_circuit() };
+    let params = generate_random_parameters(setup, seeded_rng(1)).unwrap();
+    let r = "0x0f1e2d3c4b5a69788796a5b4c3d2e1f00112233445566778899aabbccddeeff";
+    let s = "2a";
+
+    let proof = create_proof_with_blinding_hex(sample_circuit(), &params, r, s).unwrap();
+    assert_eq!(proof_to_hex(&proof), concat!(
+        "2430d9a4d19d009dbb79dc0aa5bce70c4644f503e14f687ebde3c67a2898ccea",
+        "1cdda907fb2148fe8ab912c642db530ab993eea2e7c0d117e23e983e7ce15338",
+        "01e8f1505d730c1451afabaecfe38f87b89191e70252a6280780acf4a0ca5915",
+        "24e87763b32f0ae23749e99a89b870ff7987f5d721e7ac3a552ea5d9a00e0aa3",
+        "15b7d81fa157cc83ba26b7b6cb6364fbef55bcf19fc35d13816d249ce06a521f",
+        "1d5af7bafdec96daac51f93a92105f14e06f3bd052b9034ca6698e18cdb6c9e4",
+        "25b3494b74e9516ea95579c917804f47611f0ce94fa645719eb67fea2dd138f9",
+        "0fef68494c12ded9371ffeeeea88ece22b6a348d326de8a0be6ace0f0d09cf56",
+    ));
+    assert!(verify(&params, &proof, &sample_circuit().get_public_inputs().unwrap()).unwrap());
+    let other = create_proof_with_blinding_hex(sample_circuit(), &params, r, "2b").unwrap();
+    assert!(other != proof);
+
+    let modulus = "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001";
+    let err = create_proof_with_blinding_hex(sample_circuit(), &params, r, modulus).unwrap_err();
+    assert!(matches!(err, BlindingError::Scalar(ParseError::Malformed { .. })));
+    assert!(err.to_string().starts_with("s: "));
+    let err = create_proof_with_blinding_hex(sample_circuit(), &params, "0xg", s).unwrap_err();
+    assert!(err.to_string().starts_with("r: "));
+    let setup = CircomCircuit { witness: None, ..sample_circuit() };
+    assert!(matches!(create_proof_with_blinding_hex(setup, &params, r, s), Err(BlindingError::Synthesis(SynthesisError::AssignmentMissing))));
+}
+
+#[test]
+fn seeded_proofs() {
+    use crate::circom_circuit::{prove, sample_circuit, sample_params};
+
+    let params = sample_params();
+    let proof = |seed: [u8; 32]| prove(sample_circuit(), &params, create_rng_from_seed(&seed)).unwrap();
+    assert!(proof([7; 32]) == proof([7; 32]));
+    assert!(proof([7; 32]) != proof([8; 32]));
+
+    // `seeded_rng` keys the same RNG with the seed's 8 bytes, zero-padded
+    let mut key = [0; 32];
+    key[..8].copy_from_slice(&0x0123_4567_89ab_cdefu64.to_le_bytes());
+    assert!(proof(key) == prove(sample_circuit(), &params, crate::circom_circuit::seeded_rng(0x0123_4567_89ab_cdef)).unwrap());
+}
+
+#[test]
+fn prepared_vk_reuse() {
+    use crate::circom_circuit::{prove, sample_circuit, sample_params, seeded_rng};
+
+    let params = sample_params();
+    let vk = PreparedVk::new(&params.vk);
+    let inputs = sample_circuit().get_public_inputs().unwrap();
+    let proofs = (0..3).map(|seed| prove(sample_circuit(), &params, seeded_rng(seed)).unwrap()).collect_vec();
+    for proof in proofs.iter() {
+        assert!(vk.verify(proof, &inputs).unwrap());
+        assert!(!vk.verify(proof, &[Fr::one()]).unwrap());
+    }
+
+    let other = prove(sample_circuit(), &sample_params(), seeded_rng(0)).unwrap();
+    assert!(!vk.verify(&other, &inputs).unwrap());
+
+    let shared = vk.clone();
+    assert!(std::ptr::eq(shared.prepared(), vk.prepared()));
+    let proof = proofs[0].clone();
+    let handle = std::thread::spawn(move || shared.verify(&proof, &inputs).unwrap());
+    assert!(handle.join().unwrap());
+
+    let from_prepared = PreparedVk::from(prepare_verifying_key(&params.vk));
+    assert!(from_prepared.verify(&proofs[1], &sample_circuit().get_public_inputs().unwrap()).unwrap());
+}
+
+#[test]
+fn prepared_vk_round_trip() {
+    use crate::circom_circuit::{prove, sample_circuit, sample_params, seeded_rng};
+
+    let params = sample_params();
+    let pvk = prepare_verifying_key(&params.vk);
+    let mut bytes = vec![];
+    write_prepared_vk(&pvk, &mut bytes).unwrap();
+    assert_eq!(bytes.len(), 12 * 32 + 2 * 128 + 4 + pvk.ic().len() * 64);
+
+    let loaded = read_prepared_vk(&bytes[..]).unwrap();
+    assert_eq!(loaded.alpha_g1_beta_g2(), pvk.alpha_g1_beta_g2());
+    assert_eq!(loaded.gamma_g2(), pvk.gamma_g2());
+    assert_eq!(loaded.delta_g2(), pvk.delta_g2());
+    assert_eq!(loaded.ic(), pvk.ic());
+    let mut again = vec![];
+    write_prepared_vk(&loaded, &mut again).unwrap();
+    assert_eq!(again, bytes);
+
+    let inputs = sample_circuit().get_public_inputs().unwrap();
+    let proof = prove(sample_circuit(), &params, seeded_rng(4)).unwrap();
+    assert!(verify_proof(&loaded, &proof, &inputs).unwrap());
+    assert!(!verify_proof(&loaded, &proof, &[Fr::one()]).unwrap());
+
+    assert!(read_prepared_vk(&bytes[..bytes.len() - 1]).is_err());
+
+    // gamma and delta on the curve but outside the prime-order subgroup
+    for offset in [12 * 32, 12 * 32 + 128].iter() {
+        let mut tampered = bytes.clone();
+        tampered[*offset..*offset + 128].copy_from_slice(off_subgroup_g2().into_uncompressed().as_ref());
+        let err = read_prepared_vk(&tampered[..]).err().unwrap();
+        assert_eq!(err.to_string(), "G2 point is not in the prime-order subgroup");
+    }
+}
+
+#[test]
+fn vk_x_matches_verifier() {
+    use bellman_ce::groth16::prepare_verifying_key;
+    use crate::circom_circuit::{prove, sample_circuit, sample_params, seeded_rng};
+
+    let params = sample_params();
+    let vk = &params.vk;
+    let inputs = sample_circuit().get_public_inputs().unwrap();
+    let proof = prove(sample_circuit(), &params, seeded_rng(5)).unwrap();
+    assert!(verify_proof(&prepare_verifying_key(vk), &proof, &inputs).unwrap());
+
+    // A * B = alpha * beta + vk_x * gamma + C * delta
+    let equation_holds = |vk_x: G1Affine| {
+        let mut rhs = Bn256::pairing(vk.alpha_g1, vk.beta_g2);
+        rhs.mul_assign(&Bn256::pairing(vk_x, vk.gamma_g2));
+        rhs.mul_assign(&Bn256::pairing(proof.c, vk.delta_g2));
+        Bn256::pairing(proof.a, proof.b) == rhs
+    };
+    let vk_x = compute_vk_x(vk, &inputs).unwrap();
+    assert!(equation_holds(vk_x));
+    let mut expected = vk.ic[1].mul(inputs[0].into_repr());
+    expected.add_assign_mixed(&vk.ic[0]);
+    assert!(vk_x == expected.into_affine());
+
+    let mut wrong = inputs.clone();
+    wrong[0].add_assign(&Fr::one());
+    assert!(!equation_holds(compute_vk_x(vk, &wrong).unwrap()));
+    assert!(compute_vk_x(vk, &[Fr::zero()]).unwrap() == vk.ic[0]);
+    assert!(matches!(
+        compute_vk_x(vk, &[]),
+        Err(SynthesisError::InputCountMismatch { expected: 1, got: 0 })
+    ));
+}
+
+#[test]
+fn library_setup() {
+    use crate::circom_circuit::{prove_with_seed, sample_circuit, seeded_rng, verify_circuit};
+
+    let params = generate_params(sample_circuit(), &mut seeded_rng(1)).unwrap();
+    let setup = CircomCircuit { witness: None, ..sample_circuit() };
+    assert!(generate_params(setup, &mut seeded_rng(1)).unwrap() == params);
+    let proof = prove_with_seed(sample_circuit(), &params, 2).unwrap();
+    assert!(verify_circuit(&sample_circuit(), &params, &proof).unwrap());
+    let pvk = prepare_verifying_key(&params.vk);
+    assert!(!verify_proof(&pvk, &proof, &[Fr::one()]).unwrap());
+}
+
+#[test]
+fn batch_verification() {
+    use bellman_ce::groth16::prepare_verifying_key;
+    use crate::circom_circuit::{prove, sample_circuit, sample_params, seeded_rng};
+
+    let params = sample_params();
+    let pvk = prepare_verifying_key(&params.vk);
+    let inputs = sample_circuit().get_public_inputs().unwrap();
+    let mut items = (0..4)
+        .map(|seed| (prove(sample_circuit(), &params, seeded_rng(seed)).unwrap(), inputs.clone()))
+        .collect::<Vec<_>>();
+    assert!(batch_verify(&pvk, &items).unwrap());
+    assert!(batch_verify(&pvk, &[]).unwrap());
+
+    // one proof checked against the wrong input
+    let mut wrong_input = items.clone();
+    wrong_input[2].1[0].add_assign(&Fr::one());
+    assert!(!batch_verify(&pvk, &wrong_input).unwrap());
+
+    // two invalid proofs whose errors cancel in an unweighted sum
+    let mut shift = G1Affine::one().into_projective();
+    shift.double();
+    let mut c0 = items[0].0.c.into_projective();
+    c0.add_assign(&shift);
+    let mut c1 = items[1].0.c.into_projective();
+    c1.sub_assign(&shift);
+    items[0].0.c = c0.into_affine();
+    items[1].0.c = c1.into_affine();
+    assert!(!batch_verify(&pvk, &items).unwrap());
+
+    items[3].1.push(Fr::one());
+    assert!(matches!(
+        batch_verify(&pvk, &items),
+        Err(SynthesisError::InputCountMismatch { expected: 1, got: 2 })
+    ));
+}
+
+#[test]
+fn stable_r1cs_hash() {
+    use crate::circom_circuit::sample_circuit;
+
+    let r1cs = sample_circuit().r1cs;
+    let hash = r1cs_hash(&r1cs);
+    assert_eq!(hash.iter().map(|b| format!("{:02x}", b)).join(""), "378575ad6f7bc122ff9b6ffac1c5661c5487c5ffe8464b05570648a1a8336dff");
+
+    let mut changed = r1cs.clone();
+    changed.constraints[0].c[0].1 = Fr::from_str("2").unwrap();
+    assert_ne!(r1cs_hash(&changed), hash);
+    let mut relabeled = r1cs;
+    relabeled.wire_to_label = vec![0, 1, 2, 3];
+    assert_eq!(r1cs_hash(&relabeled), hash);
+}
+
+#[test]
+fn params_round_trip() {
+    use crate::circom_circuit::{create_rng, prove, sample_circuit, sample_params, verify};
+
+    let params = sample_params();
+    let r1cs = sample_circuit().r1cs;
+    let mut bytes = vec![];
+    write_params(&params, &r1cs, &mut bytes, true).unwrap();
+    let mut uncompressed = vec![];
+    write_params(&params, &r1cs, &mut uncompressed, false).unwrap();
+    let mut bellman = vec![];
+    params.write(&mut bellman).unwrap();
+    assert!(bytes.len() < bellman.len());
+    assert!(bytes.len() < uncompressed.len());
+    assert_eq!(uncompressed[1], UNCOMPRESSED);
+
+    let (read, hash) = read_params(&mut &bytes[..]).unwrap();
+    assert!(read == params);
+    assert_eq!(hash, r1cs.hash());
+    let mut other = r1cs.clone();
+    other.constraints.pop();
+    assert_ne!(hash, other.hash());
+    let (read_uncompressed, hash) = read_params(&mut &uncompressed[..]).unwrap();
+    assert!(read_uncompressed == params);
+    assert_eq!(hash, r1cs.hash());
+
+    let proof = prove(sample_circuit(), &read, create_rng()).unwrap();
+    assert!(verify(&params, &proof, &sample_circuit().get_public_inputs().unwrap()).unwrap());
+
+    bytes[0] = PARAMS_VERSION + 1;
+    assert_eq!(read_params(&mut &bytes[..]).err().unwrap().to_string(), "Unsupported params version 2");
+    bytes[0] = PARAMS_VERSION;
+    bytes[1] = 2;
+    assert!(read_params(&mut &bytes[..]).is_err());
+    bytes[1] = COMPRESSED;
+    bytes.pop();
+    assert!(read_params(&mut &bytes[..]).is_err());
+}
+
+#[test]
+fn params_subgroup_checks() {
+    use crate::circom_circuit::{sample_circuit, sample_params};
+
+    let r1cs = sample_circuit().r1cs;
+    let mut bad_b_g2 = sample_params();
+    let mut b_g2 = bad_b_g2.b_g2.to_vec();
+    b_g2[0] = off_subgroup_g2();
+    bad_b_g2.b_g2 = Arc::new(b_g2);
+    let mut bad_delta = sample_params();
+    bad_delta.vk.delta_g2 = off_subgroup_g2();
+    for params in [bad_b_g2, bad_delta].iter() {
+        let mut bytes = vec![];
+        write_params(params, &r1cs, &mut bytes, false).unwrap();
+        let err = read_params(&mut &bytes[..]).err().unwrap();
+        assert_eq!(err.to_string(), "G2 point is not in the prime-order subgroup");
+    }
+}
+
+#[test]
+fn params_with_huge_query_length() {
+    use crate::circom_circuit::{sample_circuit, sample_params};
+
+    let params = sample_params();
+    let mut bytes = vec![];
+    write_params(&params, &sample_circuit().r1cs, &mut bytes, true).unwrap();
+    let mut vk = vec![];
+    params.vk.write(&mut vk).unwrap();
+    // claim 2^32 - 1 points in the H query, followed by a single point's worth of data
+    let h_len = 2 + 32 + vk.len();
+    bytes[h_len..h_len + 4].copy_from_slice(&[0xff; 4]);
+    bytes.truncate(h_len + 4 + 32);
+    let err = read_params(&mut &bytes[..]).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn proof_evm_bytes_round_trip() {
+    use crate::circom_circuit::{prove_with_seed, sample_circuit, sample_params};
+
+    let proof = prove_with_seed(sample_circuit(), &sample_params(), 3).unwrap();
+    let bytes = proof_to_evm_bytes(&proof);
+    assert!(proof_from_evm_bytes(&bytes).unwrap() == proof);
+    assert_eq!(bytes.iter().map(|b| format!("{:02x}", b)).join(""), proof_to_hex(&proof));
+    let mut b_x_c1 = vec![];
+    proof.b.into_xy_unchecked().0.c1.into_repr().write_be(&mut b_x_c1).unwrap();
+    assert_eq!(bytes[64..96], b_x_c1[..]);
+
+    for len in [0, 128, 255, 257].iter() {
+        let mut data = bytes.to_vec();
+        data.resize(*len, 0);
+        let err = proof_from_evm_bytes(&data).unwrap_err();
+        assert_eq!(err.to_string(), format!("Expected a 256-byte proof, got {} bytes at offset 0", len));
+    }
+    let mut data = bytes;
+    data[96..128].copy_from_slice(&[0xff; 32]);
+    let err = proof_from_evm_bytes(&data).unwrap_err();
+    assert_eq!(err.to_string(), "b.x.c0 is not below the modulus at offset 96");
+    let mut data = bytes;
+    data[255] ^= 1;
+    let err = proof_from_evm_bytes(&data).unwrap_err();
+    assert!(matches!(err, ParseError::Malformed { offset: 192, .. }));
+    assert!(err.to_string().starts_with("c: "));
+    let err = proof_from_evm_bytes(&[0; 256]).unwrap_err();
+    assert_eq!(err.to_string(), "a is the point at infinity at offset 0");
+}
+
+#[test]
+fn proof_bytes_round_trip() {
+    use crate::circom_circuit::{prove_with_seed, sample_circuit, sample_params};
+
+    let proof = prove_with_seed(sample_circuit(), &sample_params(), 3).unwrap();
+    let bytes = proof_to_bytes(&proof);
+    assert!(proof_from_bytes(&bytes).unwrap() == proof);
+    let mut b_x_c0 = vec![];
+    proof.b.into_xy_unchecked().0.c0.into_repr().write_be(&mut b_x_c0).unwrap();
+    assert_eq!(bytes[64..96], b_x_c0[..]);
+    let evm = proof_to_evm_bytes(&proof);
+    assert_eq!(bytes[1..32], evm[1..32]);
+    assert_eq!(bytes[97..], evm[193..224]);
+
+    for len in [0, 127, 129, 256].iter() {
+        let mut data = bytes.to_vec();
+        data.resize(*len, 0);
+        let err = proof_from_bytes(&data).unwrap_err();
+        assert_eq!(err.to_string(), format!("Expected a 128-byte proof, got {} bytes at offset 0", len));
+    }
+    let mut data = bytes;
+    data[64..96].copy_from_slice(&[0xff; 32]);
+    let err = proof_from_bytes(&data).unwrap_err();
+    assert!(matches!(err, ParseError::Malformed { offset: 32, .. }));
+    assert!(err.to_string().starts_with("b: "));
+    // flipping the sign flag gives -c, which is still a valid point
+    let mut data = bytes;
+    data[96] ^= 0x80;
+    let mut minus_c = proof.c;
+    minus_c.negate();
+    assert!(proof_from_bytes(&data).unwrap().c == minus_c);
+    let mut data = bytes;
+    data[..32].copy_from_slice(G1Affine::zero().into_compressed().as_ref());
+    let err = proof_from_bytes(&data).unwrap_err();
+    assert_eq!(err.to_string(), "a is the point at infinity at offset 0");
+}
+
+#[test]
+fn proof_round_trip() {
+    use crate::circom_circuit::{prove_with_seed, sample_circuit, sample_params};
+
+    let proof = prove_with_seed(sample_circuit(), &sample_params(), 3).unwrap();
+    let mut compressed = vec![];
+    write_proof(&proof, &mut compressed, true).unwrap();
+    let mut uncompressed = vec![];
+    write_proof(&proof, &mut uncompressed, false).unwrap();
+    assert_eq!(compressed.len(), 1 + 32 + 64 + 32);
+    assert_eq!(uncompressed.len(), 1 + 64 + 128 + 64);
+    let mut bellman = vec![];
+    proof.write(&mut bellman).unwrap();
+    assert_eq!(compressed[1..], bellman[..]);
+    assert!(read_proof(&mut &compressed[..]).unwrap() == proof);
+    assert!(read_proof(&mut &uncompressed[..]).unwrap() == proof);
+
+    // the flag decides how the points are decoded
+    let mut mislabeled = uncompressed.clone();
+    mislabeled[0] = COMPRESSED;
+    assert!(!matches!(read_proof(&mut &mislabeled[..]), Ok(p) if p == proof));
+    mislabeled[0] = 7;
+    assert!(read_proof(&mut &mislabeled[..]).is_err());
+    let mut infinity = vec![UNCOMPRESSED];
+    write_point(&mut infinity, &G1Affine::zero(), false).unwrap();
+    infinity.extend_from_slice(&uncompressed[65..]);
+    assert!(read_proof(&mut &infinity[..]).is_err());
+    assert!(read_proof(&mut &uncompressed[..uncompressed.len() - 1]).is_err());
+}
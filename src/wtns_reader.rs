@@ -1,9 +1,14 @@
-use byteorder::{ReadBytesExt, LittleEndian};
-use std::io::{Read, Result, ErrorKind, Error};
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use std::fs::File;
+use std::io::{Read, Write, Result, ErrorKind, Error, Seek, SeekFrom, BufReader};
+use std::path::Path;
+use num_bigint::BigUint;
+use crate::parse_error::{AtOffset, OffsetReader, ParseError, ParseResult};
+use crate::utils::{engine_curve, field_from_digits, field_modulus, fr_from_le_bytes};
 use bellman_ce::pairing::{
     Engine,
     ff::{
-        Field, PrimeField, PrimeFieldRepr,
+        PrimeField, PrimeFieldRepr,
     }
 };
 
@@ -13,33 +18,42 @@ pub struct Header {
     pub witness_len: u32,
 }
 
+impl Header {
+    /// The prime of the field the witness was computed in
+    pub fn prime(&self) -> BigUint {
+        BigUint::from_bytes_le(&self.prime_size)
+    }
+}
+
 pub struct WTNSFile<E: Engine> {
     pub version: u32,
     pub header: Header,
     pub witness: Vec<E::Fr>,
 }
 
-fn read_field<R: Read, E: Engine>(mut reader: R) -> Result<E::Fr> {
-    let mut repr = E::Fr::zero().into_repr();
-    repr.read_le(&mut reader)?;
-    let fr = E::Fr::from_repr(repr)
-        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
-    Ok(fr)
+fn read_field<R: Read, E: Engine>(mut reader: R, header: &Header) -> Result<E::Fr> {
+    // the header is checked to declare 32-byte fields before any element is read
+    let mut bytes = [0u8; 32];
+    reader.read_exact(&mut bytes)?;
+    fr_from_le_bytes(&bytes, header.field_size as usize).map_err(|e| match e {
+        ParseError::Malformed { reason, .. } => Error::new(ErrorKind::InvalidData, reason),
+        e => e.into(),
+    })
 }
 
-fn read_header<R: Read>(mut reader: R, size: u64) -> Result<Header> {
-    let field_size = reader.read_u32::<LittleEndian>()?;
-    let mut prime_size = vec![0u8; field_size as usize];
-    reader.read_exact(&mut prime_size)?;
-    //if size != 32 + field_size as u64 {
-    if size != 4 + 32 + 4 {
-        return Err(Error::new(ErrorKind::InvalidData, "Invalid header section size"))
+fn read_header<R: Read>(reader: &mut OffsetReader<R>) -> ParseResult<Header> {
+    let offset = reader.offset;
+    let field_size = reader.read_u32::<LittleEndian>().at(reader.offset)?;
+    if field_size != 32 {
+        return Err(ParseError::FieldSizeMismatch { offset, field_size })
     }
+    let mut prime_size = vec![0u8; field_size as usize];
+    reader.read_exact(&mut prime_size).at(reader.offset)?;
 
     Ok(Header {
         field_size,
         prime_size,
-        witness_len: reader.read_u32::<LittleEndian>()?,
+        witness_len: reader.read_u32::<LittleEndian>().at(reader.offset)?,
     })
 }
 
@@ -49,46 +63,374 @@ fn read_witness<R: Read, E:Engine>(mut reader: R, size: u64, header: &Header) ->
     }
     let mut result = Vec::with_capacity(header.witness_len as usize);
     for _ in 0..header.witness_len {
-        result.push(read_field::<&mut R, E>(&mut reader)?);
+        result.push(read_field::<&mut R, E>(&mut reader, header)?);
     }
     Ok(result)
 }
 
-pub fn read<E: Engine, R: Read>(mut reader: R) -> Result<WTNSFile<E>> {
+fn read_preamble<R: Read>(reader: &mut OffsetReader<R>) -> ParseResult<(u32, Header)> {
+    let start = reader.offset;
     let mut magic = [0u8; 4];
-    reader.read_exact(&mut magic)?;
+    reader.read_exact(&mut magic).at(reader.offset)?;
     if magic != [119, 116, 110, 115] { // magic = "wtns"
-        return Err(Error::new(ErrorKind::InvalidData, "Invalid magic number"))
+        return Err(ParseError::BadMagic { offset: start })
     }
 
-    let version = reader.read_u32::<LittleEndian>()?;
+    let version = reader.read_u32::<LittleEndian>().at(reader.offset)?;
     if version > 2 {
-        return Err(Error::new(ErrorKind::InvalidData, "Unsupported version"))
+        return Err(ParseError::UnsupportedVersion { offset: start + 4, version })
+    }
+
+    // version 1 has no section table, the header fields directly follow the version
+    if version == 1 {
+        return Ok((version, read_header(reader)?));
     }
 
-    let _num_sections = reader.read_u32::<LittleEndian>()?;
+    let _num_sections = reader.read_u32::<LittleEndian>().at(reader.offset)?;
 
     // todo: rewrite this to support different section order and unknown sections
-    // todo: handle sec_size correctly
-    let sec_type = reader.read_u32::<LittleEndian>()?;
+    let sec_type = reader.read_u32::<LittleEndian>().at(reader.offset)?;
     if sec_type != 1 {
-        return Err(Error::new(ErrorKind::InvalidData, "Invalid section type"));
+        return Err(ParseError::malformed(reader.offset - 4, "Invalid section type"));
+    }
+    let sec_size = reader.read_u64::<LittleEndian>().at(reader.offset)?;
+    let header_offset = reader.offset;
+    let header = read_header(reader)?;
+    if sec_size != 4 + 32 + 4 {
+        return Err(ParseError::malformed(header_offset, "Invalid header section size"))
     }
-    let sec_size = reader.read_u64::<LittleEndian>()?;
-    let header = read_header(&mut reader, sec_size)?;
-    if header.field_size != 32 {
-        return Err(Error::new(ErrorKind::InvalidData, "This parser only supports 32-byte fields"))
+
+    Ok((version, header))
+}
+
+// Offset of the prime: after the magic and version, then for version 2 the section count and
+// the header section type and size, then the field size
+fn prime_offset(version: u32) -> u64 {
+    if version == 1 {
+        8 + 4
+    } else {
+        12 + 12 + 4
     }
-    if header.prime_size != hex!("010000f093f5e1439170b97948e833285d588181b64550b829a031e1724e6430") {
-        return Err(Error::new(ErrorKind::InvalidData, "This parser only supports bn256"))
+}
+
+fn check_prime<E: Engine>(header: &Header, offset: u64) -> ParseResult<()> {
+    if engine_curve::<E>().map(|curve| curve.scalar_modulus()) != Some(header.prime()) {
+        return Err(ParseError::malformed(
+            offset,
+            format!("Witness prime 0x{:x} doesn't match the engine", header.prime()),
+        ))
     }
+    Ok(())
+}
 
-    let sec_type = reader.read_u32::<LittleEndian>()?;
+fn read_witness_section_size<R: Read>(reader: &mut OffsetReader<R>, version: u32, header: &Header) -> ParseResult<u64> {
+    if version == 1 {
+        return Ok(header.witness_len as u64 * header.field_size as u64);
+    }
+    let sec_type = reader.read_u32::<LittleEndian>().at(reader.offset)?;
     if sec_type != 2 {
-        return Err(Error::new(ErrorKind::InvalidData, "Invalid section type"));
+        return Err(ParseError::malformed(reader.offset - 4, "Invalid section type"));
     }
-    let sec_size = reader.read_u64::<LittleEndian>()?;
-    let witness = read_witness::<&mut R, E>(&mut reader, sec_size, &header)?;
+    reader.read_u64::<LittleEndian>().at(reader.offset)
+}
+
+pub fn read<E: Engine, R: Read>(reader: R) -> ParseResult<WTNSFile<E>> {
+    let mut reader = OffsetReader::new(reader, 0);
+    let (version, header) = read_preamble(&mut reader)?;
+    check_prime::<E>(&header, prime_offset(version))?;
+    let sec_size = read_witness_section_size(&mut reader, version, &header)?;
+    let witness = read_witness::<_, E>(&mut reader, sec_size, &header).at(reader.offset)?;
 
     Ok(WTNSFile { version, header, witness })
 }
+
+/// Reads only witness values `1..=num_public` (the public outputs and inputs),
+/// seeking past the constant one wire instead of decoding the whole witness.
+pub fn read_public<E: Engine, R: Read + Seek>(mut reader: R, num_public: usize) -> ParseResult<Vec<E::Fr>> {
+    let start = reader.stream_position().at(0)?;
+    let mut reader = OffsetReader::new(&mut reader, start);
+    let (version, header) = read_preamble(&mut reader)?;
+    check_prime::<E>(&header, start + prime_offset(version))?;
+    if num_public + 1 > header.witness_len as usize {
+        return Err(ParseError::malformed(reader.offset, format!(
+            "Requested {} public values but witness only has {} elements", num_public, header.witness_len
+        )));
+    }
+    read_witness_section_size(&mut reader, version, &header)?;
+    reader.seek(SeekFrom::Current(header.field_size as i64)).at(reader.offset)?;
+    let mut result = Vec::with_capacity(num_public);
+    for _ in 0..num_public {
+        result.push(read_field::<_, E>(&mut reader, &header).at(reader.offset)?);
+    }
+    Ok(result)
+}
+
+/// Reads the header of a witness file without decoding any values
+pub fn read_file_header<R: Read>(reader: R) -> ParseResult<Header> {
+    Ok(read_preamble(&mut OffsetReader::new(reader, 0))?.1)
+}
+
+/// Reads a snarkjs `witness.json`, a JSON array of decimal strings, into the same values `read`
+/// returns for the binary file. Every value must be below the modulus. JSON has no useful byte
+/// offsets, so errors report offset 0 and the index of the offending value instead.
+pub fn read_witness_json<E: Engine, R: Read>(reader: R) -> ParseResult<Vec<E::Fr>> {
+    let values: Vec<String> = serde_json::from_reader(reader)
+        .map_err(|e| ParseError::malformed(0, format!("Invalid witness JSON: {}", e)))?;
+    values.iter().enumerate().map(|(i, value)| {
+        field_from_digits::<E::Fr>(value, 10).map_err(|e| match e {
+            ParseError::Malformed { reason, .. } => ParseError::malformed(0, format!("Witness value {}: {}", i, reason)),
+            e => e,
+        })
+    }).collect()
+}
+
+/// Writes `witnesses` as a version 2 `.wtns` file that `read` accepts: the header section with
+/// the field size, `field_prime` and the witness length, then the values as little-endian
+/// field elements. `field_prime` must be the modulus of `F`.
+pub fn write_witness<F: PrimeField>(witnesses: &[F], field_prime: &BigUint, writer: &mut impl Write) -> Result<()> {
+    if *field_prime != field_modulus::<F>() {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("Prime 0x{:x} isn't the modulus of the witness field", field_prime)));
+    }
+    // circom sizes fields in whole 64-bit words
+    let field_size = (field_prime.bits() as u32).div_ceil(64) * 8;
+    let mut prime = field_prime.to_bytes_le();
+    prime.resize(field_size as usize, 0);
+
+    writer.write_all(b"wtns")?;
+    writer.write_u32::<LittleEndian>(2)?;
+    writer.write_u32::<LittleEndian>(2)?;
+    writer.write_u32::<LittleEndian>(1)?;
+    writer.write_u64::<LittleEndian>(4 + field_size as u64 + 4)?;
+    writer.write_u32::<LittleEndian>(field_size)?;
+    writer.write_all(&prime)?;
+    writer.write_u32::<LittleEndian>(witnesses.len() as u32)?;
+    writer.write_u32::<LittleEndian>(2)?;
+    writer.write_u64::<LittleEndian>(witnesses.len() as u64 * field_size as u64)?;
+    for value in witnesses {
+        value.into_repr().write_le(&mut *writer)?;
+    }
+    Ok(())
+}
+
+pub fn load_public<E: Engine, P: AsRef<Path>>(path: P, num_public: usize) -> ParseResult<Vec<E::Fr>> {
+    let file = File::open(path).at(0)?;
+    read_public::<E, _>(BufReader::new(file), num_public)
+}
+
+/// Reads the witness files at `paths` and concatenates their values in the order given, e.g.
+/// to combine the witnesses of several circuit instances. Every file must declare the prime of
+/// the first one; errors name the offending file.
+pub fn concat_witnesses<E: Engine>(paths: &[&Path]) -> ParseResult<Vec<E::Fr>> {
+    let mut prime = None;
+    let mut result = vec![];
+    for path in paths {
+        let with_path = |e| match e {
+            ParseError::Malformed { offset, reason } => ParseError::malformed(offset, format!("{}: {}", path.display(), reason)),
+            e => e,
+        };
+        let data = std::fs::read(path).at(0)?;
+        let header = read_file_header(&data[..]).map_err(with_path)?;
+        match &prime {
+            None => prime = Some(header.prime()),
+            Some(prime) if *prime != header.prime() => {
+                return Err(with_path(ParseError::malformed(0, format!(
+                    "Witness prime 0x{:x} differs from 0x{:x} of {}", header.prime(), prime, paths[0].display()
+                ))));
+            }
+            Some(_) => {}
+        }
+        result.extend(read::<E, _>(&data[..]).map_err(with_path)?.witness);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+pub(crate) const SAMPLE_WTNS: [u8; 204] = hex!("
+    77746e73
+    02000000
+    02000000
+    01000000 28000000 00000000
+    20000000
+    010000f0 93f5e143 9170b979 48e83328 5d588181 b64550b8 29a031e1 724e6430
+    04000000
+    02000000 80000000 00000000
+    01000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    0c000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    03000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    04000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+");
+
+/// `SAMPLE_WTNS` in the version 1 layout, which has no sections
+#[cfg(test)]
+const SAMPLE_WTNS_V1: [u8; 176] = hex!("
+    77746e73
+    01000000
+    20000000
+    010000f0 93f5e143 9170b979 48e83328 5d588181 b64550b8 29a031e1 724e6430
+    04000000
+    01000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    0c000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    03000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+    04000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000
+");
+
+#[test]
+fn both_versions() {
+    use std::io::Cursor;
+    use bellman_ce::pairing::{bls12_381::Bls12, bn256::Bn256};
+
+    let v1 = read::<Bn256, _>(&SAMPLE_WTNS_V1[..]).unwrap();
+    let v2 = read::<Bn256, _>(&SAMPLE_WTNS[..]).unwrap();
+    assert_eq!((v1.version, v2.version), (1, 2));
+    assert_eq!(v1.witness, v2.witness);
+    assert_eq!(
+        read_public::<Bn256, _>(Cursor::new(&SAMPLE_WTNS_V1[..]), 2).unwrap(),
+        read_public::<Bn256, _>(Cursor::new(&SAMPLE_WTNS[..]), 2).unwrap(),
+    );
+    assert!(matches!(read::<Bls12, _>(&SAMPLE_WTNS_V1[..]), Err(ParseError::Malformed { offset: 12, .. })));
+    assert!(matches!(
+        read::<Bn256, _>(&SAMPLE_WTNS_V1[..150]),
+        Err(ParseError::TruncatedSection { offset: 150 })
+    ));
+}
+
+#[test]
+fn write_round_trip() {
+    use bellman_ce::pairing::{bls12_381::Bls12, bn256::{Bn256, Fr}, ff::Field};
+
+    let file = read::<Bn256, _>(&SAMPLE_WTNS[..]).unwrap();
+    let mut written = vec![];
+    write_witness(&file.witness, &file.header.prime(), &mut written).unwrap();
+    assert_eq!(written, &SAMPLE_WTNS[..]);
+
+    let mut reordered = file.witness.clone();
+    reordered.swap(1, 3);
+    let mut minus_one = Fr::one();
+    minus_one.negate();
+    reordered.push(minus_one);
+    let mut written = vec![];
+    write_witness(&reordered, &file.header.prime(), &mut written).unwrap();
+    let reread = read::<Bn256, _>(&written[..]).unwrap();
+    assert_eq!(reread.version, 2);
+    assert_eq!(reread.header.witness_len, 5);
+    assert_eq!(reread.witness, reordered);
+
+    let bls_prime = engine_curve::<Bls12>().unwrap().scalar_modulus();
+    assert!(write_witness(&reordered, &bls_prime, &mut vec![]).is_err());
+}
+
+#[test]
+fn concatenated_witnesses() {
+    use bellman_ce::pairing::{bn256::{Bn256, Fr}, ff::Field};
+    use crate::utils::with_temp_files;
+
+    let mut minus_one = Fr::one();
+    minus_one.negate();
+    let values = vec![Fr::one(), Fr::from_str("5").unwrap(), minus_one];
+    let mut written = vec![];
+    write_witness(&values, &field_modulus::<Fr>(), &mut written).unwrap();
+    let mut data = SAMPLE_WTNS.to_vec();
+    data[28..60].copy_from_slice(&hex!("01000000fffffffffe5bfeff02a4bd5305d8a10908d83933487d9d2953a7ed73"));
+
+    let files = [("first.wtns", &SAMPLE_WTNS[..]), ("second.wtns", &written[..]), ("bls.wtns", &data[..])];
+    let (both, reversed, mixed, empty, paths) = with_temp_files("concat", &files, |paths| {
+        let (first, second, bls) = (&paths[0], &paths[1], &paths[2]);
+        (
+            concat_witnesses::<Bn256>(&[first, second]),
+            concat_witnesses::<Bn256>(&[second, first]),
+            concat_witnesses::<Bn256>(&[first, bls]),
+            concat_witnesses::<Bn256>(&[]),
+            paths.to_vec(),
+        )
+    });
+    let (first, bls) = (&paths[0], &paths[2]);
+    let missing = concat_witnesses::<Bn256>(&[first]);
+
+    let both = both.unwrap();
+    assert_eq!(both.len(), 7);
+    assert_eq!(both[..4], read::<Bn256, _>(&SAMPLE_WTNS[..]).unwrap().witness[..]);
+    assert_eq!(both[3], Fr::from_str("4").unwrap());
+    assert_eq!(both[4..], values[..]);
+    let reversed = reversed.unwrap();
+    assert_eq!(reversed[2], minus_one);
+    assert_eq!(reversed[3], Fr::one());
+    assert_eq!(reversed[6], Fr::from_str("4").unwrap());
+    let err = mixed.unwrap_err().to_string();
+    assert!(err.starts_with(&format!("{}: Witness prime 0x73eda753", bls.display())), "{}", err);
+    assert!(err.contains(&first.display().to_string()), "{}", err);
+    assert!(empty.unwrap().is_empty());
+    assert!(matches!(missing, Err(ParseError::Io { .. })));
+}
+
+#[test]
+fn json_witness() {
+    use bellman_ce::pairing::bn256::Bn256;
+
+    let binary = read::<Bn256, _>(&SAMPLE_WTNS[..]).unwrap().witness;
+    let json = read_witness_json::<Bn256, _>(&br#"["1", "12", "3", "4"]"#[..]).unwrap();
+    assert_eq!(json, binary);
+
+    let modulus = "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+    let err = read_witness_json::<Bn256, _>(format!(r#"["1", "{}"]"#, modulus).as_bytes()).err().unwrap();
+    assert!(err.to_string().starts_with("Witness value 1: "), "{}", err);
+    assert!(read_witness_json::<Bn256, _>(&br#"["1", 12]"#[..]).is_err());
+    assert!(read_witness_json::<Bn256, _>(&br#"{"0": "1"}"#[..]).is_err());
+}
+
+#[test]
+fn public_only() {
+    use std::io::Cursor;
+    use bellman_ce::pairing::{bn256::Bn256, ff};
+
+    let file = read::<Bn256, _>(&SAMPLE_WTNS[..]).unwrap();
+    assert_eq!(file.witness.len(), 4);
+
+    let public = read_public::<Bn256, _>(Cursor::new(&SAMPLE_WTNS[..]), 2).unwrap();
+    assert_eq!(public, vec![ff::from_hex("0x0c").unwrap(), ff::from_hex("0x03").unwrap()]);
+    assert_eq!(public[..], file.witness[1..3]);
+
+    assert!(read_public::<Bn256, _>(Cursor::new(&SAMPLE_WTNS[..]), 4).is_err());
+}
+
+#[test]
+fn parse_errors() {
+    use bellman_ce::pairing::bn256::Bn256;
+
+    let mut data = SAMPLE_WTNS.to_vec();
+    data[1] = 0;
+    assert!(matches!(read::<Bn256, _>(&data[..]), Err(ParseError::BadMagic { offset: 0 })));
+    assert!(matches!(
+        read::<Bn256, _>(&SAMPLE_WTNS[..100]),
+        Err(ParseError::TruncatedSection { offset: 100 })
+    ));
+    let mut data = SAMPLE_WTNS.to_vec();
+    data[24] = 16;
+    assert!(matches!(
+        read::<Bn256, _>(&data[..]),
+        Err(ParseError::FieldSizeMismatch { offset: 24, field_size: 16 })
+    ));
+    // the last witness value out of range
+    let mut data = SAMPLE_WTNS.to_vec();
+    data[172..].copy_from_slice(&[0xff; 32]);
+    let err = read::<Bn256, _>(&data[..]).err().unwrap();
+    assert_eq!(err.to_string(), "Field element is not below the modulus at offset 204");
+}
+
+#[test]
+fn witness_prime() {
+    use bellman_ce::pairing::{bls12_381::Bls12, bn256::Bn256};
+    use crate::utils::Curve;
+
+    let header = read_file_header(&SAMPLE_WTNS[..]).unwrap();
+    assert_eq!(header.prime(), Curve::Bn254.scalar_modulus());
+
+    let err = read::<Bls12, _>(&SAMPLE_WTNS[..]).err().unwrap();
+    assert!(matches!(err, ParseError::Malformed { offset: 28, .. }));
+    assert!(err.to_string().starts_with("Witness prime 0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001"));
+
+    let mut data = SAMPLE_WTNS.to_vec();
+    data[28..60].copy_from_slice(&hex!("01000000fffffffffe5bfeff02a4bd5305d8a10908d83933487d9d2953a7ed73"));
+    assert_eq!(read_file_header(&data[..]).unwrap().prime(), Curve::Bls12_381.scalar_modulus());
+    assert_eq!(read::<Bls12, _>(&data[..]).unwrap().witness.len(), 4);
+    assert!(read::<Bn256, _>(&data[..]).is_err());
+}
@@ -0,0 +1,1002 @@
+//! Reader for groth16 `.zkey` proving keys written by snarkjs for the bn128 curve.
+//!
+//! Two things differ from bellman's layout. snarkjs stores `H` in the Lagrange basis over
+//! the odd points of a domain twice the circuit's size; `read` converts it to bellman's
+//! `τ^i t(τ) / δ` basis. snarkjs also derives its roots of unity from 5 rather than 7, so
+//! constraint `j` sits at a different domain point than bellman's `prepare_prover` would put
+//! it. Proofs for such parameters have to be made with `circom_circuit::prove_zkey`.
+//!
+//! For keys too large to hold in memory, `load_streaming` checks a `.zkey` and keeps only its
+//! verifying key and `H`; `circom_circuit::prove_zkey_streaming` then reads the other queries
+//! from the file. `write_streaming` writes parameters in `read`'s layout back out as a `.zkey`.
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use std::{fs::File, io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write}, path::{Path, PathBuf}, sync::Arc};
+use bellman_ce::{
+    domain::{EvaluationDomain, Point},
+    groth16::{ParameterSource, Parameters, QueryDensities, VerifyingKey},
+    pairing::{
+        bn256::{Bn256, Fq, Fq2, Fr, G1, G1Affine, G2Affine},
+        ff::{Field, PrimeField, PrimeFieldRepr},
+        CurveAffine, CurveProjective,
+    },
+    source::{DensityTracker, QueryDensity, Source, SourceBuilder},
+    worker::Worker,
+    SynthesisError,
+};
+use crate::circom_circuit::{effective_constraint_count, R1CS};
+use crate::parse_error::{AtOffset, OffsetReader, ParseError, ParseResult};
+use crate::sections::{self, Sections};
+use crate::utils::in_prime_order_subgroup;
+#[cfg(test)]
+use rand::{Rand, Rng};
+#[cfg(test)]
+use std::io::Cursor;
+#[cfg(test)]
+use crate::circom_circuit::{prove_zkey, prove_zkey_streaming, seeded_rng, verify, CircomCircuit, R1CSBuilder};
+
+const PROTOCOL_TYPE: u32 = 1;
+const GROTH16_HEADER_TYPE: u32 = 2;
+const IC_TYPE: u32 = 3;
+const COEFS_TYPE: u32 = 4;
+const A_TYPE: u32 = 5;
+const B1_TYPE: u32 = 6;
+const B2_TYPE: u32 = 7;
+const C_TYPE: u32 = 8;
+const H_TYPE: u32 = 9;
+
+const GROTH16_PROTOCOL: u32 = 1;
+const G1_SIZE: u64 = 64;
+const G2_SIZE: u64 = 128;
+
+struct Header {
+    n_vars: u32,
+    n_public: u32,
+    domain_size: u32,
+    alpha_g1: G1Affine,
+    beta_g1: G1Affine,
+    beta_g2: G2Affine,
+    gamma_g2: G2Affine,
+    delta_g1: G1Affine,
+    delta_g2: G2Affine,
+}
+
+// snarkjs writes field elements little-endian in Montgomery form
+fn read_fq<R: Read>(mut reader: R) -> Result<Fq> {
+    let mut repr = Fq::zero().into_raw_repr();
+    repr.read_le(&mut reader)?;
+    Fq::from_raw_repr(repr).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+fn read_fq2<R: Read>(mut reader: R) -> Result<Fq2> {
+    Ok(Fq2 { c0: read_fq(&mut reader)?, c1: read_fq(&mut reader)? })
+}
+
+fn read_g1<R: Read>(mut reader: R) -> Result<G1Affine> {
+    let x = read_fq(&mut reader)?;
+    let y = read_fq(&mut reader)?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1Affine::zero())
+    }
+    G1Affine::from_xy_checked(x, y).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+// Points of a loaded `StreamedZkey` were checked when it was indexed
+fn read_g1_unchecked<R: Read>(reader: &mut R) -> Result<G1Affine> {
+    let x = read_fq(&mut *reader)?;
+    let y = read_fq(&mut *reader)?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1Affine::zero())
+    }
+    Ok(G1Affine::from_xy_unchecked(x, y))
+}
+
+fn read_g2_unchecked<R: Read>(reader: &mut R) -> Result<G2Affine> {
+    let x = read_fq2(&mut *reader)?;
+    let y = read_fq2(&mut *reader)?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(G2Affine::zero())
+    }
+    Ok(G2Affine::from_xy_unchecked(x, y))
+}
+
+// both coordinates are stored as (c0, c1)
+fn read_g2<R: Read>(mut reader: R) -> Result<G2Affine> {
+    let x = read_fq2(&mut reader)?;
+    let y = read_fq2(&mut reader)?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(G2Affine::zero())
+    }
+    let p = G2Affine::from_xy_checked(x, y).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    // being on the curve doesn't put a G2 point in the prime order subgroup
+    if !in_prime_order_subgroup(&p) {
+        return Err(Error::new(ErrorKind::InvalidData, "G2 point is not in the prime-order subgroup"))
+    }
+    Ok(p)
+}
+
+fn read_sections<R: Read + Seek>(reader: &mut OffsetReader<R>) -> ParseResult<Sections> {
+    sections::read_sections(reader, b"zkey", &[1])
+}
+
+fn read_header<R: Read + Seek>(reader: &mut OffsetReader<R>, sections: &Sections) -> ParseResult<Header> {
+    let (offset, _) = sections.get(PROTOCOL_TYPE)?;
+    reader.seek(SeekFrom::Start(offset)).at(offset)?;
+    let protocol = reader.read_u32::<LittleEndian>().at(reader.offset)?;
+    if protocol != GROTH16_PROTOCOL {
+        return Err(ParseError::malformed(offset, format!("Unsupported protocol {}, only groth16 zkeys can be read", protocol)))
+    }
+
+    let (offset, size) = sections.get(GROTH16_HEADER_TYPE)?;
+    reader.seek(SeekFrom::Start(offset)).at(offset)?;
+    for modulus in [Fq::char().as_ref(), Fr::char().as_ref()].iter() {
+        let field_offset = reader.offset;
+        let field_size = reader.read_u32::<LittleEndian>().at(reader.offset)?;
+        if field_size != 32 {
+            return Err(ParseError::FieldSizeMismatch { offset: field_offset, field_size })
+        }
+        let mut prime = Fr::zero().into_repr();
+        prime.read_le(&mut *reader).at(reader.offset)?;
+        if prime.as_ref() != *modulus {
+            return Err(ParseError::malformed(field_offset + 4, "zkey isn't for the bn128 curve"))
+        }
+    }
+    let n_vars = reader.read_u32::<LittleEndian>().at(reader.offset)?;
+    let n_public = reader.read_u32::<LittleEndian>().at(reader.offset)?;
+    let domain_offset = reader.offset;
+    let domain_size = reader.read_u32::<LittleEndian>().at(reader.offset)?;
+    // H is defined over a domain twice this size, which needs a root of unity as well
+    if !domain_size.is_power_of_two() || domain_size < 2 || domain_size.trailing_zeros() >= Fr::S {
+        return Err(ParseError::malformed(domain_offset, format!("Invalid domain size {}", domain_size)))
+    }
+    if n_public >= n_vars {
+        return Err(ParseError::malformed(domain_offset - 4, format!("{} public signals for {} variables", n_public, n_vars)))
+    }
+    let header = Header {
+        n_vars,
+        n_public,
+        domain_size,
+        alpha_g1: read_g1(&mut *reader).at(reader.offset)?,
+        beta_g1: read_g1(&mut *reader).at(reader.offset)?,
+        beta_g2: read_g2(&mut *reader).at(reader.offset)?,
+        gamma_g2: read_g2(&mut *reader).at(reader.offset)?,
+        delta_g1: read_g1(&mut *reader).at(reader.offset)?,
+        delta_g2: read_g2(&mut *reader).at(reader.offset)?,
+    };
+    if reader.offset != offset + size {
+        return Err(ParseError::malformed(reader.offset, "Invalid groth16 header section size"))
+    }
+    Ok(header)
+}
+
+/// Reads the `count` points of a section one at a time, returning the offset of the section
+fn for_each_point<R: Read + Seek, G, F, V>(
+    reader: &mut OffsetReader<R>,
+    sections: &Sections,
+    sec_type: u32,
+    count: usize,
+    point_size: u64,
+    mut read_point: F,
+    mut visit: V,
+) -> ParseResult<u64>
+where
+    F: FnMut(&mut OffsetReader<R>) -> Result<G>,
+    V: FnMut(G),
+{
+    let (offset, size) = sections.get(sec_type)?;
+    if size != count as u64 * point_size {
+        return Err(ParseError::malformed(offset, format!("Invalid size of section {}", sec_type)))
+    }
+    reader.seek(SeekFrom::Start(offset)).at(offset)?;
+    for _ in 0..count {
+        visit(read_point(&mut *reader).at(reader.offset)?);
+    }
+    Ok(offset)
+}
+
+fn read_points<R: Read + Seek, G, F>(
+    reader: &mut OffsetReader<R>,
+    sections: &Sections,
+    sec_type: u32,
+    count: usize,
+    point_size: u64,
+    read_point: F,
+) -> ParseResult<Vec<G>>
+where
+    F: FnMut(&mut OffsetReader<R>) -> Result<G>,
+{
+    // the section size is checked against `count` before anything is read
+    let mut points = Vec::new();
+    for_each_point(reader, sections, sec_type, count, point_size, read_point, |p| points.push(p))?;
+    Ok(points)
+}
+
+/// snarkjs's root of unity of order `2^exp`, a power of the smallest quadratic non-residue 5
+fn snarkjs_root(exp: u32) -> Fr {
+    let mut t = Fr::char();
+    t.sub_noborrow(&1.into());
+    t.shr(Fr::S);
+    let mut root = Fr::from_str("5").unwrap().pow(t);
+    for _ in exp..Fr::S {
+        root.square();
+    }
+    root
+}
+
+/// The `k` for which snarkjs's root of unity of order `domain_size` is the `k`-th power of
+/// bellman's, so snarkjs's constraint `j` is bellman's domain point `j * k % domain_size`.
+/// `domain_size` must be a power of two no larger than `2^28`.
+pub fn snarkjs_root_power(domain_size: usize) -> usize {
+    assert!(domain_size.is_power_of_two() && domain_size.trailing_zeros() <= Fr::S);
+    // both roots of order 2^S generate the same group, so find the discrete log bit by bit
+    let target = snarkjs_root(Fr::S);
+    let base = Fr::root_of_unity();
+    let mut k = 0u64;
+    for i in 0..Fr::S {
+        let mut x = base.pow([k]).inverse().unwrap();
+        x.mul_assign(&target);
+        for _ in i + 1..Fr::S {
+            x.square();
+        }
+        if x != Fr::one() {
+            k |= 1 << i;
+        }
+    }
+    (k % domain_size as u64) as usize
+}
+
+/// Converts snarkjs's `H_i = L_{2i+1}(τ) / δ`, with `L` the Lagrange basis over the domain of
+/// size `2n` with root `g`, to bellman's `τ^k t(τ) / δ` for `k < n - 1`. Evaluating
+/// `x^k t(x)` at the points of that domain gives `τ^k t(τ) = -2 g^k Σ_i g^{2ik} L_{2i+1}(τ)`.
+fn h_query(points: &[G1Affine]) -> Vec<G1Affine> {
+    let n = points.len();
+    let exp = n.trailing_zeros();
+    let worker = Worker::new();
+    let coeffs = points.iter().map(|p| Point(p.into_projective())).collect();
+    let mut domain = EvaluationDomain::<Bn256, Point<G1>>::from_coeffs(coeffs)
+        .expect("domain size is checked when reading the header");
+    // bellman's FFT sums over its own root, snarkjs's g^2 is that root to the power `k`
+    domain.fft(&worker);
+    let sums = domain.into_coeffs();
+    let k = snarkjs_root_power(n);
+    let g = snarkjs_root(exp + 1);
+    let mut scale = Fr::from_str("2").unwrap();
+    scale.negate();
+    let mut h = Vec::with_capacity(n - 1);
+    for i in 0..n - 1 {
+        let mut p = sums[i * k % n].0;
+        p.mul_assign(scale.into_repr());
+        h.push(p);
+        scale.mul_assign(&g);
+    }
+    G1::batch_normalization(&mut h);
+    h.into_iter().map(|p| p.into_affine()).collect()
+}
+
+/// Inverse of `h_query`, from bellman's `n - 1` points back to snarkjs's `n`. Writing
+/// `h_k = τ^k t(τ) / δ`, `h_query` computes `h_k = -2 g^k F_{kK}` with `F` the FFT of snarkjs's
+/// points over bellman's root and `K` the `snarkjs_root_power`. As `K` is odd, `k -> kK` runs
+/// over the whole domain, so setting `F_{kK} = -h_k / (2 g^k)`, and zero for `k = n - 1`, and
+/// taking the inverse FFT gives points `h_query` maps back to `h`.
+fn snarkjs_h_query(h: &[G1Affine]) -> Vec<G1Affine> {
+    let n = h.len() + 1;
+    let exp = n.trailing_zeros();
+    let worker = Worker::new();
+    let k = snarkjs_root_power(n);
+    let g_inv = snarkjs_root(exp + 1).inverse().unwrap();
+    let mut scale = Fr::from_str("2").unwrap().inverse().unwrap();
+    scale.negate();
+    let mut coeffs = vec![Point(G1::zero()); n];
+    for (i, p) in h.iter().enumerate() {
+        coeffs[i * k % n] = Point(p.mul(scale.into_repr()));
+        scale.mul_assign(&g_inv);
+    }
+    let mut domain = EvaluationDomain::<Bn256, Point<G1>>::from_coeffs(coeffs)
+        .expect("domain size is checked by the caller");
+    domain.ifft(&worker);
+    let mut points = domain.into_coeffs().into_iter().map(|p| p.0).collect::<Vec<_>>();
+    G1::batch_normalization(&mut points);
+    points.into_iter().map(|p| p.into_affine()).collect()
+}
+
+fn verifying_key(header: &Header, ic: Vec<G1Affine>) -> VerifyingKey<Bn256> {
+    VerifyingKey {
+        alpha_g1: header.alpha_g1,
+        beta_g1: header.beta_g1,
+        beta_g2: header.beta_g2,
+        gamma_g2: header.gamma_g2,
+        delta_g1: header.delta_g1,
+        delta_g2: header.delta_g2,
+        ic,
+    }
+}
+
+/// Reads a groth16 `.zkey` into bellman parameters and the verifying key they contain.
+/// Every point is checked to be on the curve and in the right subgroup.
+pub fn read<R: Read + Seek>(mut reader: R) -> ParseResult<(Parameters<Bn256>, VerifyingKey<Bn256>)> {
+    let start = reader.stream_position().at(0)?;
+    let mut reader = OffsetReader::new(&mut reader, start);
+    let sections = read_sections(&mut reader)?;
+    let header = read_header(&mut reader, &sections)?;
+    let n_vars = header.n_vars as usize;
+    let n_public = header.n_public as usize;
+
+    let ic = read_points(&mut reader, &sections, IC_TYPE, n_public + 1, G1_SIZE, |r| read_g1(r))?;
+    let a = read_points(&mut reader, &sections, A_TYPE, n_vars, G1_SIZE, |r| read_g1(r))?;
+    let b_g1 = read_points(&mut reader, &sections, B1_TYPE, n_vars, G1_SIZE, |r| read_g1(r))?;
+    let b_g2 = read_points(&mut reader, &sections, B2_TYPE, n_vars, G2_SIZE, |r| read_g2(r))?;
+    let l = read_points(&mut reader, &sections, C_TYPE, n_vars - n_public - 1, G1_SIZE, |r| read_g1(r))?;
+    let h = read_points(&mut reader, &sections, H_TYPE, header.domain_size as usize, G1_SIZE, |r| read_g1(r))?;
+    let h = h_query(&h);
+
+    let vk = verifying_key(&header, ic);
+    let params = Parameters {
+        vk: vk.clone(),
+        h: Arc::new(h),
+        l: Arc::new(l),
+        a: Arc::new(a),
+        b_g1: Arc::new(b_g1),
+        b_g2: Arc::new(b_g2),
+    };
+    Ok((params, vk))
+}
+
+fn write_fq<W: Write>(mut writer: W, fq: &Fq) -> Result<()> {
+    fq.into_raw_repr().write_le(&mut writer)
+}
+
+fn write_g1<W: Write>(mut writer: W, p: &G1Affine) -> Result<()> {
+    let (x, y) = if p.is_zero() { (Fq::zero(), Fq::zero()) } else { p.into_xy_unchecked() };
+    write_fq(&mut writer, &x)?;
+    write_fq(&mut writer, &y)
+}
+
+fn write_g2<W: Write>(mut writer: W, p: &G2Affine) -> Result<()> {
+    let (x, y) = if p.is_zero() { (Fq2::zero(), Fq2::zero()) } else { p.into_xy_unchecked() };
+    for c in [x.c0, x.c1, y.c0, y.c1].iter() {
+        write_fq(&mut writer, c)?;
+    }
+    Ok(())
+}
+
+// snarkjs's prover multiplies the coefficients with the witness in Montgomery form, so they are
+// stored as the Montgomery form of `c R`
+fn write_coef<W: Write>(mut writer: W, coeff: &Fr) -> Result<()> {
+    let r = Fr::from_repr(Fr::one().into_raw_repr()).unwrap();
+    let mut c = *coeff;
+    c.mul_assign(&r);
+    c.into_raw_repr().write_le(&mut writer)
+}
+
+fn write_section_header<W: Write>(mut writer: W, sec_type: u32, size: u64) -> Result<()> {
+    writer.write_u32::<LittleEndian>(sec_type)?;
+    writer.write_u64::<LittleEndian>(size)
+}
+
+/// Writes parameters in the layout `read` returns them in, with a point in `A`, `B1` and `B2`
+/// for every variable of `r1cs`, as a `.zkey`. Points are written out as they are encoded;
+/// only `H` is buffered, to convert it back to snarkjs's basis. The ceremony contributions
+/// section is not written, so snarkjs can prove with the file but not verify its setup.
+pub fn write_streaming<W: Write>(params: &Parameters<Bn256>, r1cs: &R1CS<Bn256>, writer: W) -> Result<()> {
+    let n_vars = r1cs.num_variables;
+    let num_inputs = r1cs.num_inputs;
+    let domain_size = params.h.len() + 1;
+    if num_inputs == 0
+        || domain_size < 2
+        || effective_constraint_count(r1cs).next_power_of_two() != domain_size
+        || [params.a.len(), params.b_g1.len(), params.b_g2.len()].iter().any(|len| *len != n_vars)
+        || params.l.len() + num_inputs != n_vars
+        || params.vk.ic.len() != num_inputs
+    {
+        return Err(Error::new(ErrorKind::InvalidInput, "parameters don't have a point for every variable of the circuit"))
+    }
+    // the A and B terms of every constraint, then the `x * 0 = 0` constraint of every input
+    let coefs = || {
+        r1cs.constraints.iter().enumerate().flat_map(|(j, constraint)| {
+            let a = constraint.a.iter().map(move |(i, coeff)| (0u32, j, *i, *coeff));
+            a.chain(constraint.b.iter().map(move |(i, coeff)| (1u32, j, *i, *coeff)))
+        })
+        .chain((0..num_inputs).map(|s| (0u32, r1cs.constraints.len() + s, s, Fr::one())))
+    };
+    let num_coefs = coefs().count();
+    let vk = &params.vk;
+
+    let mut writer = BufWriter::new(writer);
+    writer.write_all(b"zkey")?;
+    writer.write_u32::<LittleEndian>(1)?;
+    writer.write_u32::<LittleEndian>(9)?;
+    write_section_header(&mut writer, PROTOCOL_TYPE, 4)?;
+    writer.write_u32::<LittleEndian>(GROTH16_PROTOCOL)?;
+
+    write_section_header(&mut writer, GROTH16_HEADER_TYPE, 2 * (4 + 32) + 3 * 4 + 3 * G1_SIZE + 3 * G2_SIZE)?;
+    for modulus in [Fq::char().as_ref(), Fr::char().as_ref()].iter() {
+        writer.write_u32::<LittleEndian>(32)?;
+        for limb in modulus.iter() {
+            writer.write_u64::<LittleEndian>(*limb)?;
+        }
+    }
+    for x in [n_vars, num_inputs - 1, domain_size].iter() {
+        writer.write_u32::<LittleEndian>(*x as u32)?;
+    }
+    write_g1(&mut writer, &vk.alpha_g1)?;
+    write_g1(&mut writer, &vk.beta_g1)?;
+    write_g2(&mut writer, &vk.beta_g2)?;
+    write_g2(&mut writer, &vk.gamma_g2)?;
+    write_g1(&mut writer, &vk.delta_g1)?;
+    write_g2(&mut writer, &vk.delta_g2)?;
+
+    write_section_header(&mut writer, IC_TYPE, vk.ic.len() as u64 * G1_SIZE)?;
+    for p in vk.ic.iter() {
+        write_g1(&mut writer, p)?;
+    }
+    write_section_header(&mut writer, COEFS_TYPE, 4 + num_coefs as u64 * (3 * 4 + 32))?;
+    writer.write_u32::<LittleEndian>(num_coefs as u32)?;
+    for (matrix, j, i, coeff) in coefs() {
+        for x in [matrix, j as u32, i as u32].iter() {
+            writer.write_u32::<LittleEndian>(*x)?;
+        }
+        write_coef(&mut writer, &coeff)?;
+    }
+    for (sec_type, points) in [(A_TYPE, &params.a), (B1_TYPE, &params.b_g1)].iter() {
+        write_section_header(&mut writer, *sec_type, points.len() as u64 * G1_SIZE)?;
+        for p in points.iter() {
+            write_g1(&mut writer, p)?;
+        }
+    }
+    write_section_header(&mut writer, B2_TYPE, n_vars as u64 * G2_SIZE)?;
+    for p in params.b_g2.iter() {
+        write_g2(&mut writer, p)?;
+    }
+    write_section_header(&mut writer, C_TYPE, params.l.len() as u64 * G1_SIZE)?;
+    for p in params.l.iter() {
+        write_g1(&mut writer, p)?;
+    }
+    write_section_header(&mut writer, H_TYPE, domain_size as u64 * G1_SIZE)?;
+    for p in snarkjs_h_query(&params.h).iter() {
+        write_g1(&mut writer, p)?;
+    }
+    writer.flush()
+}
+
+// indices, shifted by `offset`, of the variables marked in `tracker`
+fn used(tracker: &DensityTracker, offset: usize) -> impl Iterator<Item = u32> + '_ {
+    QueryDensity::iter(tracker).enumerate().filter(|(_, used)| *used).map(move |(i, _)| (offset + i) as u32)
+}
+
+/// A `.zkey` indexed by `load_streaming`. Only the verifying key and `H`, which has to be
+/// converted from snarkjs's basis as a whole, are held in memory; the `A`, `B1`, `B2` and `C`
+/// points are read from the file while proving with `circom_circuit::prove_zkey_streaming`.
+pub struct StreamedZkey {
+    path: Arc<PathBuf>,
+    vk: VerifyingKey<Bn256>,
+    n_vars: usize,
+    h: Arc<Vec<G1Affine>>,
+    // offsets of the A, B1, B2 and C sections
+    a: u64,
+    b_g1: u64,
+    b_g2: u64,
+    c: u64,
+}
+
+/// Indexes the `.zkey` at `path`. Every point is checked as by `read`, but one at a time, so
+/// apart from `H` no query is held in memory.
+pub fn load_streaming<P: AsRef<Path>>(path: P) -> ParseResult<StreamedZkey> {
+    let path = path.as_ref().to_path_buf();
+    let mut reader = OffsetReader::new(BufReader::new(File::open(&path).at(0)?), 0);
+    let sections = read_sections(&mut reader)?;
+    let header = read_header(&mut reader, &sections)?;
+    let n_vars = header.n_vars as usize;
+    let n_public = header.n_public as usize;
+
+    let ic = read_points(&mut reader, &sections, IC_TYPE, n_public + 1, G1_SIZE, |r| read_g1(r))?;
+    let a = for_each_point(&mut reader, &sections, A_TYPE, n_vars, G1_SIZE, |r| read_g1(r), drop)?;
+    let b_g1 = for_each_point(&mut reader, &sections, B1_TYPE, n_vars, G1_SIZE, |r| read_g1(r), drop)?;
+    let b_g2 = for_each_point(&mut reader, &sections, B2_TYPE, n_vars, G2_SIZE, |r| read_g2(r), drop)?;
+    let c = for_each_point(&mut reader, &sections, C_TYPE, n_vars - n_public - 1, G1_SIZE, |r| read_g1(r), drop)?;
+    let h = read_points(&mut reader, &sections, H_TYPE, header.domain_size as usize, G1_SIZE, |r| read_g1(r))?;
+
+    Ok(StreamedZkey {
+        path: Arc::new(path),
+        vk: verifying_key(&header, ic),
+        n_vars,
+        h: Arc::new(h_query(&h)),
+        a,
+        b_g1,
+        b_g2,
+        c,
+    })
+}
+
+impl StreamedZkey {
+    pub fn vk(&self) -> &VerifyingKey<Bn256> {
+        &self.vk
+    }
+
+    pub fn num_variables(&self) -> usize {
+        self.n_vars
+    }
+
+    pub fn domain_size(&self) -> usize {
+        self.h.len() + 1
+    }
+
+    /// The queries for a circuit with `num_inputs` public wires whose constraints use the
+    /// variables marked in `densities`, as a `ParameterSource`. Only their points are read.
+    pub fn parameters(&self, num_inputs: usize, densities: &QueryDensities) -> ZkeyParameters<'_> {
+        let a = (0..num_inputs as u32).chain(used(&densities.a_aux, num_inputs)).collect();
+        let b = used(&densities.b_input, 0).chain(used(&densities.b_aux, num_inputs)).collect();
+        let c = (0..(self.n_vars - num_inputs) as u32).collect();
+        ZkeyParameters { zkey: self, a: Arc::new(a), b: Arc::new(b), c: Arc::new(c) }
+    }
+
+    fn query<G: CurveAffine>(
+        &self,
+        offset: u64,
+        size: u64,
+        read: fn(&mut BufReader<File>) -> Result<G>,
+        positions: &Arc<Vec<u32>>,
+        start: usize,
+    ) -> ZkeySourceBuilder<G> {
+        let points = QueryPoints::File { path: self.path.clone(), offset, size, read, positions: positions.clone() };
+        ZkeySourceBuilder { points, start }
+    }
+}
+
+/// The queries of a `StreamedZkey` for one circuit, see `StreamedZkey::parameters`
+pub struct ZkeyParameters<'a> {
+    zkey: &'a StreamedZkey,
+    // indices of the points of the A, B1/B2 and C sections the queries are made of
+    a: Arc<Vec<u32>>,
+    b: Arc<Vec<u32>>,
+    c: Arc<Vec<u32>>,
+}
+
+impl ParameterSource<Bn256> for ZkeyParameters<'_> {
+    type G1Builder = ZkeySourceBuilder<G1Affine>;
+    type G2Builder = ZkeySourceBuilder<G2Affine>;
+
+    fn get_vk(&mut self, _: usize) -> std::result::Result<VerifyingKey<Bn256>, SynthesisError> {
+        Ok(self.zkey.vk.clone())
+    }
+
+    fn get_h(&mut self, _: usize) -> std::result::Result<Self::G1Builder, SynthesisError> {
+        Ok(ZkeySourceBuilder { points: QueryPoints::Memory(self.zkey.h.clone()), start: 0 })
+    }
+
+    fn get_l(&mut self, _: usize) -> std::result::Result<Self::G1Builder, SynthesisError> {
+        Ok(self.zkey.query(self.zkey.c, G1_SIZE, read_g1_unchecked, &self.c, 0))
+    }
+
+    fn get_a(
+        &mut self,
+        num_inputs: usize,
+        num_aux: usize,
+    ) -> std::result::Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        // Same consistency check as for in-memory `Parameters`
+        if self.a.len() != num_inputs + num_aux {
+            return Err(SynthesisError::InconsistentParameters)
+        }
+        let offset = self.zkey.a;
+        Ok((
+            self.zkey.query(offset, G1_SIZE, read_g1_unchecked, &self.a, 0),
+            self.zkey.query(offset, G1_SIZE, read_g1_unchecked, &self.a, num_inputs),
+        ))
+    }
+
+    fn get_b_g1(
+        &mut self,
+        num_inputs: usize,
+        _: usize,
+    ) -> std::result::Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        let offset = self.zkey.b_g1;
+        Ok((
+            self.zkey.query(offset, G1_SIZE, read_g1_unchecked, &self.b, 0),
+            self.zkey.query(offset, G1_SIZE, read_g1_unchecked, &self.b, num_inputs),
+        ))
+    }
+
+    fn get_b_g2(
+        &mut self,
+        num_inputs: usize,
+        _: usize,
+    ) -> std::result::Result<(Self::G2Builder, Self::G2Builder), SynthesisError> {
+        let offset = self.zkey.b_g2;
+        Ok((
+            self.zkey.query(offset, G2_SIZE, read_g2_unchecked, &self.b, 0),
+            self.zkey.query(offset, G2_SIZE, read_g2_unchecked, &self.b, num_inputs),
+        ))
+    }
+}
+
+#[derive(Clone)]
+enum QueryPoints<G: CurveAffine> {
+    Memory(Arc<Vec<G>>),
+    // the points at `positions` of the section at `offset`, which holds points of `size` bytes
+    File {
+        path: Arc<PathBuf>,
+        offset: u64,
+        size: u64,
+        read: fn(&mut BufReader<File>) -> Result<G>,
+        positions: Arc<Vec<u32>>,
+    },
+}
+
+impl<G: CurveAffine> QueryPoints<G> {
+    fn len(&self) -> usize {
+        match self {
+            QueryPoints::Memory(points) => points.len(),
+            QueryPoints::File { positions, .. } => positions.len(),
+        }
+    }
+}
+
+/// Hands out readers over one query of a `StreamedZkey`; each multiexp chunk opens its own.
+#[derive(Clone)]
+pub struct ZkeySourceBuilder<G: CurveAffine> {
+    points: QueryPoints<G>,
+    start: usize,
+}
+
+impl<G: CurveAffine> SourceBuilder<G> for ZkeySourceBuilder<G> {
+    type Source = ZkeySource<G>;
+
+    fn new(self) -> ZkeySource<G> {
+        ZkeySource { points: self.points, pos: self.start, reader: None }
+    }
+}
+
+/// Sequential reader over a query. The file is opened on first use, and points of unused
+/// variables are seeked over rather than decoded.
+pub struct ZkeySource<G: CurveAffine> {
+    points: QueryPoints<G>,
+    pos: usize,
+    // Reader together with the index of the point it is positioned at.
+    reader: Option<(BufReader<File>, u32)>,
+}
+
+impl<G: CurveAffine> Source<G> for ZkeySource<G> {
+    fn add_assign_mixed(
+        &mut self,
+        to: &mut <G as CurveAffine>::Projective,
+    ) -> std::result::Result<(), SynthesisError> {
+        if self.points.len() <= self.pos {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "expected more bases when adding from source",
+            )
+            .into());
+        }
+
+        let point = match &self.points {
+            QueryPoints::Memory(points) => points[self.pos],
+            QueryPoints::File { path, offset, size, read, positions } => {
+                if self.reader.is_none() {
+                    self.reader = Some((BufReader::new(File::open(&**path)?), u32::MAX));
+                }
+                let (reader, at) = self.reader.as_mut().unwrap();
+                let index = positions[self.pos];
+                if *at != index {
+                    reader.seek(SeekFrom::Start(offset + index as u64 * size))?;
+                }
+                *at = index + 1;
+                read(reader)?
+            }
+        };
+        if point.is_zero() {
+            return Err(SynthesisError::UnexpectedIdentity);
+        }
+        to.add_assign_mixed(&point);
+
+        self.pos += 1;
+
+        Ok(())
+    }
+
+    fn skip(&mut self, amt: usize) -> std::result::Result<(), SynthesisError> {
+        if self.points.len() <= self.pos {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "expected more bases skipping from source",
+            )
+            .into());
+        }
+
+        self.pos += amt;
+
+        Ok(())
+    }
+}
+
+/// Runs a snarkjs style setup for `r1cs` and writes the resulting `.zkey`: constraint `j`
+/// is placed at the `j`-th power of snarkjs's root, followed by one constraint per input,
+/// and `H` uses the odd points of the domain of twice the size. Only the sections `read`
+/// looks at are written.
+#[cfg(test)]
+fn sample_zkey<R: Rng>(r1cs: &R1CS<Bn256>, rng: &mut R) -> Vec<u8> {
+    let fr = |n: usize| Fr::from_str(&n.to_string()).unwrap();
+    let n_public = r1cs.num_inputs - 1;
+    let rows = r1cs.constraints.len() + r1cs.num_inputs;
+    let n = rows.next_power_of_two();
+    let exp = n.trailing_zeros();
+    let (tau, alpha, beta, gamma, delta) = (Fr::rand(rng), Fr::rand(rng), Fr::rand(rng), Fr::rand(rng), Fr::rand(rng));
+
+    // L_j(τ) = ω^j (τ^size - 1) / (size (τ - ω^j)) over the domain of `size` with root `omega`
+    let lagrange = |size: usize, omega: Fr| {
+        let mut t = tau.pow([size as u64]);
+        t.sub_assign(&Fr::one());
+        t.mul_assign(&fr(size).inverse().unwrap());
+        let mut w = Fr::one();
+        let mut basis = Vec::with_capacity(size);
+        for _ in 0..size {
+            let mut den = tau;
+            den.sub_assign(&w);
+            let mut l = den.inverse().unwrap();
+            l.mul_assign(&w);
+            l.mul_assign(&t);
+            basis.push(l);
+            w.mul_assign(&omega);
+        }
+        basis
+    };
+    let basis = lagrange(n, snarkjs_root(exp));
+    let mut u = vec![Fr::zero(); r1cs.num_variables];
+    let mut v = u.clone();
+    let mut w = u.clone();
+    let mut coefs = vec![];
+    for (j, constraint) in r1cs.constraints.iter().enumerate() {
+        for (matrix, lc, polys) in [(0usize, &constraint.a, &mut u), (1, &constraint.b, &mut v), (2, &constraint.c, &mut w)].iter_mut() {
+            for (i, coeff) in lc.iter() {
+                let mut term = basis[j];
+                term.mul_assign(coeff);
+                polys[*i].add_assign(&term);
+                if *matrix < 2 {
+                    coefs.push((*matrix, j, *i, *coeff));
+                }
+            }
+        }
+    }
+    for (s, u_s) in u.iter_mut().enumerate().take(r1cs.num_inputs) {
+        let j = r1cs.constraints.len() + s;
+        u_s.add_assign(&basis[j]);
+        coefs.push((0, j, s, Fr::one()));
+    }
+
+    let g1 = |x: Fr| G1Affine::one().mul(x.into_repr()).into_affine();
+    let g2 = |x: Fr| G2Affine::one().mul(x.into_repr()).into_affine();
+    // (β u_i + α v_i + w_i) / divisor
+    let combined = |i: usize, divisor: Fr| {
+        let mut x = u[i];
+        x.mul_assign(&beta);
+        let mut y = v[i];
+        y.mul_assign(&alpha);
+        x.add_assign(&y);
+        x.add_assign(&w[i]);
+        x.mul_assign(&divisor.inverse().unwrap());
+        g1(x)
+    };
+    let delta_inv = delta.inverse().unwrap();
+    let odd = lagrange(2 * n, snarkjs_root(exp + 1));
+
+    let mut sections: Vec<(u32, Vec<u8>)> = vec![];
+    let mut protocol = vec![];
+    protocol.extend_from_slice(&GROTH16_PROTOCOL.to_le_bytes());
+    sections.push((PROTOCOL_TYPE, protocol));
+    let mut header = vec![];
+    header.extend_from_slice(&32u32.to_le_bytes());
+    Fq::char().write_le(&mut header).unwrap();
+    header.extend_from_slice(&32u32.to_le_bytes());
+    Fr::char().write_le(&mut header).unwrap();
+    for x in [r1cs.num_variables, n_public, n].iter() {
+        header.extend_from_slice(&(*x as u32).to_le_bytes());
+    }
+    write_g1(&mut header, &g1(alpha)).unwrap();
+    write_g1(&mut header, &g1(beta)).unwrap();
+    write_g2(&mut header, &g2(beta)).unwrap();
+    write_g2(&mut header, &g2(gamma)).unwrap();
+    write_g1(&mut header, &g1(delta)).unwrap();
+    write_g2(&mut header, &g2(delta)).unwrap();
+    sections.push((GROTH16_HEADER_TYPE, header));
+    let mut ic = vec![];
+    for i in 0..=n_public {
+        write_g1(&mut ic, &combined(i, gamma)).unwrap();
+    }
+    sections.push((IC_TYPE, ic));
+    let mut coefs_section = vec![];
+    coefs_section.extend_from_slice(&(coefs.len() as u32).to_le_bytes());
+    for (matrix, j, i, coeff) in coefs.iter() {
+        for x in [*matrix, *j, *i].iter() {
+            coefs_section.extend_from_slice(&(*x as u32).to_le_bytes());
+        }
+        write_coef(&mut coefs_section, coeff).unwrap();
+    }
+    sections.push((COEFS_TYPE, coefs_section));
+    let (mut a, mut b1, mut b2) = (vec![], vec![], vec![]);
+    for i in 0..r1cs.num_variables {
+        write_g1(&mut a, &g1(u[i])).unwrap();
+        write_g1(&mut b1, &g1(v[i])).unwrap();
+        write_g2(&mut b2, &g2(v[i])).unwrap();
+    }
+    sections.push((A_TYPE, a));
+    sections.push((B1_TYPE, b1));
+    sections.push((B2_TYPE, b2));
+    let mut c = vec![];
+    for i in n_public + 1..r1cs.num_variables {
+        write_g1(&mut c, &combined(i, delta)).unwrap();
+    }
+    sections.push((C_TYPE, c));
+    let mut h = vec![];
+    for i in 0..n {
+        let mut x = odd[2 * i + 1];
+        x.mul_assign(&delta_inv);
+        write_g1(&mut h, &g1(x)).unwrap();
+    }
+    sections.push((H_TYPE, h));
+
+    let mut zkey = b"zkey".to_vec();
+    zkey.extend_from_slice(&1u32.to_le_bytes());
+    zkey.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+    for (sec_type, data) in sections {
+        zkey.extend_from_slice(&sec_type.to_le_bytes());
+        zkey.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        zkey.extend_from_slice(&data);
+    }
+    zkey
+}
+
+/// `x * x = y_1`, `y_i * y_i = y_{i+1}`, ..., ending in the public output `y_len`. With
+/// enough constraints the domain is past the sizes where snarkjs and bellman order it alike.
+#[cfg(test)]
+fn square_chain(len: usize) -> CircomCircuit<Bn256> {
+    let one = Fr::one();
+    // 0 is the constant, 1 the output, 2 the input x and 3.. the intermediate squares
+    let wire = |i: usize| if i == len { 1 } else { i + 2 };
+    let mut builder = R1CSBuilder::<Bn256>::new(len + 2);
+    builder.set_public_count(1);
+    let mut witness = vec![one; len + 2];
+    witness[2] = Fr::from_str("3").unwrap();
+    for i in 0..len {
+        builder.add_constraint(vec![(wire(i), one)], vec![(wire(i), one)], vec![(wire(i + 1), one)]);
+        let mut square = witness[wire(i)];
+        square.square();
+        witness[wire(i + 1)] = square;
+    }
+    CircomCircuit { r1cs: builder.build().unwrap(), witness: Some(witness), wire_mapping: None }
+}
+
+#[test]
+fn snarkjs_root_power_matches() {
+    for exp in [1, 5, 6, 10, Fr::S].iter() {
+        let n = 1usize << exp;
+        let k = snarkjs_root_power(n);
+        let mut bellman = Fr::root_of_unity();
+        for _ in *exp..Fr::S {
+            bellman.square();
+        }
+        assert_eq!(bellman.pow([k as u64]), snarkjs_root(*exp));
+    }
+    // the two orders agree up to 32 points and differ from 64 on
+    assert_eq!(snarkjs_root_power(32), 1);
+    assert_eq!(snarkjs_root_power(64), 33);
+}
+
+#[test]
+fn prove_with_zkey() {
+    let mut rng = seeded_rng(270);
+    for len in [1, 70].iter() {
+        let circuit = square_chain(*len);
+        let zkey = sample_zkey(&circuit.r1cs, &mut rng);
+        let (params, vk) = read(Cursor::new(&zkey)).unwrap();
+        assert!(params.vk == vk);
+        assert_eq!(params.h.len() + 1, (len + 2).next_power_of_two());
+
+        let inputs = circuit.get_public_inputs().unwrap();
+        let proof = prove_zkey(circuit.clone(), &params, &mut rng).unwrap();
+        assert!(verify(&params, &proof, &inputs).unwrap());
+        let mut wrong = inputs.clone();
+        wrong[0].add_assign(&Fr::one());
+        assert!(!verify(&params, &proof, &wrong).unwrap());
+    }
+}
+
+#[test]
+fn prove_with_mismatched_zkey() {
+    let mut rng = seeded_rng(2);
+    let circuit = square_chain(1);
+    let other = square_chain(2);
+    let (params, _) = read(Cursor::new(&sample_zkey(&other.r1cs, &mut rng))).unwrap();
+    assert_eq!(params.h.len() + 1, 4);
+    assert!(matches!(prove_zkey(circuit.clone(), &params, &mut rng), Err(SynthesisError::InconsistentParameters)));
+
+    let (params, _) = read(Cursor::new(&sample_zkey(&circuit.r1cs, &mut rng))).unwrap();
+    let mut short = circuit.clone();
+    short.witness.as_mut().unwrap().pop();
+    assert!(matches!(prove_zkey(short, &params, &mut rng), Err(SynthesisError::AssignmentMissing)));
+    let mut short_mapping = circuit;
+    short_mapping.wire_mapping = Some(vec![0, 1]);
+    assert!(matches!(prove_zkey(short_mapping, &params, &mut rng), Err(SynthesisError::AssignmentMissing)));
+}
+
+#[test]
+fn prove_with_streamed_zkey() {
+    use crate::utils::with_temp_files;
+
+    let path = with_temp_files("streamed", &[("circuit.zkey", &b""[..])], |paths| {
+        let path = &paths[0];
+        for len in [1, 70].iter() {
+            let circuit = square_chain(*len);
+            std::fs::write(path, sample_zkey(&circuit.r1cs, &mut seeded_rng(217))).unwrap();
+            let (params, _) = read(std::fs::File::open(path).unwrap()).unwrap();
+            let zkey = load_streaming(path).unwrap();
+            assert!(*zkey.vk() == params.vk);
+            assert_eq!(zkey.num_variables(), circuit.r1cs.num_variables);
+            assert_eq!(zkey.domain_size(), params.h.len() + 1);
+
+            let inputs = circuit.get_public_inputs().unwrap();
+            let proof = prove_zkey_streaming(circuit.clone(), &zkey, seeded_rng(1)).unwrap();
+            assert!(proof == prove_zkey(circuit, &params, seeded_rng(1)).unwrap());
+            assert!(verify(&params, &proof, &inputs).unwrap());
+        }
+        let circuit = square_chain(1);
+        assert!(matches!(prove_zkey_streaming(square_chain(2), &load_streaming(path).unwrap(), seeded_rng(1)), Err(SynthesisError::InconsistentParameters)));
+        let zkey = sample_zkey(&circuit.r1cs, &mut seeded_rng(2));
+        std::fs::write(path, &zkey[..zkey.len() - 1]).unwrap();
+        assert!(matches!(load_streaming(path), Err(ParseError::TruncatedSection { .. })));
+        path.clone()
+    });
+    assert!(matches!(load_streaming(&path), Err(ParseError::Io { .. })));
+}
+
+#[test]
+fn write_zkey() {
+    let circuit = square_chain(70);
+    let zkey = sample_zkey(&circuit.r1cs, &mut seeded_rng(5));
+    let (params, _) = read(Cursor::new(&zkey)).unwrap();
+    let mut written = vec![];
+    write_streaming(&params, &circuit.r1cs, &mut written).unwrap();
+    assert!(read(Cursor::new(&written)).unwrap().0 == params);
+    // everything up to the points of H is written as snarkjs does
+    let h_start = zkey.len() - params.h.len() * 64 - 64;
+    assert_eq!(zkey.len(), written.len());
+    assert_eq!(zkey[..h_start], written[..h_start]);
+
+    let other = square_chain(1);
+    assert_eq!(write_streaming(&params, &other.r1cs, &mut vec![]).unwrap_err().kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn g2_subgroup_check() {
+    let p = crate::utils::off_subgroup_g2();
+    let mut bytes = vec![];
+    write_g2(&mut bytes, &p).unwrap();
+    assert_eq!(read_g2(&bytes[..]).unwrap_err().to_string(), "G2 point is not in the prime-order subgroup");
+    let mut bytes = vec![];
+    write_g2(&mut bytes, &G2Affine::one()).unwrap();
+    assert!(read_g2(&bytes[..]).unwrap() == G2Affine::one());
+}
+
+#[test]
+fn malformed_zkeys() {
+    let circuit = square_chain(1);
+    let zkey = sample_zkey(&circuit.r1cs, &mut seeded_rng(1));
+
+    let mut bad_magic = zkey.clone();
+    bad_magic[0] = b'r';
+    assert!(matches!(read(Cursor::new(&bad_magic)), Err(ParseError::BadMagic { offset: 0 })));
+
+    let truncated = &zkey[..zkey.len() - 1];
+    assert!(matches!(read(Cursor::new(truncated)), Err(ParseError::TruncatedSection { .. })));
+
+    // a size that wraps around when added to the section's offset
+    let mut overflowing = zkey.clone();
+    overflowing[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(matches!(read(Cursor::new(&overflowing)), Err(ParseError::TruncatedSection { .. })));
+
+    // first coordinate of alpha_1: 12 bytes of file header, 12 + 4 + 12 of sections 1 and 2,
+    // then the two 36-byte primes and three counts
+    let alpha = 12 + 16 + 12 + 36 * 2 + 12;
+    let mut off_curve = zkey.clone();
+    off_curve[alpha] ^= 1;
+    match read(Cursor::new(&off_curve)) {
+        Err(e @ ParseError::Malformed { .. }) => assert_eq!(e.offset(), alpha as u64 + 64),
+        _ => panic!("expected an off-curve error"),
+    }
+}